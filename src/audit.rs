@@ -0,0 +1,146 @@
+//! Compliance audit: lists the spindle speeds every program has now and flags the ones
+//! that need a look. Nothing is written and no change is worked out, so a periodic sweep
+//! of the program library can't touch it.
+
+use crate::encoding::read_program;
+use crate::gcode::{is_plain_number, spindle_speeds};
+use crate::history::csv_row;
+use crate::project::ProjectRules;
+use crate::scan::FileInfo;
+use crate::settings::PathDisplay;
+use serde::Serialize;
+use std::collections::HashMap;
+use std::fmt;
+use std::path::PathBuf;
+
+const CSV_HEADER: &str = "path,root,speeds,anomalies\n";
+
+/// Something about a program's spindle speeds worth a look.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize)]
+#[serde(tag = "anomaly", rename_all = "snake_case")]
+pub enum Anomaly {
+    NoSpindleCommand,
+    /// A speed outside the configured range, as written.
+    OutOfRange {
+        speed: String,
+    },
+    /// A number the parser won't rewrite, such as `1.0E3`.
+    UnsupportedNotation {
+        speed: String,
+    },
+    /// Spindle words at more than one speed.
+    ConflictingSpeeds,
+    /// The file couldn't be read as text.
+    Unreadable {
+        error: String,
+    },
+}
+
+impl fmt::Display for Anomaly {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Anomaly::NoSpindleCommand => write!(f, "no S command"),
+            Anomaly::OutOfRange { speed } => write!(f, "S{} out of range", speed),
+            Anomaly::UnsupportedNotation { speed } => {
+                write!(f, "unsupported notation S{}", speed)
+            }
+            Anomaly::ConflictingSpeeds => write!(f, "several different speeds"),
+            Anomaly::Unreadable { error } => write!(f, "can't be read: {}", error),
+        }
+    }
+}
+
+/// One program as the audit found it.
+#[derive(Clone, Debug, Serialize)]
+pub struct AuditEntry {
+    pub path: PathBuf,
+    #[serde(skip)]
+    pub root: PathBuf,
+    /// Every spindle word's number in file order, as written.
+    pub speeds: Vec<String>,
+    pub anomalies: Vec<Anomaly>,
+}
+
+/// The spindle speeds of `content` and what is odd about them. Speeds are compared as
+/// values, so `S12000` and `S12000.0` don't conflict.
+pub fn audit_content(content: &str, rules: &ProjectRules) -> (Vec<String>, Vec<Anomaly>) {
+    let speeds: Vec<String> = spindle_speeds(content, rules.spindle_letter)
+        .into_iter()
+        .map(str::to_string)
+        .collect();
+    if speeds.is_empty() {
+        return (speeds, vec![Anomaly::NoSpindleCommand]);
+    }
+
+    let mut anomalies = Vec::new();
+    let mut values: Vec<f64> = Vec::new();
+    for speed in &speeds {
+        let value = speed.parse::<f64>().ok().filter(|_| is_plain_number(speed));
+        let Some(value) = value else {
+            anomalies.push(Anomaly::UnsupportedNotation {
+                speed: speed.clone(),
+            });
+            continue;
+        };
+        let in_range = f64::from(*rules.speed_range.start()) <= value
+            && value <= f64::from(*rules.speed_range.end());
+        // S0 stops the spindle rather than setting a speed.
+        if value != 0.0 && !in_range {
+            anomalies.push(Anomaly::OutOfRange {
+                speed: speed.clone(),
+            });
+        }
+        if value != 0.0 && !values.contains(&value) {
+            values.push(value);
+        }
+    }
+    if values.len() > 1 {
+        anomalies.push(Anomaly::ConflictingSpeeds);
+    }
+    (speeds, anomalies)
+}
+
+/// Audits every file of `file_cache`, in path order.
+pub async fn audit_files(
+    file_cache: &HashMap<PathBuf, FileInfo>,
+    rules: &ProjectRules,
+) -> Vec<AuditEntry> {
+    let mut files: Vec<&FileInfo> = file_cache.values().collect();
+    files.sort_by(|a, b| a.path.cmp(&b.path));
+
+    let mut entries = Vec::with_capacity(files.len());
+    for info in files {
+        let (speeds, anomalies) = match read_program(&info.path).await {
+            Ok((content, _)) => audit_content(&content, rules),
+            Err(error) => (
+                Vec::new(),
+                vec![Anomaly::Unreadable {
+                    error: error.to_string(),
+                }],
+            ),
+        };
+        entries.push(AuditEntry {
+            path: info.path.clone(),
+            root: info.root.clone(),
+            speeds,
+            anomalies,
+        });
+    }
+    entries
+}
+
+/// The audit as CSV, one row per file with a header. Several speeds or anomalies share
+/// one field, separated by `;`.
+pub fn audit_csv(entries: &[AuditEntry], path_display: PathDisplay) -> String {
+    let mut csv = CSV_HEADER.to_string();
+    for entry in entries {
+        let anomalies: Vec<String> = entry.anomalies.iter().map(Anomaly::to_string).collect();
+        csv.push_str(&csv_row(&[
+            path_display.show(&entry.path, Some(&entry.root)),
+            entry.root.display().to_string(),
+            entry.speeds.join(";"),
+            anomalies.join(";"),
+        ]));
+    }
+    csv
+}
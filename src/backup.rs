@@ -0,0 +1,268 @@
+//! Copies of programs taken before a run rewrites them, and the manifest that lists them
+//! so they can be restored without the window that made them.
+//!
+//! Restoring to a run puts every file back the way it was before that run, undoing the
+//! later runs with it: each file gets its copy from the earliest run, from the chosen one
+//! on, that backed it up.
+
+use crate::update::write_atomically;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashSet};
+use std::io;
+use std::path::{Path, PathBuf};
+
+/// Written to each selected folder that had files backed up.
+pub const MANIFEST_FILE_NAME: &str = "backup-manifest.json";
+
+/// Where `path` is copied before run `run_id` rewrites it: `part.nc` becomes
+/// `part.nc.<run_id>.bak`, so every run keeps its own generation next to the original.
+pub fn backup_path(path: &Path, run_id: &str) -> PathBuf {
+    let mut name = path.file_name().unwrap_or_default().to_os_string();
+    name.push(format!(".{}.bak", run_id));
+    path.with_file_name(name)
+}
+
+pub fn manifest_path(root: &Path) -> PathBuf {
+    root.join(MANIFEST_FILE_NAME)
+}
+
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+pub struct BackupManifest {
+    /// Oldest run first.
+    pub runs: Vec<ManifestRun>,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct ManifestRun {
+    pub run_id: String,
+    pub files: Vec<BackupEntry>,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct BackupEntry {
+    pub original: PathBuf,
+    pub backup: PathBuf,
+    /// UTC, `YYYYMMDD-HHMMSS`.
+    pub backed_up_at: String,
+}
+
+impl BackupManifest {
+    /// A folder without a manifest yet has an empty one.
+    pub async fn load(root: &Path) -> io::Result<Self> {
+        match tokio::fs::read_to_string(manifest_path(root)).await {
+            Ok(text) => serde_json::from_str(&text)
+                .map_err(|error| io::Error::new(io::ErrorKind::InvalidData, error)),
+            Err(error) if error.kind() == io::ErrorKind::NotFound => Ok(Self::default()),
+            Err(error) => Err(error),
+        }
+    }
+
+    pub async fn save(&self, root: &Path) -> io::Result<()> {
+        let text = serde_json::to_string_pretty(self)
+            .map_err(|error| io::Error::new(io::ErrorKind::InvalidData, error))?;
+        write_atomically(&manifest_path(root), text.as_bytes()).await
+    }
+
+    /// Adds `entry` to the run `run_id`, starting that run if it isn't listed yet.
+    pub fn record(&mut self, run_id: &str, entry: BackupEntry) {
+        match self.runs.iter_mut().find(|run| run.run_id == run_id) {
+            Some(run) => run.files.push(entry),
+            None => self.runs.push(ManifestRun {
+                run_id: run_id.to_string(),
+                files: vec![entry],
+            }),
+        }
+    }
+}
+
+/// A run that has copies in the manifests of the chosen folders.
+#[derive(Clone, Debug, Serialize)]
+pub struct BackedUpRun {
+    pub run_id: String,
+    /// When its first copy was taken, UTC `YYYYMMDD-HHMMSS`.
+    pub started: String,
+    pub files: usize,
+}
+
+/// Every run with copies in the manifests of `roots`, newest first.
+pub async fn backed_up_runs(roots: &[PathBuf]) -> io::Result<Vec<BackedUpRun>> {
+    let mut runs: Vec<BackedUpRun> = Vec::new();
+    for root in roots {
+        for run in BackupManifest::load(root).await?.runs {
+            let started = run
+                .files
+                .first()
+                .map(|entry| entry.backed_up_at.clone())
+                .unwrap_or_default();
+            match runs.iter_mut().find(|known| known.run_id == run.run_id) {
+                Some(known) => {
+                    known.files += run.files.len();
+                    known.started = known.started.clone().min(started);
+                }
+                None => runs.push(BackedUpRun {
+                    run_id: run.run_id,
+                    started,
+                    files: run.files.len(),
+                }),
+            }
+        }
+    }
+    runs.sort_by(|a, b| (&b.started, &b.run_id).cmp(&(&a.started, &a.run_id)));
+    Ok(runs)
+}
+
+/// What restoring does to one file.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum RestoreAction {
+    /// Overwrite the file with its copy.
+    Restore,
+    /// The file was deleted since; it is written again from its copy.
+    Recreate,
+    /// The copy is gone, so the file is left as it is.
+    BackupMissing,
+}
+
+#[derive(Clone, Debug, Serialize)]
+pub struct RestoreItem {
+    pub original: PathBuf,
+    pub backup: PathBuf,
+    #[serde(skip)]
+    pub root: PathBuf,
+    /// The run that took the copy.
+    pub from_run: String,
+    pub action: RestoreAction,
+}
+
+/// What restoring `roots` to before run `run_id` would do, file by file in path order.
+/// In a folder that run didn't back anything up in, the runs that started after it are
+/// undone. Files no run backed up, such as programs added since, are left out.
+pub async fn plan_restore(roots: &[PathBuf], run_id: &str) -> io::Result<Vec<RestoreItem>> {
+    let mut items = Vec::new();
+    for root in roots {
+        let manifest = BackupManifest::load(root).await?;
+        let first = match manifest.runs.iter().position(|run| run.run_id == run_id) {
+            Some(index) => index,
+            // Run IDs start with their UTC start time.
+            None => manifest
+                .runs
+                .iter()
+                .position(|run| run.run_id.as_str() > run_id)
+                .unwrap_or(manifest.runs.len()),
+        };
+        let mut seen = HashSet::new();
+        for run in &manifest.runs[first..] {
+            for entry in &run.files {
+                if !seen.insert(entry.original.clone()) {
+                    continue;
+                }
+                let action = if !entry.backup.is_file() {
+                    RestoreAction::BackupMissing
+                } else if entry.original.exists() {
+                    RestoreAction::Restore
+                } else {
+                    RestoreAction::Recreate
+                };
+                items.push(RestoreItem {
+                    original: entry.original.clone(),
+                    backup: entry.backup.clone(),
+                    root: root.clone(),
+                    from_run: run.run_id.clone(),
+                    action,
+                });
+            }
+        }
+    }
+    items.sort_by(|a, b| a.original.cmp(&b.original));
+    Ok(items)
+}
+
+#[derive(Clone, Debug, Default, Serialize)]
+pub struct RestoreSummary {
+    pub restored: usize,
+    pub recreated: usize,
+    /// Files whose copy was gone.
+    pub missing: Vec<PathBuf>,
+    pub failed: Vec<(PathBuf, String)>,
+}
+
+/// Carries out `items`. Each file that is still there is first backed up itself as run
+/// `restore_run_id`, so the restore can be undone like any other run; a file that can't
+/// be backed up is left alone.
+pub async fn restore_files(items: &[RestoreItem], restore_run_id: &str) -> RestoreSummary {
+    let mut summary = RestoreSummary::default();
+    let mut backups = RunBackups::default();
+    for item in items {
+        if item.action == RestoreAction::BackupMissing {
+            summary.missing.push(item.original.clone());
+            continue;
+        }
+        match restore_file(item, restore_run_id, &mut backups).await {
+            Ok(()) if item.action == RestoreAction::Recreate => summary.recreated += 1,
+            Ok(()) => summary.restored += 1,
+            Err(error) => summary
+                .failed
+                .push((item.original.clone(), error.to_string())),
+        }
+    }
+    if let Err((manifest, error)) = backups.save(restore_run_id).await {
+        summary.failed.push((manifest, error.to_string()));
+    }
+    summary
+}
+
+async fn restore_file(
+    item: &RestoreItem,
+    restore_run_id: &str,
+    backups: &mut RunBackups,
+) -> io::Result<()> {
+    let contents = tokio::fs::read(&item.backup).await?;
+    if item.original.exists() {
+        let entry = BackupEntry {
+            original: item.original.clone(),
+            backup: backup_path(&item.original, restore_run_id),
+            backed_up_at: crate::timestamp::UtcDateTime::now().compact(),
+        };
+        tokio::fs::copy(&item.original, &entry.backup).await?;
+        backups.add(&item.root, entry);
+    } else if let Some(parent) = item.original.parent() {
+        tokio::fs::create_dir_all(parent).await?;
+    }
+    write_atomically(&item.original, &contents).await
+}
+
+/// The files one run backed up, by the folder whose manifest lists them. Each manifest
+/// is loaded and saved once, by [`RunBackups::save`], rather than once per file.
+#[derive(Debug, Default)]
+pub struct RunBackups {
+    roots: BTreeMap<PathBuf, Vec<BackupEntry>>,
+}
+
+impl RunBackups {
+    pub fn add(&mut self, root: &Path, entry: BackupEntry) {
+        self.roots
+            .entry(root.to_path_buf())
+            .or_default()
+            .push(entry);
+    }
+
+    /// Adds the gathered files to the manifest of each folder under run `run_id`. Every
+    /// folder is tried; the first one that failed is returned with its error.
+    pub async fn save(self, run_id: &str) -> Result<(), (PathBuf, io::Error)> {
+        let mut first_error = None;
+        for (root, entries) in self.roots {
+            let result = async {
+                let mut manifest = BackupManifest::load(&root).await?;
+                for entry in entries {
+                    manifest.record(run_id, entry);
+                }
+                manifest.save(&root).await
+            }
+            .await;
+            if let Err(error) = result {
+                first_error.get_or_insert((manifest_path(&root), error));
+            }
+        }
+        first_error.map_or(Ok(()), Err)
+    }
+}
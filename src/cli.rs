@@ -0,0 +1,1030 @@
+//! Headless mode, used when the program is started with arguments:
+//!
+//! ```text
+//! spindle_speed_manager (--speed <RPM> | --mapping <CSV> | --speed-rules | --audit <CSV>
+//!         | --list-backups | --restore <RUN ID>)
+//!     [--folder <PATH>]... [--follow-symlinks]
+//!     [--write-delay-ms <MS>] [--max-failure-percent <N>] [--force] [--json]
+//!     [--io-retries <N>] [--match-current <RPM>] [--trim-rewritten] [--uppercase-m-codes]
+//!     [--pad-speed-width] [--spindle-word-first] [--no-structure-check] [--speed-override <MARKER>] [--change-stamp <TEMPLATE>]
+//!     [--max-files <N>]
+//!     [--verify-writes] [--backup] [--preserve-metadata] [--after-tool-change]
+//!     [--each-tool-change] [--spindle <main|N|all>]
+//!     [--speed-drop-percent <N>] [--patch <FILE>] [--stdin] [--history <FILE>]
+//!     [--chunk-size <N>] [--require-s] [--modified-within-days <N>]
+//!     [--output-folder <PATH> [--copy-unchanged]]
+//! ```
+//!
+//! Every file is reported on its own line prefixed with `CHANGED`, `UNCHANGED`,
+//! `SKIPPED` or `ERROR`; `--json` prints one JSON document with the same information.
+//!
+//! `--self-test` reads and writes no files: it runs the parser on the post samples built
+//! into the binary and prints `PASS` or `FAIL` per sample, for checking a deployed copy.
+//! It is left out of the synopsis above as a support tool.
+//! `--compare <OLD> <NEW>` writes nothing: it lists the files of two folders whose
+//! spindle speeds differ, and the files present in only one of them.
+//! `--audit <CSV>` changes nothing either: it writes every file's current spindle speeds
+//! to the CSV and flags files without an S command, with a speed out of range or in an
+//! unsupported notation, or with more than one speed. It exits with
+//! [`SpindleSpeedUpdaterError::AuditFindings`] when any file is flagged. Only
+//! `--folder`, `--follow-symlinks`, `--modified-within-days` and `--json` apply to it.
+//! `--list-backups` lists the runs in the folders' `backup-manifest.json`, newest first.
+//! `--restore <RUN ID>` puts every backed-up file back the way it was before that run,
+//! undoing the runs after it too, and prints what it did with each file. Only `--folder`
+//! and `--json` apply to either.
+//! `--match-current` leaves every file alone whose first spindle command isn't already at
+//! that speed, for targeted changes such as moving all 10000 RPM programs to 12000.
+//! `--mapping` applies per-file speeds from a `filename,rpm` CSV instead of one speed.
+//! `--speed-rules` gives each file the speed of the first `[[speed_rules]]` pattern in the
+//! project config that matches its name, and skips files matching none; which rule
+//! applied to each file is listed before the run.
+//! Before writing, a dry pass refuses the run when more than `--max-failure-percent`
+//! of the files would fail; `--force` runs regardless. `--max-files` refuses any run that
+//! would modify more files than that, and `--force` does not override it. Neither does
+//! it override `--require-s`, which refuses the run when any targeted file has no S
+//! command and lists those files.
+//! A run that would take most files below `--speed-drop-percent` (default 50, 0 turns the
+//! check off) of their current speed is refused too, unless `--force` is given.
+//! `--spindle 2` updates only `S2=` words on multi-spindle machines, `--spindle all`
+//! every indexed spindle; the default `main` updates the bare `S` word.
+//! A spindle line that is reformatted keeps its other words, such as a gear range `M42`,
+//! its comment, and its own `M3` or `M4`, recognised in either case;
+//! `--uppercase-m-codes` writes that in capitals.
+//! `--pad-speed-width` zero-pads the new speed to the digit count of the old one, so
+//! `S01000` becomes `S01200`, for controllers that expect fixed-width fields.
+//! `--spindle-word-first` writes a block such as `M3 S1000` in the order `S12000 M3`;
+//! without it the words keep the order the post wrote them in.
+//! Every update is checked to change nothing but spindle lines (and the one line an
+//! insertion adds) before it is written; a file that fails is reported as an error and
+//! left alone, since only a parser bug gets there. `--no-structure-check` turns that off.
+//! `--speed-override "SPEED OVERRIDE"` scales the speed of each file that has a comment
+//! such as `(SPEED OVERRIDE 80%)` by that percentage; other files get the speed as given.
+//! `--change-stamp "(SPEED SET TO {speed} ON {date})"` puts that comment, filled in, at
+//! the top of every changed file, replacing a stamp an earlier run left there. The
+//! template has to be a comment.
+//! `--each-tool-change` updates the first spindle word after every `M6` instead of only
+//! the first in the file, leaving later overrides and everything before the first `M6`.
+//! `--after-tool-change` leaves spindle words before the first `T` word or `M6` alone
+//! and skips programs without a tool change.
+//! `--patch <FILE>` writes nothing but the dry pass, as a unified diff with paths
+//! relative to the folder, and exits.
+//! `--backup` copies each file to `<name>.<run ID>.bak` before changing it and lists the
+//! copies in `backup-manifest.json` in the folder.
+//! `--output-folder <PATH>` writes updated files there, laid out like the folder they
+//! came from (under a subfolder per folder when several are given), and leaves the
+//! originals alone; `--backup` does nothing then. `--copy-unchanged` also copies the files
+//! that need no change, so the output folder holds the whole batch.
+//! `--preserve-metadata` gives each rewritten file back its modified time, and on Unix
+//! its permissions.
+//! `--verify-writes` reads every written file back and reports it as an error unless it
+//! holds exactly what was written.
+//! Every run is appended as one row to `runs.csv` next to the executable, or to the file
+//! given with `--history`.
+//! `--chunk-size <N>` appends every file's result to `run-<run ID>.csv` beside the run
+//! history each N files and lists only the changed and failed files at the end, so a run
+//! over a very large folder doesn't hold every result in memory.
+//! `--modified-within-days <N>` leaves out files last modified N or more days ago, such as
+//! archived programs, and reports how many are left.
+//! `--stdin` processes exactly the files listed on standard input, one path per line,
+//! instead of walking a folder: `fd -e tap | spindle_speed_manager --speed 12000 --stdin`.
+//! Paths that aren't files or lack a supported extension are reported and left out.
+//! `--folder` may be repeated to process several folders in one run; without it the
+//! executable's folder is processed, like the GUI does. The
+//! process exits with [`SpindleSpeedUpdaterError::exit_code`] on failure.
+
+use crate::audit::{audit_csv, audit_files};
+use crate::backup::{backed_up_runs, plan_restore, restore_files, RestoreAction};
+use crate::compare::{compare_folders, FolderComparison};
+use crate::gcode::{ChangeStamp, SpeedOverride, SpindleTarget, UpdateOptions};
+use crate::history::{append_run, history_path};
+use crate::mapping::{compare_mapping, load_mapping, rule_for, SpeedPlan};
+use crate::patch::build_patch;
+use crate::preview::{build_preview, PreflightSummary};
+use crate::progress::Progress;
+use crate::project::{load_project_rules, ProjectConfigStatus, ProjectRules, CONFIG_FILE_NAME};
+use crate::report::{RunReport, RunTotals};
+use crate::safety::{
+    file_cap_refusal, missing_spindle_refusal, preflight_warning, speed_drop_warning,
+};
+use crate::scan::{
+    dedupe_roots, executable_folder, list_files, modified_within, pin_to_preview, scan_folders,
+    FileList,
+};
+use crate::self_test::run_self_test;
+use crate::settings::{PathDisplay, Settings};
+use crate::speed::{format_rpm, parse_spindle_speed};
+use crate::strings::{fill, Language, Text};
+use crate::summary::{matched_count, skip_breakdown, summarize_by_root};
+use crate::timestamp::UtcDateTime;
+use crate::update::{
+    new_run_id, overlapping_folder, result_chunks, update_spindle_speed, FileOutcome, FileResult,
+    OutputFolder, RunOptions,
+};
+use crate::SpindleSpeedUpdaterError;
+use serde::Serialize;
+use std::collections::BTreeMap;
+use std::io::BufRead;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use std::sync::Arc;
+use std::time::{Duration, Instant, SystemTime};
+use tokio::sync::oneshot;
+
+enum Target {
+    Speed(String),
+    Mapping(PathBuf),
+    /// The speed rules of the project config.
+    Rules,
+    /// Audit two folders instead of updating anything.
+    Compare(PathBuf, PathBuf),
+    /// Report the current speeds of the folders to a CSV instead of updating anything.
+    Audit(PathBuf),
+    /// Check the parser against the built-in samples.
+    SelfTest,
+    /// List the runs with backups, or restore to before one with `Some` run ID.
+    Restore(Option<String>),
+}
+
+pub struct CliArgs {
+    target: Target,
+    folders: Vec<PathBuf>,
+    follow_symlinks: bool,
+    write_delay_ms: u64,
+    max_failure_percent: u8,
+    force: bool,
+    json: bool,
+    io_retries: u32,
+    match_current_speed: Option<u32>,
+    trim_rewritten_lines: bool,
+    uppercase_m_codes: bool,
+    pad_speed_width: bool,
+    spindle_word_first: bool,
+    check_structure: bool,
+    /// Comment text before a per-file percentage to scale the speed by.
+    speed_override: Option<String>,
+    /// Comment template stamped on changed files.
+    change_stamp: Option<String>,
+    max_files_to_modify: Option<usize>,
+    require_spindle_command: bool,
+    verify_writes: bool,
+    create_backups: bool,
+    preserve_metadata: bool,
+    spindle_target: SpindleTarget,
+    after_tool_change: bool,
+    each_tool_change: bool,
+    /// 0 disables the check.
+    speed_drop_percent: u8,
+    /// Export the dry pass as a patch here instead of updating.
+    patch: Option<PathBuf>,
+    /// Take the files from stdin instead of scanning `folders`.
+    stdin: bool,
+    /// Append the run here instead of to the default `runs.csv`.
+    history: Option<PathBuf>,
+    /// Write the results out every this many files; 0 keeps them all until the end.
+    chunk_size: usize,
+    /// Only process files modified within this many days.
+    modified_within_days: Option<u32>,
+    /// Write updated files here instead of over the originals.
+    output_folder: Option<PathBuf>,
+    copy_unchanged: bool,
+}
+
+#[derive(Serialize)]
+struct CliReport {
+    run_id: String,
+    /// See [`crate::fingerprint::change_hash`].
+    change_hash: String,
+    /// Skipped files per reason, e.g. `{"no S command": 3}`.
+    skipped: BTreeMap<&'static str, usize>,
+    /// Every file, or only the changed and failed ones when `results_file` is set.
+    files: Vec<FileResult>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    results_file: Option<PathBuf>,
+    #[serde(skip)]
+    totals: RunTotals,
+}
+
+/// Returns `None` when there are no arguments and the GUI should start instead.
+pub fn parse_args(
+    mut args: impl Iterator<Item = String>,
+) -> Option<Result<CliArgs, SpindleSpeedUpdaterError>> {
+    let first = args.next()?;
+    Some(parse_flags(std::iter::once(first).chain(args)))
+}
+
+fn parse_flags(
+    mut args: impl Iterator<Item = String>,
+) -> Result<CliArgs, SpindleSpeedUpdaterError> {
+    let defaults = Settings::default();
+    let mut target = None;
+    let mut folders = Vec::new();
+    let mut follow_symlinks = false;
+    let mut write_delay_ms = defaults.write_delay_ms;
+    let mut max_failure_percent = defaults.max_failure_percent;
+    let mut force = false;
+    let mut json = false;
+    let mut io_retries = defaults.io_retries;
+    let mut match_current_speed = None;
+    let mut trim_rewritten_lines = defaults.trim_rewritten_lines;
+    let mut uppercase_m_codes = defaults.uppercase_m_codes;
+    let mut pad_speed_width = defaults.pad_speed_width;
+    let mut spindle_word_first = defaults.spindle_word_first;
+    let mut check_structure = defaults.check_structure;
+    let mut speed_override = None;
+    let mut change_stamp = None;
+    let mut max_files_to_modify = None;
+    let mut require_spindle_command = defaults.require_spindle_command;
+    let mut verify_writes = defaults.verify_writes;
+    let mut create_backups = defaults.create_backups;
+    let mut preserve_metadata = defaults.preserve_metadata;
+    let mut spindle_target = defaults.spindle_target;
+    let mut after_tool_change = defaults.after_tool_change;
+    let mut each_tool_change = defaults.each_tool_change;
+    let mut patch = None;
+    let mut stdin = false;
+    let mut history = defaults.run_history_file;
+    let mut chunk_size = defaults.result_chunk_size;
+    let mut modified_within_days = None;
+    let mut output_folder = None;
+    let mut copy_unchanged = false;
+    let mut speed_drop_percent = if defaults.warn_on_speed_drop {
+        defaults.speed_drop_percent
+    } else {
+        0
+    };
+
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--speed" => target = Some(Target::Speed(flag_value(&arg, args.next())?)),
+            "--compare" => {
+                let old = PathBuf::from(flag_value(&arg, args.next())?);
+                let new = PathBuf::from(flag_value(&arg, args.next())?);
+                target = Some(Target::Compare(old, new));
+            }
+            "--self-test" => target = Some(Target::SelfTest),
+            "--speed-rules" => target = Some(Target::Rules),
+            "--list-backups" => target = Some(Target::Restore(None)),
+            "--restore" => target = Some(Target::Restore(Some(flag_value(&arg, args.next())?))),
+            "--audit" => {
+                target = Some(Target::Audit(PathBuf::from(flag_value(&arg, args.next())?)))
+            }
+            "--mapping" => {
+                target = Some(Target::Mapping(PathBuf::from(flag_value(
+                    &arg,
+                    args.next(),
+                )?)))
+            }
+            "--folder" => folders.push(PathBuf::from(flag_value(&arg, args.next())?)),
+            "--follow-symlinks" => follow_symlinks = true,
+            "--write-delay-ms" => write_delay_ms = parse_number(&arg, args.next())?,
+            "--max-failure-percent" => max_failure_percent = parse_number(&arg, args.next())?,
+            "--force" => force = true,
+            "--json" => json = true,
+            "--trim-rewritten" => trim_rewritten_lines = true,
+            "--uppercase-m-codes" => uppercase_m_codes = true,
+            "--pad-speed-width" => pad_speed_width = true,
+            "--spindle-word-first" => spindle_word_first = true,
+            "--no-structure-check" => check_structure = false,
+            "--speed-override" => speed_override = Some(flag_value(&arg, args.next())?),
+            "--change-stamp" => change_stamp = Some(flag_value(&arg, args.next())?),
+            "--io-retries" => io_retries = parse_number(&arg, args.next())?,
+            "--verify-writes" => verify_writes = true,
+            "--backup" => create_backups = true,
+            "--preserve-metadata" => preserve_metadata = true,
+            "--after-tool-change" => after_tool_change = true,
+            "--each-tool-change" => each_tool_change = true,
+            "--speed-drop-percent" => speed_drop_percent = parse_number(&arg, args.next())?,
+            "--stdin" => stdin = true,
+            "--history" => history = Some(PathBuf::from(flag_value(&arg, args.next())?)),
+            "--chunk-size" => chunk_size = parse_number(&arg, args.next())?,
+            "--modified-within-days" => {
+                modified_within_days = Some(parse_number(&arg, args.next())?)
+            }
+            "--output-folder" => {
+                output_folder = Some(PathBuf::from(flag_value(&arg, args.next())?))
+            }
+            "--copy-unchanged" => copy_unchanged = true,
+            "--patch" => patch = Some(PathBuf::from(flag_value(&arg, args.next())?)),
+            "--spindle" => spindle_target = parse_spindle_target(&arg, args.next())?,
+            "--max-files" => max_files_to_modify = Some(parse_number(&arg, args.next())?),
+            "--require-s" => require_spindle_command = true,
+            "--match-current" => match_current_speed = Some(parse_number(&arg, args.next())?),
+            _ => {
+                return Err(SpindleSpeedUpdaterError::InvalidArgument(format!(
+                    "unrecognised argument '{}'",
+                    arg
+                )))
+            }
+        }
+    }
+
+    let target = target.ok_or_else(|| {
+        SpindleSpeedUpdaterError::InvalidArgument(
+            "--speed <RPM>, --mapping <CSV> or --compare <OLD> <NEW> is required".to_string(),
+        )
+    })?;
+    if copy_unchanged && output_folder.is_none() {
+        return Err(SpindleSpeedUpdaterError::InvalidArgument(
+            "--copy-unchanged needs --output-folder".to_string(),
+        ));
+    }
+    if let Some(template) = change_stamp
+        .as_ref()
+        .filter(|template| !ChangeStamp::is_comment(template))
+    {
+        return Err(SpindleSpeedUpdaterError::InvalidArgument(format!(
+            "--change-stamp expects a comment, (...) or ; ..., got '{}'",
+            template
+        )));
+    }
+
+    Ok(CliArgs {
+        target,
+        folders,
+        follow_symlinks,
+        write_delay_ms,
+        max_failure_percent,
+        force,
+        json,
+        io_retries,
+        match_current_speed,
+        trim_rewritten_lines,
+        uppercase_m_codes,
+        pad_speed_width,
+        spindle_word_first,
+        check_structure,
+        speed_override,
+        change_stamp,
+        max_files_to_modify,
+        require_spindle_command,
+        verify_writes,
+        create_backups,
+        preserve_metadata,
+        spindle_target,
+        after_tool_change,
+        each_tool_change,
+        speed_drop_percent,
+        patch,
+        stdin,
+        history,
+        chunk_size,
+        modified_within_days,
+        output_folder,
+        copy_unchanged,
+    })
+}
+
+fn flag_value(flag: &str, value: Option<String>) -> Result<String, SpindleSpeedUpdaterError> {
+    value.ok_or_else(|| {
+        SpindleSpeedUpdaterError::InvalidArgument(format!("{} expects a value", flag))
+    })
+}
+
+fn parse_number<T: FromStr>(
+    flag: &str,
+    value: Option<String>,
+) -> Result<T, SpindleSpeedUpdaterError> {
+    let value = flag_value(flag, value)?;
+    value.parse().map_err(|_| {
+        SpindleSpeedUpdaterError::InvalidArgument(format!(
+            "{} expects a number, got '{}'",
+            flag, value
+        ))
+    })
+}
+
+/// `main` for the bare S word, `all` for every indexed spindle, or a spindle number.
+fn parse_spindle_target(
+    flag: &str,
+    value: Option<String>,
+) -> Result<SpindleTarget, SpindleSpeedUpdaterError> {
+    let value = flag_value(flag, value)?;
+    match value.as_str() {
+        "main" => Ok(SpindleTarget::Main),
+        "all" => Ok(SpindleTarget::AllIndexed),
+        _ => value.parse().map(SpindleTarget::Indexed).map_err(|_| {
+            SpindleSpeedUpdaterError::InvalidArgument(format!(
+                "{} expects main, all or a spindle number, got '{}'",
+                flag, value
+            ))
+        }),
+    }
+}
+
+/// Runs the headless update and returns the process exit code.
+pub async fn run(args: Result<CliArgs, SpindleSpeedUpdaterError>) -> i32 {
+    attach_console();
+    let json = args.as_ref().is_ok_and(|args| args.json);
+
+    if let Ok(CliArgs {
+        target: Target::Compare(old, new),
+        follow_symlinks,
+        ..
+    }) = &args
+    {
+        let (rules, _) = load_project_rules(&[old.clone(), new.clone()]);
+        let result = compare_folders(old, new, *follow_symlinks, &rules)
+            .await
+            .map(|comparison| print_comparison(&comparison, json));
+        return exit_code(result, json);
+    }
+
+    if let Ok(CliArgs {
+        target: Target::SelfTest,
+        ..
+    }) = &args
+    {
+        let result = match run_self_test() {
+            0 => Ok(()),
+            failed => Err(SpindleSpeedUpdaterError::SelfTestFailed(failed)),
+        };
+        return exit_code(result, json);
+    }
+
+    if let Ok(
+        audit @ CliArgs {
+            target: Target::Audit(csv),
+            ..
+        },
+    ) = &args
+    {
+        return exit_code(run_audit(audit, csv).await, json);
+    }
+
+    if let Ok(
+        restore @ CliArgs {
+            target: Target::Restore(run_id),
+            ..
+        },
+    ) = &args
+    {
+        return exit_code(run_restore(restore, run_id.as_deref()).await, json);
+    }
+
+    let result = match run_update(args).await {
+        Ok(None) => Ok(()),
+        Ok(Some(report)) => {
+            print_report(&report, json);
+            match report.totals.failed {
+                0 => Ok(()),
+                failed => Err(SpindleSpeedUpdaterError::FilesFailed(failed)),
+            }
+        }
+        Err(error) => Err(error),
+    };
+    exit_code(result, json)
+}
+
+fn exit_code(result: Result<(), SpindleSpeedUpdaterError>, json: bool) -> i32 {
+    match result {
+        Ok(()) => 0,
+        Err(error) => {
+            if json {
+                println!(
+                    "{}",
+                    serde_json::json!({ "error": error.to_string(), "exit_code": error.exit_code() })
+                );
+            } else {
+                eprintln!("{}", error);
+            }
+            error.exit_code()
+        }
+    }
+}
+
+fn print_report(report: &CliReport, json: bool) {
+    if json {
+        match serde_json::to_string_pretty(report) {
+            Ok(document) => println!("{}", document),
+            Err(error) => eprintln!("Failed to serialise report: {}", error),
+        }
+        return;
+    }
+
+    for file in &report.files {
+        match &file.outcome {
+            FileOutcome::Skipped(reason) => println!(
+                "{:<9} {} ({})",
+                file.outcome.label(),
+                file.path.display(),
+                reason
+            ),
+            FileOutcome::Error(detail) => println!(
+                "{:<9} {} ({})",
+                file.outcome.label(),
+                file.path.display(),
+                detail
+            ),
+            _ => println!("{:<9} {}", file.outcome.label(), file.path.display()),
+        }
+    }
+    println!("Processed {} files", report.totals.processed());
+    if let Some(reasons) = skip_breakdown(&report.totals) {
+        println!("Skipped: {}", reasons);
+    }
+    if let Some(path) = &report.results_file {
+        println!("Per-file results: {}", path.display());
+    }
+    println!("Change hash: {}", report.change_hash);
+    let by_root = summarize_by_root(&report.totals);
+    if by_root.len() > 1 {
+        for (root, summary) in by_root {
+            println!("  {}: {}", root.display(), summary);
+        }
+    }
+}
+
+/// Audits the folders of `args`, writes every file's row to `csv` and prints them.
+async fn run_audit(args: &CliArgs, csv: &Path) -> Result<(), SpindleSpeedUpdaterError> {
+    let folders = if args.folders.is_empty() {
+        vec![executable_folder()?]
+    } else {
+        dedupe_roots(&args.folders)
+    };
+    let (rules, _) = load_project_rules(&folders);
+    let (mut file_cache, _) = scan_folders(&folders, args.follow_symlinks, &rules)?;
+    if let Some(days) = args.modified_within_days {
+        file_cache = modified_within(&file_cache, Some(days), SystemTime::now());
+    }
+    let entries = audit_files(&file_cache, &rules).await;
+    tokio::fs::write(csv, audit_csv(&entries, PathDisplay::default()))
+        .await
+        .map_err(SpindleSpeedUpdaterError::file_io(csv))?;
+    let flagged = entries
+        .iter()
+        .filter(|entry| !entry.anomalies.is_empty())
+        .count();
+
+    if args.json {
+        match serde_json::to_string_pretty(&entries) {
+            Ok(document) => println!("{}", document),
+            Err(error) => eprintln!("Failed to serialise audit: {}", error),
+        }
+    } else {
+        for entry in &entries {
+            let speeds: Vec<String> = entry
+                .speeds
+                .iter()
+                .map(|speed| format!("S{}", speed))
+                .collect();
+            if entry.anomalies.is_empty() {
+                println!("{:<9} {} {}", "OK", entry.path.display(), speeds.join(", "));
+            } else {
+                let anomalies: Vec<String> =
+                    entry.anomalies.iter().map(ToString::to_string).collect();
+                println!(
+                    "{:<9} {} {} ({})",
+                    "FLAGGED",
+                    entry.path.display(),
+                    speeds.join(", "),
+                    anomalies.join(", ")
+                );
+            }
+        }
+        println!(
+            "Audited {} files, {} flagged; results in {}",
+            entries.len(),
+            flagged,
+            csv.display()
+        );
+    }
+    match flagged {
+        0 => Ok(()),
+        flagged => Err(SpindleSpeedUpdaterError::AuditFindings(flagged)),
+    }
+}
+
+/// Lists the backed-up runs of the folders of `args`, or restores them to before `run_id`.
+async fn run_restore(args: &CliArgs, run_id: Option<&str>) -> Result<(), SpindleSpeedUpdaterError> {
+    let folders = if args.folders.is_empty() {
+        vec![executable_folder()?]
+    } else {
+        dedupe_roots(&args.folders)
+    };
+    let runs = backed_up_runs(&folders).await?;
+    let Some(run_id) = run_id else {
+        if args.json {
+            match serde_json::to_string_pretty(&runs) {
+                Ok(document) => println!("{}", document),
+                Err(error) => eprintln!("Failed to serialise the runs: {}", error),
+            }
+        } else {
+            for run in &runs {
+                println!("{}  {} files backed up", run.run_id, run.files);
+            }
+            println!("{} runs with backups", runs.len());
+        }
+        return Ok(());
+    };
+    if !runs.iter().any(|run| run.run_id == run_id) {
+        return Err(SpindleSpeedUpdaterError::InvalidArgument(format!(
+            "no backups of run {} in the selected folders",
+            run_id
+        )));
+    }
+
+    let items = plan_restore(&folders, run_id).await?;
+    let restore_run_id = new_run_id();
+    note(
+        args.json,
+        format!(
+            "Restoring to before run {}; the files as they are now are backed up as run {}",
+            run_id, restore_run_id
+        ),
+    );
+    let summary = restore_files(&items, &restore_run_id).await;
+    if args.json {
+        let document = serde_json::json!({
+            "run_id": restore_run_id,
+            "files": items,
+            "summary": summary,
+        });
+        match serde_json::to_string_pretty(&document) {
+            Ok(document) => println!("{}", document),
+            Err(error) => eprintln!("Failed to serialise the restore: {}", error),
+        }
+    } else {
+        for item in &items {
+            let failure = summary
+                .failed
+                .iter()
+                .find(|(path, _)| *path == item.original);
+            match (failure, item.action) {
+                (Some((_, error)), _) => {
+                    println!("{:<9} {} ({})", "ERROR", item.original.display(), error)
+                }
+                (None, RestoreAction::BackupMissing) => println!(
+                    "{:<9} {} (copy {} is gone)",
+                    "MISSING",
+                    item.original.display(),
+                    item.backup.display()
+                ),
+                (None, RestoreAction::Recreate) => println!(
+                    "{:<9} {} (from run {})",
+                    "RECREATED",
+                    item.original.display(),
+                    item.from_run
+                ),
+                (None, RestoreAction::Restore) => println!(
+                    "{:<9} {} (from run {})",
+                    "RESTORED",
+                    item.original.display(),
+                    item.from_run
+                ),
+            }
+        }
+        println!(
+            "Restored {} files, recreated {}, {} copies missing, {} failed",
+            summary.restored,
+            summary.recreated,
+            summary.missing.len(),
+            summary.failed.len()
+        );
+    }
+    match summary.failed.len() + summary.missing.len() {
+        0 => Ok(()),
+        failed => Err(SpindleSpeedUpdaterError::FilesFailed(failed)),
+    }
+}
+
+fn print_comparison(comparison: &FolderComparison, json: bool) {
+    if json {
+        match serde_json::to_string_pretty(comparison) {
+            Ok(document) => println!("{}", document),
+            Err(error) => eprintln!("Failed to serialise comparison: {}", error),
+        }
+        return;
+    }
+
+    let speed = |speed: &Option<String>| match speed {
+        Some(number) => format!("S{}", number),
+        None => "no S".to_string(),
+    };
+    for difference in &comparison.differences {
+        println!(
+            "{:<9} {}: {} -> {}",
+            "DIFFERS",
+            difference.name,
+            speed(&difference.old_speed),
+            speed(&difference.new_speed)
+        );
+    }
+    for name in &comparison.only_in_old {
+        println!("{:<9} {}", "OLD ONLY", name);
+    }
+    for name in &comparison.only_in_new {
+        println!("{:<9} {}", "NEW ONLY", name);
+    }
+    println!(
+        "{} files differ, {} match, {} only in the old folder, {} only in the new folder",
+        comparison.differences.len(),
+        comparison.matching,
+        comparison.only_in_old.len(),
+        comparison.only_in_new.len()
+    );
+}
+
+/// Informational output that isn't part of the report goes to stderr in JSON mode so
+/// stdout stays a single parseable document.
+fn note(json: bool, message: String) {
+    if json {
+        eprintln!("{}", message);
+    } else {
+        println!("{}", message);
+    }
+}
+
+/// `None` when only a patch was exported.
+async fn run_update(
+    args: Result<CliArgs, SpindleSpeedUpdaterError>,
+) -> Result<Option<CliReport>, SpindleSpeedUpdaterError> {
+    let args = args?;
+    let run_id = new_run_id();
+    let started = (UtcDateTime::now(), Instant::now());
+    note(args.json, format!("Run ID: {}", run_id));
+
+    let folders = if !args.folders.is_empty() {
+        dedupe_roots(&args.folders)
+    } else if args.stdin {
+        vec![std::env::current_dir()?]
+    } else {
+        vec![executable_folder()?]
+    };
+    if let Some(output) = &args.output_folder {
+        if let Some(folder) = overlapping_folder(output, &folders) {
+            return Err(SpindleSpeedUpdaterError::InvalidArgument(fill(
+                Text::OutputOverlapsFolder.get(Language::English),
+                &[&output.display(), &folder.display()],
+            )));
+        }
+        note(
+            args.json,
+            format!("Writing updated files to {}", output.display()),
+        );
+    }
+    let (rules, config) = load_project_rules(&folders);
+    match config {
+        ProjectConfigStatus::None => {}
+        ProjectConfigStatus::Loaded(path) => note(
+            args.json,
+            format!("Using project config {}", path.display()),
+        ),
+        ProjectConfigStatus::Invalid(path, error) => eprintln!(
+            "Ignoring project config {} ({}); using the defaults",
+            path.display(),
+            error
+        ),
+    }
+    let (mut file_cache, duplicates) = if args.stdin {
+        let listed = read_file_list(std::io::stdin().lock(), &folders, &rules)?;
+        for rejected in &listed.rejected {
+            note(args.json, rejected.clone());
+        }
+        (listed.file_cache, listed.duplicates)
+    } else {
+        scan_folders(&folders, args.follow_symlinks, &rules)?
+    };
+    for path in &duplicates {
+        note(
+            args.json,
+            format!(
+                "Same file as one already found, processed once: {}",
+                path.display()
+            ),
+        );
+    }
+    if let Some(days) = args.modified_within_days {
+        let found = file_cache.len();
+        file_cache = modified_within(&file_cache, Some(days), SystemTime::now());
+        note(
+            args.json,
+            format!(
+                "{} of {} files were modified in the last {} days",
+                file_cache.len(),
+                found,
+                days
+            ),
+        );
+    }
+
+    let (plan, target) = match args.target {
+        Target::Speed(input) => {
+            let speed = parse_spindle_speed(&input, &rules.speed_range, Language::English)
+                .map_err(|error| {
+                    SpindleSpeedUpdaterError::InvalidSpindleSpeed(error.to_string())
+                })?;
+            (SpeedPlan::Uniform(speed), format_rpm(speed))
+        }
+        Target::Compare(..) | Target::SelfTest | Target::Audit(_) | Target::Restore(_) => {
+            return Err(SpindleSpeedUpdaterError::InvalidArgument(
+                "--compare, --self-test, --audit and --restore aren't speed updates".to_string(),
+            ))
+        }
+        Target::Mapping(path) => {
+            let mapping = load_mapping(&path, &rules.speed_range)?;
+            let report = compare_mapping(&mapping.speeds, &file_cache);
+            for name in &report.missing_files {
+                note(args.json, format!("Not found: {}", name));
+            }
+            for path in &report.unmapped_files {
+                note(args.json, format!("Not in mapping: {}", path.display()));
+            }
+            let target = format!(
+                "the speeds listed in {}",
+                path.file_name().unwrap_or_default().to_string_lossy()
+            );
+            (SpeedPlan::PerFile(mapping.speeds), target)
+        }
+        Target::Rules => {
+            if rules.speed_rules.is_empty() {
+                return Err(SpindleSpeedUpdaterError::InvalidArgument(format!(
+                    "--speed-rules needs [[speed_rules]] in {}",
+                    CONFIG_FILE_NAME
+                )));
+            }
+            let mut paths: Vec<&PathBuf> = file_cache.keys().collect();
+            paths.sort();
+            for path in paths {
+                match rule_for(&rules.speed_rules, path) {
+                    Some((index, rule)) => note(
+                        args.json,
+                        format!(
+                            "Rule {} ({}, {}): {}",
+                            index + 1,
+                            rule.pattern,
+                            format_rpm(rule.speed),
+                            path.display()
+                        ),
+                    ),
+                    None => note(args.json, format!("No matching rule: {}", path.display())),
+                }
+            }
+            let target = format!("the speed rules in {}", CONFIG_FILE_NAME);
+            (
+                SpeedPlan::Rules(Arc::new(rules.speed_rules.clone())),
+                target,
+            )
+        }
+    };
+
+    let options = RunOptions {
+        write_delay: Duration::from_millis(args.write_delay_ms),
+        io_retries: args.io_retries,
+        verify_writes: args.verify_writes,
+        create_backups: args.create_backups,
+        preserve_metadata: args.preserve_metadata,
+        // Scripts get full paths, whatever the window is set to.
+        result_chunks: result_chunks(
+            args.chunk_size,
+            args.history.as_deref(),
+            PathDisplay::Absolute,
+        ),
+        output_folder: args.output_folder.clone().map(|folder| OutputFolder {
+            folder,
+            copy_unchanged: args.copy_unchanged,
+            nest_by_root: folders.len() > 1,
+        }),
+        update: UpdateOptions {
+            match_current_speed: args.match_current_speed,
+            spindle_on: rules.spindle_on.clone(),
+            trim_rewritten_lines: args.trim_rewritten_lines,
+            uppercase_m_codes: args.uppercase_m_codes,
+            pad_speed_width: args.pad_speed_width,
+            spindle_word_first: args.spindle_word_first,
+            check_structure: args.check_structure,
+            spindle_target: args.spindle_target,
+            spindle_letter: rules.spindle_letter,
+            after_tool_change: args.after_tool_change,
+            each_tool_change: args.each_tool_change,
+            speed_override: args.speed_override.clone().map(|marker| SpeedOverride {
+                marker,
+                speed_range: rules.speed_range.clone(),
+            }),
+            change_stamp: args.change_stamp.clone().map(|template| ChangeStamp {
+                template,
+                date: UtcDateTime::now().date(),
+            }),
+            ..UpdateOptions::default()
+        },
+    };
+
+    let entries = build_preview(&plan, options.update.clone(), &file_cache).await;
+    pin_to_preview(&mut file_cache, &entries);
+    if let Some(path) = &args.patch {
+        let patch = build_patch(&plan, &options.update, &entries, &file_cache).await?;
+        tokio::fs::write(path, patch)
+            .await
+            .map_err(SpindleSpeedUpdaterError::file_io(path))?;
+        let summary = PreflightSummary::from_entries(&entries);
+        note(
+            args.json,
+            format!(
+                "Wrote the changes to {} files to {}",
+                summary.changing,
+                path.display()
+            ),
+        );
+        return Ok(None);
+    }
+    let summary = PreflightSummary::from_entries(&entries);
+    if let Some(refusal) = file_cap_refusal(&summary, args.max_files_to_modify, Language::English) {
+        return Err(SpindleSpeedUpdaterError::SafetyGate(refusal));
+    }
+    if args.require_spindle_command {
+        let show_path = |path: &Path| path.display().to_string();
+        if let Some(refusal) = missing_spindle_refusal(&entries, show_path, Language::English) {
+            return Err(SpindleSpeedUpdaterError::SafetyGate(refusal));
+        }
+    }
+    if summary.exceeds(args.max_failure_percent) {
+        let warning = preflight_warning(&summary, args.max_failure_percent, Language::English);
+        if !args.force {
+            return Err(SpindleSpeedUpdaterError::SafetyGate(warning));
+        }
+        eprintln!("Proceeding despite --force: {}", warning);
+    }
+    if args.speed_drop_percent > 0 {
+        if let Some(warning) =
+            speed_drop_warning(&entries, args.speed_drop_percent, Language::English)
+        {
+            if !args.force {
+                return Err(SpindleSpeedUpdaterError::SafetyGate(warning));
+            }
+            eprintln!("Proceeding despite --force: {}", warning);
+        }
+    }
+
+    let progress = Arc::new(Progress::default());
+    // The sender is held for the whole run: dropping it would read as a cancellation.
+    let (_cancel_sender, cancel_receiver) = oneshot::channel();
+
+    let results = update_spindle_speed(
+        &run_id,
+        &plan,
+        options,
+        progress,
+        &file_cache,
+        cancel_receiver,
+        None,
+    )
+    .await?;
+
+    if let Some(speed) = args.match_current_speed {
+        note(
+            args.json,
+            format!(
+                "{} files matched the current speed {}",
+                matched_count(&results.totals),
+                speed
+            ),
+        );
+    }
+
+    let report = RunReport::new(run_id, target, folders, started, results);
+    match history_path(args.history.as_deref()) {
+        Ok(path) => {
+            if let Err(error) = append_run(&path, &report).await {
+                eprintln!("Couldn't add the run to {}: {}", path.display(), error);
+            }
+        }
+        Err(error) => eprintln!("No run history file: {}", error),
+    }
+
+    Ok(Some(CliReport {
+        skipped: report.totals.skipped.clone(),
+        run_id: report.run_id,
+        change_hash: report.change_hash,
+        files: report.files,
+        results_file: report.results_file,
+        totals: report.totals,
+    }))
+}
+
+/// Builds the file cache from newline-separated paths, as `find` or `fd` print them.
+fn read_file_list(
+    input: impl BufRead,
+    folders: &[PathBuf],
+    rules: &ProjectRules,
+) -> Result<FileList, SpindleSpeedUpdaterError> {
+    let mut paths = Vec::new();
+    for line in input.lines() {
+        let line = line?;
+        let line = line.strip_suffix('\r').unwrap_or(&line);
+        if !line.is_empty() {
+            paths.push(PathBuf::from(line));
+        }
+    }
+    list_files(paths, folders, rules, Language::English)
+}
+
+/// The release build uses the Windows GUI subsystem, so output is only visible after
+/// attaching to the console of the shell that started us.
+#[cfg(windows)]
+fn attach_console() {
+    unsafe {
+        winapi::um::wincon::AttachConsole(winapi::um::wincon::ATTACH_PARENT_PROCESS);
+    }
+}
+
+#[cfg(not(windows))]
+fn attach_console() {}
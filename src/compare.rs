@@ -0,0 +1,101 @@
+//! Audit mode: compares the spindle speeds of two folders without writing anything,
+//! e.g. a job before and after re-posting it with a changed post-processor.
+
+use crate::encoding::read_program;
+use crate::gcode::current_spindle_speed;
+use crate::mapping::mapping_key;
+use crate::project::ProjectRules;
+use crate::scan::{scan_folder, FileInfo};
+use crate::SpindleSpeedUpdaterError;
+use serde::Serialize;
+use std::collections::{BTreeMap, HashMap};
+use std::path::{Path, PathBuf};
+use tracing::warn;
+
+/// A file present in both folders whose first spindle command differs.
+#[derive(Clone, Debug, Serialize)]
+pub struct SpeedDifference {
+    pub name: String,
+    /// The S number as written, `None` when the file has none or can't be read.
+    pub old_speed: Option<String>,
+    pub new_speed: Option<String>,
+}
+
+#[derive(Clone, Debug, Default, Serialize)]
+pub struct FolderComparison {
+    pub differences: Vec<SpeedDifference>,
+    /// Files present in both folders with the same speed.
+    pub matching: usize,
+    pub only_in_old: Vec<String>,
+    pub only_in_new: Vec<String>,
+}
+
+/// Files keyed like a speed mapping: by lowercase file name, wherever they sit below the
+/// folder.
+fn files_by_name(file_cache: HashMap<PathBuf, FileInfo>) -> BTreeMap<String, PathBuf> {
+    let mut files = BTreeMap::new();
+    for path in file_cache.into_keys() {
+        let Some(name) = mapping_key(&path) else {
+            continue;
+        };
+        if let Some(previous) = files.insert(name, path.clone()) {
+            warn!(
+                "{:?} and {:?} share a name; only one is compared",
+                previous, path
+            );
+        }
+    }
+    files
+}
+
+async fn read_speed(path: &Path, letter: char) -> Option<String> {
+    match read_program(path).await {
+        Ok((content, _)) => current_spindle_speed(&content, letter).map(str::to_string),
+        Err(error) => {
+            warn!("Failed to read {:?} for comparison: {}", path, error);
+            None
+        }
+    }
+}
+
+/// Matches the files of both folders by name and compares the number of their
+/// first spindle command. Numbers are compared as values, so `S12000` and `S12000.0`
+/// count as the same speed.
+pub async fn compare_folders(
+    old_folder: &Path,
+    new_folder: &Path,
+    follow_symlinks: bool,
+    rules: &ProjectRules,
+) -> Result<FolderComparison, SpindleSpeedUpdaterError> {
+    let old_files = files_by_name(scan_folder(old_folder, follow_symlinks, rules)?);
+    let mut new_files = files_by_name(scan_folder(new_folder, follow_symlinks, rules)?);
+    let mut comparison = FolderComparison::default();
+
+    for (name, old_path) in old_files {
+        let Some(new_path) = new_files.remove(&name) else {
+            comparison.only_in_old.push(name);
+            continue;
+        };
+        let old_speed = read_speed(&old_path, rules.spindle_letter).await;
+        let new_speed = read_speed(&new_path, rules.spindle_letter).await;
+        let as_number = |speed: &Option<String>| {
+            speed
+                .as_deref()
+                .and_then(|number| number.parse::<f64>().ok())
+        };
+        if old_speed == new_speed
+            || as_number(&old_speed).is_some_and(|old| Some(old) == as_number(&new_speed))
+        {
+            comparison.matching += 1;
+        } else {
+            comparison.differences.push(SpeedDifference {
+                name,
+                old_speed,
+                new_speed,
+            });
+        }
+    }
+    comparison.only_in_new = new_files.into_keys().collect();
+
+    Ok(comparison)
+}
@@ -0,0 +1,106 @@
+//! Program text as stored on disk: UTF-8, or UTF-16 with a byte order mark as Windows
+//! Notepad saves it. A rewritten program is written back in the encoding it was read in.
+
+use std::io;
+use std::path::Path;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum TextEncoding {
+    Utf8,
+    Utf16Le,
+    Utf16Be,
+}
+
+impl TextEncoding {
+    /// `text` in this encoding, with the byte order mark for UTF-16.
+    pub fn encode(self, text: &str) -> Vec<u8> {
+        let units = || std::iter::once(0xFEFF).chain(text.encode_utf16());
+        match self {
+            TextEncoding::Utf8 => text.as_bytes().to_vec(),
+            TextEncoding::Utf16Le => units().flat_map(u16::to_le_bytes).collect(),
+            TextEncoding::Utf16Be => units().flat_map(u16::to_be_bytes).collect(),
+        }
+    }
+}
+
+/// The text of `bytes` and how it was encoded, told by a UTF-16 byte order mark. Without
+/// one the bytes have to be UTF-8; anything undecodable is `InvalidData`, as from
+/// `read_to_string`, which the callers take to mean a binary file.
+pub fn decode(bytes: Vec<u8>) -> io::Result<(String, TextEncoding)> {
+    let (encoding, from_bytes): (_, fn([u8; 2]) -> u16) = match bytes.get(..2) {
+        Some([0xFF, 0xFE]) => (TextEncoding::Utf16Le, u16::from_le_bytes),
+        Some([0xFE, 0xFF]) => (TextEncoding::Utf16Be, u16::from_be_bytes),
+        _ => {
+            return String::from_utf8(bytes)
+                .map(|text| (text, TextEncoding::Utf8))
+                .map_err(|error| io::Error::new(io::ErrorKind::InvalidData, error))
+        }
+    };
+    let body = &bytes[2..];
+    if !body.len().is_multiple_of(2) {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            "UTF-16 text with an odd number of bytes",
+        ));
+    }
+    let units: Vec<u16> = body
+        .chunks_exact(2)
+        .map(|pair| from_bytes([pair[0], pair[1]]))
+        .collect();
+    String::from_utf16(&units)
+        .map(|text| (text, encoding))
+        .map_err(|error| io::Error::new(io::ErrorKind::InvalidData, error))
+}
+
+/// Reads a program in either encoding; see [`decode`].
+pub async fn read_program(path: &Path) -> io::Result<(String, TextEncoding)> {
+    decode(tokio::fs::read(path).await?)
+}
+
+/// Blocking counterpart of [`read_program`].
+pub fn read_program_blocking(path: &Path) -> io::Result<(String, TextEncoding)> {
+    decode(std::fs::read(path)?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::gcode::{rewrite_content, UpdateOptions};
+
+    /// Saved by Notepad: UTF-16LE with a byte order mark and CRLF line endings.
+    #[test]
+    fn utf16le_program_is_updated_and_written_back_as_utf16le() {
+        let saved = TextEncoding::Utf16Le.encode("G21\r\nS1000 M3\r\nM30\r\n");
+        assert_eq!(&saved[..2], [0xFF, 0xFE]);
+
+        let (text, encoding) = decode(saved).unwrap();
+        assert_eq!(encoding, TextEncoding::Utf16Le);
+        let update = rewrite_content(&text, 12000, &UpdateOptions::default()).unwrap();
+
+        assert_eq!(
+            encoding.encode(&update.content),
+            TextEncoding::Utf16Le.encode("G21\r\nS12000 M3\r\nM30\r\n")
+        );
+    }
+
+    #[test]
+    fn utf16be_and_plain_utf8_are_told_apart() {
+        let big_endian = TextEncoding::Utf16Be.encode("S1000 M3\n");
+        assert_eq!(
+            decode(big_endian).unwrap(),
+            ("S1000 M3\n".to_string(), TextEncoding::Utf16Be)
+        );
+        assert_eq!(
+            decode(b"S1000 M3\n".to_vec()).unwrap(),
+            ("S1000 M3\n".to_string(), TextEncoding::Utf8)
+        );
+    }
+
+    #[test]
+    fn undecodable_bytes_are_invalid_data() {
+        for bytes in [vec![0xFF, 0xFE, b'S'], vec![b'S', 0xC3, 0x28]] {
+            let error = decode(bytes).unwrap_err();
+            assert_eq!(error.kind(), io::ErrorKind::InvalidData);
+        }
+    }
+}
@@ -0,0 +1,151 @@
+//! The error type of every fallible operation, and the exit code the command line
+//! reports for each kind.
+
+use std::io;
+use std::path::{Path, PathBuf};
+use thiserror::Error;
+
+#[derive(Debug, Error)]
+pub enum SpindleSpeedUpdaterError {
+    #[error("IO error: {0}")]
+    Io(#[from] io::Error),
+    #[error("{}: IO error: {source}", path.display())]
+    FileIo { path: PathBuf, source: io::Error },
+    #[error("No parent directory found")]
+    NoParentDirectory,
+    #[error("Failed to lock progress: {0}")]
+    ProgressLockFailure(String),
+    #[error("Invalid spindle speed: {0}")]
+    InvalidSpindleSpeed(String),
+    #[error("Backup failure: {0}")]
+    BackupFailure(String),
+    #[error("Operation cancelled: {0}")]
+    CancelError(String),
+    #[error("Invalid argument: {0}")]
+    InvalidArgument(String),
+    #[error("Invalid speed mapping: {0}")]
+    InvalidMapping(String),
+    #[error("Refusing to run: {0}")]
+    SafetyGate(String),
+    #[error("{0} files could not be updated")]
+    FilesFailed(usize),
+    #[error("{0} self-test cases failed")]
+    SelfTestFailed(usize),
+    #[error("Storage check failed: {0}")]
+    StorageCheck(String),
+    #[error("{0} files flagged by the audit")]
+    AuditFindings(usize),
+}
+
+impl SpindleSpeedUpdaterError {
+    /// Process exit code reported by the command-line mode, one per variant so wrapper
+    /// scripts can tell failures apart. Success exits with 0.
+    ///
+    /// | code | variant                 |
+    /// |------|-------------------------|
+    /// | 1    | `ProgressLockFailure`   |
+    /// | 2    | `Io`, `FileIo`          |
+    /// | 3    | `InvalidSpindleSpeed`   |
+    /// | 4    | `CancelError`           |
+    /// | 5    | `NoParentDirectory`     |
+    /// | 6    | `BackupFailure`         |
+    /// | 7    | `InvalidArgument`       |
+    /// | 8    | `InvalidMapping`        |
+    /// | 9    | `SafetyGate`            |
+    /// | 10   | `FilesFailed`           |
+    /// | 11   | `SelfTestFailed`        |
+    /// | 12   | `StorageCheck`          |
+    /// | 13   | `AuditFindings`         |
+    pub fn exit_code(&self) -> i32 {
+        match self {
+            SpindleSpeedUpdaterError::ProgressLockFailure(_) => 1,
+            SpindleSpeedUpdaterError::Io(_) | SpindleSpeedUpdaterError::FileIo { .. } => 2,
+            SpindleSpeedUpdaterError::InvalidSpindleSpeed(_) => 3,
+            SpindleSpeedUpdaterError::CancelError(_) => 4,
+            SpindleSpeedUpdaterError::NoParentDirectory => 5,
+            SpindleSpeedUpdaterError::BackupFailure(_) => 6,
+            SpindleSpeedUpdaterError::InvalidArgument(_) => 7,
+            SpindleSpeedUpdaterError::InvalidMapping(_) => 8,
+            SpindleSpeedUpdaterError::SafetyGate(_) => 9,
+            SpindleSpeedUpdaterError::FilesFailed(_) => 10,
+            SpindleSpeedUpdaterError::SelfTestFailed(_) => 11,
+            SpindleSpeedUpdaterError::StorageCheck(_) => 12,
+            SpindleSpeedUpdaterError::AuditFindings(_) => 13,
+        }
+    }
+}
+
+impl SpindleSpeedUpdaterError {
+    /// For `map_err`: attaches the file an IO error happened on.
+    pub fn file_io(path: &Path) -> impl FnOnce(io::Error) -> Self + '_ {
+        move |source| SpindleSpeedUpdaterError::FileIo {
+            path: path.to_path_buf(),
+            source,
+        }
+    }
+}
+
+impl From<SpindleSpeedUpdaterError> for String {
+    fn from(error: SpindleSpeedUpdaterError) -> Self {
+        error.to_string()
+    }
+}
+
+impl From<String> for SpindleSpeedUpdaterError {
+    fn from(s: String) -> Self {
+        SpindleSpeedUpdaterError::InvalidSpindleSpeed(s)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn every_variant_has_its_documented_exit_code() {
+        let text = || "x".to_string();
+        let table = [
+            (SpindleSpeedUpdaterError::ProgressLockFailure(text()), 1),
+            (SpindleSpeedUpdaterError::Io(io::ErrorKind::Other.into()), 2),
+            (
+                SpindleSpeedUpdaterError::FileIo {
+                    path: PathBuf::from("part.nc"),
+                    source: io::ErrorKind::Other.into(),
+                },
+                2,
+            ),
+            (SpindleSpeedUpdaterError::InvalidSpindleSpeed(text()), 3),
+            (SpindleSpeedUpdaterError::CancelError(text()), 4),
+            (SpindleSpeedUpdaterError::NoParentDirectory, 5),
+            (SpindleSpeedUpdaterError::BackupFailure(text()), 6),
+            (SpindleSpeedUpdaterError::InvalidArgument(text()), 7),
+            (SpindleSpeedUpdaterError::InvalidMapping(text()), 8),
+            (SpindleSpeedUpdaterError::SafetyGate(text()), 9),
+            (SpindleSpeedUpdaterError::FilesFailed(1), 10),
+            (SpindleSpeedUpdaterError::SelfTestFailed(1), 11),
+            (SpindleSpeedUpdaterError::StorageCheck(text()), 12),
+            (SpindleSpeedUpdaterError::AuditFindings(1), 13),
+        ];
+        for (error, code) in &table {
+            assert_eq!(error.exit_code(), *code, "{:?}", error);
+        }
+    }
+
+    #[test]
+    fn file_io_error_names_the_file() {
+        let denied = io::Error::new(io::ErrorKind::PermissionDenied, "access denied");
+        let error = SpindleSpeedUpdaterError::file_io(Path::new("job42.tap"))(denied);
+        assert_eq!(error.to_string(), "job42.tap: IO error: access denied");
+    }
+
+    #[test]
+    fn failed_read_names_the_file() {
+        let dir = tempfile::tempdir().unwrap();
+        let missing = dir.path().join("job42.csv");
+        let error = crate::mapping::load_mapping(&missing, &(1..=24000)).unwrap_err();
+        assert!(matches!(error, SpindleSpeedUpdaterError::FileIo { .. }));
+        assert!(error
+            .to_string()
+            .starts_with(&format!("{}: IO error: ", missing.display())));
+    }
+}
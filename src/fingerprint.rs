@@ -0,0 +1,42 @@
+//! A change-control fingerprint of a run: the same changes to the same files always
+//! give the same hash, whatever order the files were processed in.
+
+use crate::update::{FileOutcome, FileResult};
+use sha2::{Digest, Sha256};
+
+/// SHA-256, as lowercase hex, over the changed lines sorted by path. Each changed line
+/// contributes its file's path, old line and new line, each terminated by a NUL byte so
+/// field boundaries can't be forged by the content. Unchanged, skipped and failed files
+/// are left out.
+pub fn change_hash(results: &[FileResult]) -> String {
+    let mut changes: Vec<(String, &str, &str)> = results
+        .iter()
+        .flat_map(|result| match &result.outcome {
+            FileOutcome::Changed(changes) => changes
+                .iter()
+                .map(|change| {
+                    (
+                        result.path.to_string_lossy().into_owned(),
+                        change.old.as_str(),
+                        change.new.as_str(),
+                    )
+                })
+                .collect(),
+            _ => Vec::new(),
+        })
+        .collect();
+    changes.sort();
+
+    let mut hasher = Sha256::new();
+    for (path, old, new) in changes {
+        for field in [path.as_str(), old, new] {
+            hasher.update(field.as_bytes());
+            hasher.update([0]);
+        }
+    }
+    hasher
+        .finalize()
+        .iter()
+        .map(|byte| format!("{:02x}", byte))
+        .collect()
+}
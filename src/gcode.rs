@@ -0,0 +1,1606 @@
+//! Text transformation of G-code programs, independent of files and the GUI.
+
+use crate::strings::{fill, Language, Text};
+use crate::SpindleSpeedUpdaterError;
+use serde::{Deserialize, Serialize};
+use std::ops::{Range, RangeInclusive};
+use thiserror::Error;
+
+/// Where a spindle command is inserted into a program that has none.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum InsertionPoint {
+    /// After the first block selecting a motion mode (`G0` to `G3`), falling back to
+    /// [`InsertionPoint::AfterHeader`] when the program has no such block.
+    AfterFirstMotion,
+    /// After the leading `%`, `O` program number, comment and blank lines.
+    AfterHeader,
+}
+
+impl InsertionPoint {
+    pub const ALL: [InsertionPoint; 2] = [
+        InsertionPoint::AfterFirstMotion,
+        InsertionPoint::AfterHeader,
+    ];
+
+    pub fn label(self, language: Language) -> &'static str {
+        match self {
+            InsertionPoint::AfterFirstMotion => Text::AfterFirstMotion,
+            InsertionPoint::AfterHeader => Text::AfterHeader,
+        }
+        .get(language)
+    }
+}
+
+/// Which spindle words a run rewrites.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum SpindleTarget {
+    /// The bare `S` word of a single-spindle machine, e.g. `S1000`.
+    Main,
+    /// One spindle of a multi-spindle machine, e.g. `S2=1000` for 2.
+    Indexed(u8),
+    /// Every indexed spindle: the first `S1=`, the first `S2=` and so on.
+    AllIndexed,
+}
+
+impl SpindleTarget {
+    pub const ALL: [SpindleTarget; 4] = [
+        SpindleTarget::Main,
+        SpindleTarget::Indexed(1),
+        SpindleTarget::Indexed(2),
+        SpindleTarget::AllIndexed,
+    ];
+
+    pub fn label(self, language: Language) -> String {
+        match self {
+            SpindleTarget::Main => Text::MainSpindle.get(language).to_string(),
+            SpindleTarget::Indexed(index) => fill(Text::IndexedSpindle.get(language), &[&index]),
+            SpindleTarget::AllIndexed => Text::AllIndexedSpindles.get(language).to_string(),
+        }
+    }
+
+    fn addresses(self, index: u8) -> bool {
+        match self {
+            SpindleTarget::Main => false,
+            SpindleTarget::Indexed(target) => target == index,
+            SpindleTarget::AllIndexed => true,
+        }
+    }
+}
+
+/// A comment the inserted spindle command should follow, e.g. `(START)`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct InsertionMarker {
+    /// Matched case-insensitively anywhere in a line.
+    pub text: String,
+    /// Skip programs without the marker instead of inserting at the
+    /// [`InsertionPoint`].
+    pub required: bool,
+}
+
+#[derive(Clone, Debug)]
+pub struct UpdateOptions {
+    /// Insert a spindle command when a program has none, instead of leaving it alone.
+    pub insert_missing: Option<InsertionPoint>,
+    /// When inserting, go after the first line holding this marker if there is one.
+    pub insertion_marker: Option<InsertionMarker>,
+    /// Only update programs whose first spindle command is currently at this speed.
+    pub match_current_speed: Option<u32>,
+    /// M-code following the speed on an inserted spindle line, or a reformatted one that
+    /// had no `M3`/`M4` of its own.
+    pub spindle_on: String,
+    /// Write the `M3`/`M4` of a rewritten line in capitals instead of as the post did.
+    pub uppercase_m_codes: bool,
+    /// Zero-pad the new speed to the digit count of the number it replaces, for
+    /// controllers that expect fixed-width fields such as `S01000`.
+    pub pad_speed_width: bool,
+    /// Move the spindle word in front of an `M3`/`M4` that comes before it, so
+    /// `M3 S1000` is written as `S12000 M3`. Other words keep their place.
+    pub spindle_word_first: bool,
+    /// Strip trailing whitespace from the rewritten line; other lines are never touched.
+    pub trim_rewritten_lines: bool,
+    pub spindle_target: SpindleTarget,
+    /// Address letter of the spindle word, `S` on all but a few controllers.
+    pub spindle_letter: char,
+    /// Leave spindle words before the first tool change alone, e.g. a safety `S0` in
+    /// the preamble.
+    pub after_tool_change: bool,
+    /// Update the first spindle word after every `M6` instead of only the first in the
+    /// file; later words up to the next `M6` are overrides and stay. Words before the
+    /// first `M6` are left alone.
+    pub each_tool_change: bool,
+    /// Scale the speed by a percentage the program gives in a comment.
+    pub speed_override: Option<SpeedOverride>,
+    /// Note the new speed and the date in a comment at the top of changed programs.
+    pub change_stamp: Option<ChangeStamp>,
+    /// Refuse an update that changes anything but spindle lines, as a net under parser
+    /// bugs; see [`structure_violation`].
+    pub check_structure: bool,
+}
+
+/// A comment line such as `(SPEED SET TO 12000 ON 2024-06-01)` at the top of every
+/// program a run changes. A line the template matches is an earlier stamp and is
+/// replaced, so repeated runs leave a single one. Templates that aren't a comment are
+/// never stamped; see [`ChangeStamp::is_comment`].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ChangeStamp {
+    /// The line with `{speed}` and `{date}` placeholders.
+    pub template: String,
+    /// What `{date}` stands for, `YYYY-MM-DD`.
+    pub date: String,
+}
+
+impl ChangeStamp {
+    fn line(&self, speed: u32) -> String {
+        self.template
+            .trim()
+            .replace("{speed}", &speed.to_string())
+            .replace("{date}", &self.date)
+    }
+
+    /// Whether `template` is a comment, `(...)` or `; ...`. Anything else could be taken
+    /// for a line of code, or replace one.
+    pub fn is_comment(template: &str) -> bool {
+        let template = template.trim();
+        template.starts_with(';') || (template.starts_with('(') && template.ends_with(')'))
+    }
+
+    /// Whether the whole of `line` is the template with a speed (digits) and a date
+    /// (`YYYY-MM-DD`) filled in.
+    fn matches(&self, line: &str) -> bool {
+        if !Self::is_comment(&self.template) {
+            return false;
+        }
+        let mut template = self.template.trim();
+        let mut rest = line.trim();
+        loop {
+            let placeholder = ["{speed}", "{date}"]
+                .into_iter()
+                .filter_map(|placeholder| Some((template.find(placeholder)?, placeholder)))
+                .min();
+            let Some((index, placeholder)) = placeholder else {
+                return rest == template;
+            };
+            let Some(value) = rest.strip_prefix(&template[..index]) else {
+                return false;
+            };
+            let length = if placeholder == "{speed}" {
+                value.bytes().take_while(u8::is_ascii_digit).count()
+            } else {
+                date_length(value)
+            };
+            if length == 0 {
+                return false;
+            }
+            rest = &value[length..];
+            template = &template[index + placeholder.len()..];
+        }
+    }
+
+    /// `content` with the stamp for `speed` in place of an earlier one, or else on a new
+    /// line after the `%` and program number lines a controller expects first.
+    fn apply(&self, content: &str, speed: u32) -> String {
+        let stamp = self.line(speed);
+        let mut lines: Vec<&str> = content.lines().collect();
+        match lines.iter().position(|line| self.matches(line)) {
+            Some(index) => lines[index] = &stamp,
+            None => {
+                let index = lines
+                    .iter()
+                    .position(|line| {
+                        let line = line.trim();
+                        line != "%" && !line.starts_with('O')
+                    })
+                    .unwrap_or(lines.len());
+                lines.insert(index, &stamp);
+            }
+        }
+        join_lines(content, &lines)
+    }
+}
+
+/// 10 when `text` starts with a `YYYY-MM-DD` date, otherwise 0.
+fn date_length(text: &str) -> usize {
+    let is_date = text.len() >= 10
+        && text
+            .bytes()
+            .take(10)
+            .enumerate()
+            .all(|(index, byte)| match index {
+                4 | 7 => byte == b'-',
+                _ => byte.is_ascii_digit(),
+            });
+    if is_date {
+        10
+    } else {
+        0
+    }
+}
+
+/// A percentage read from a comment such as `(SPEED OVERRIDE 80%)`, applied to the
+/// entered speed for that one program. Programs without the comment get the speed as
+/// entered.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct SpeedOverride {
+    /// Text before the percentage, matched in comments ignoring case.
+    pub marker: String,
+    /// A scaled speed outside this range skips the program.
+    pub speed_range: RangeInclusive<u32>,
+}
+
+impl SpeedOverride {
+    /// `spindle_speed` scaled by the first override comment in `content`, rounded to
+    /// whole RPM.
+    fn apply(&self, content: &str, spindle_speed: u32) -> Result<u32, SkipReason> {
+        let marker = self.marker.to_ascii_lowercase();
+        for (index, line) in content.lines().enumerate() {
+            let comment = line[comment_start(line)..].to_ascii_lowercase();
+            let Some(found) = comment.find(&marker) else {
+                continue;
+            };
+            let after = comment[found + marker.len()..].trim_start();
+            let number = &after[..after
+                .find(|c: char| !(c.is_ascii_digit() || c == '.'))
+                .unwrap_or(after.len())];
+            let percent: f64 = number
+                .parse()
+                .map_err(|_| SkipReason::UnreadableOverride { line: index + 1 })?;
+            let speed = percent_of(f64::from(spindle_speed), percent).round();
+            // Named apart from the range, so `0%` reads as the typo it likely is.
+            if speed < 1.0 {
+                return Err(SkipReason::OverrideStopsSpindle {
+                    line: index + 1,
+                    percent: number.to_string(),
+                });
+            }
+            let (minimum, maximum) = (*self.speed_range.start(), *self.speed_range.end());
+            if speed < f64::from(minimum) {
+                return Err(SkipReason::OverrideBelowMinimum {
+                    percent: number.to_string(),
+                    speed: speed as u64,
+                    minimum,
+                });
+            }
+            if speed > f64::from(maximum) {
+                return Err(SkipReason::OverrideOutOfRange {
+                    percent: number.to_string(),
+                    speed: speed as u64,
+                    maximum,
+                });
+            }
+            return Ok(speed as u32);
+        }
+        Ok(spindle_speed)
+    }
+}
+
+/// `percent` percent of `value`.
+pub fn percent_of(value: f64, percent: f64) -> f64 {
+    value * percent / 100.0
+}
+
+impl Default for UpdateOptions {
+    fn default() -> Self {
+        Self {
+            insert_missing: None,
+            insertion_marker: None,
+            match_current_speed: None,
+            spindle_on: "M3".to_string(),
+            uppercase_m_codes: false,
+            pad_speed_width: false,
+            spindle_word_first: false,
+            trim_rewritten_lines: false,
+            spindle_target: SpindleTarget::Main,
+            spindle_letter: 'S',
+            after_tool_change: false,
+            each_tool_change: false,
+            speed_override: None,
+            change_stamp: None,
+            check_structure: true,
+        }
+    }
+}
+
+/// Result of applying a spindle speed to a program.
+#[derive(Debug)]
+pub struct ContentUpdate {
+    pub content: String,
+    /// The speed written, after any [`SpeedOverride`].
+    pub speed: u32,
+    /// Whether `content` differs from the input in anything but line endings.
+    pub changed: bool,
+    /// A spindle command line was added because the program had none.
+    pub inserted: bool,
+    /// The lines that changed: one, or one per spindle when several are targeted.
+    pub line_changes: Vec<LineChange>,
+}
+
+/// A spindle line before and after the update; `old` is empty for an inserted line.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize)]
+pub struct LineChange {
+    pub old: String,
+    pub new: String,
+}
+
+/// Why a program was left untouched. [`rewrite_content`] returns the reasons found in
+/// the text; `NotInMapping`, `NoMatchingRule`, `ReadOnly`, `Binary` and `ChangedAfterPreview` come from
+/// the checks a run makes around it.
+#[derive(Clone, Debug, PartialEq, Eq, Error, Serialize)]
+#[serde(tag = "reason", rename_all = "snake_case")]
+pub enum SkipReason {
+    /// Zero bytes or only whitespace; never written, not even to insert a spindle command.
+    #[error("empty file")]
+    Empty,
+    #[error("no S command")]
+    NoSpindleCommand,
+    #[error("unsupported spindle speed notation \"{notation}\"")]
+    UnsupportedNotation { notation: String },
+    #[error("current speed {current} doesn't match {expected} RPM")]
+    SpeedMismatch { current: String, expected: u32 },
+    #[error("no insertion marker \"{marker}\"")]
+    MarkerNotFound { marker: String },
+    #[error("not listed in the speed mapping")]
+    NotInMapping,
+    #[error("no speed rule matches the file name")]
+    NoMatchingRule,
+    #[error("file is read-only")]
+    ReadOnly,
+    #[error("not a text file")]
+    Binary,
+    #[error("no tool change")]
+    NoToolChange,
+    /// The spindle word sits on a line that continues the block of the line before it,
+    /// e.g. a lone `N30` followed by `S8000 M3`. Which block it belongs to is up to the
+    /// control, so the file isn't guessed at.
+    #[error("spindle word on line {line} continues the block before it")]
+    ContinuedBlock { line: usize },
+    #[error("speed override comment on line {line} has no readable percentage")]
+    UnreadableOverride { line: usize },
+    /// The percentage scales the speed to nothing, which would write `S0`.
+    #[error("speed override of {percent}% on line {line} gives S0, which would stop the spindle; the percentage must be above 0")]
+    OverrideStopsSpindle { line: usize, percent: String },
+    #[error("speed override of {percent}% gives {speed} RPM, below the minimum of {minimum} RPM")]
+    OverrideBelowMinimum {
+        percent: String,
+        speed: u64,
+        minimum: u32,
+    },
+    /// Above the maximum; speeds below the minimum are [`SkipReason::OverrideBelowMinimum`].
+    #[error("speed override of {percent}% gives {speed} RPM, above the maximum of {maximum} RPM")]
+    OverrideOutOfRange {
+        percent: String,
+        speed: u64,
+        maximum: u32,
+    },
+    /// Modified or deleted since the dry pass the run was confirmed on.
+    #[error("changed after preview")]
+    ChangedAfterPreview,
+    /// The update would have changed more than the spindle lines, which only a parser
+    /// bug can do. Reported as a failure rather than a skip.
+    #[error("internal error: the update would also change line {line}, which has no spindle word; nothing was written")]
+    StructureChanged { line: usize },
+    /// Held open by another program, such as an editor or a drip feed, that doesn't let
+    /// it be written. Worth retrying once that program lets go.
+    #[error("file in use")]
+    InUse,
+}
+
+impl SkipReason {
+    /// Short name for the per-reason counts in summaries, e.g. "2 no S command".
+    pub fn label(&self) -> &'static str {
+        match self {
+            SkipReason::Empty => "empty",
+            SkipReason::NoSpindleCommand => "no S command",
+            SkipReason::UnsupportedNotation { .. } => "unsupported notation",
+            SkipReason::SpeedMismatch { .. } => "different current speed",
+            SkipReason::MarkerNotFound { .. } => "no insertion marker",
+            SkipReason::NoToolChange => "no tool change",
+            SkipReason::ContinuedBlock { .. } => "continued block",
+            SkipReason::UnreadableOverride { .. } => "unreadable override",
+            SkipReason::OverrideStopsSpindle { .. } => "override stops spindle",
+            SkipReason::OverrideBelowMinimum { .. } => "override below minimum",
+            SkipReason::OverrideOutOfRange { .. } => "override out of range",
+            SkipReason::NotInMapping => "not in mapping",
+            SkipReason::NoMatchingRule => "no matching rule",
+            SkipReason::ReadOnly => "read-only",
+            SkipReason::Binary => "binary",
+            SkipReason::ChangedAfterPreview => "changed after preview",
+            SkipReason::StructureChanged { .. } => "safety check",
+            SkipReason::InUse => "in use",
+        }
+    }
+}
+
+/// Outcome of looking for the spindle word on one line.
+enum SpindleLine {
+    Rewritten(String),
+    /// The line holds the spindle word, but its number can't be rewritten safely.
+    Unsupported(String),
+}
+
+/// Non-empty words of a line with their byte offsets, up to the first comment.
+fn word_offsets(line: &str) -> Vec<(usize, &str)> {
+    let mut result = Vec::new();
+    let mut offset = 0;
+    for word in line.split([' ', '\t']) {
+        if word.starts_with('(') || word.starts_with(';') {
+            break;
+        }
+        if !word.is_empty() {
+            result.push((offset, word));
+        }
+        offset += word.len() + 1;
+    }
+    result
+}
+
+/// Byte offset where the comment of a line starts, by the same rule as [`word_offsets`],
+/// or the line's length when it has none.
+fn comment_start(line: &str) -> usize {
+    let mut offset = 0;
+    for word in line.split([' ', '\t']) {
+        if word.starts_with('(') || word.starts_with(';') {
+            return offset;
+        }
+        offset += word.len() + 1;
+    }
+    line.len()
+}
+
+/// Whether the block of `line` carries on to the next line: it holds nothing but a block
+/// number (`N30`), or its code ends with a continuation mark, `&` or `\`.
+fn continues_on_next_line(line: &str) -> bool {
+    let code = line[..comment_start(line)].trim_end();
+    let lone_block_number = code
+        .trim_start()
+        .strip_prefix(['N', 'n'])
+        .is_some_and(|number| !number.is_empty() && number.chars().all(|c| c.is_ascii_digit()));
+    lone_block_number || code.ends_with(['&', '\\'])
+}
+
+/// Anything that starts like a number, including forms such as `1.0E3` that are only
+/// recognised so they can be rejected.
+fn looks_numeric(text: &str) -> bool {
+    text.starts_with(|c: char| c.is_ascii_digit() || c == '.')
+        && text
+            .chars()
+            .all(|c| c.is_ascii_digit() || matches!(c, '.' | 'e' | 'E' | '+' | '-'))
+}
+
+/// Index and byte range, within `word`, of the number of an indexed spindle word such
+/// as `S2=1500`, with `letter` in place of `S`.
+fn indexed_spindle_word(word: &str, letter: char) -> Option<(u8, Range<usize>)> {
+    let (index, number) = word.strip_prefix(letter)?.split_once('=')?;
+    if index.is_empty() || !index.chars().all(|c| c.is_ascii_digit()) || !looks_numeric(number) {
+        return None;
+    }
+    let index = index.parse().ok()?;
+    Some((index, word.len() - number.len()..word.len()))
+}
+
+/// Byte range of the number of the first spindle word, addressed by `letter`. The
+/// number may follow the letter directly (`S1000`) or as the next word (`S 1000`).
+fn spindle_number(line: &str, letter: char) -> Option<Range<usize>> {
+    let words = word_offsets(line);
+    for (index, &(offset, word)) in words.iter().enumerate() {
+        let Some(rest) = word.strip_prefix(letter) else {
+            continue;
+        };
+        if rest.is_empty() {
+            if let Some(&(next_offset, next)) = words.get(index + 1) {
+                if looks_numeric(next) {
+                    return Some(next_offset..next_offset + next.len());
+                }
+            }
+        } else if looks_numeric(rest) {
+            return Some(offset + letter.len_utf8()..offset + word.len());
+        }
+    }
+    None
+}
+
+/// The number of every line's spindle word in `content`, as written, in file order.
+pub fn spindle_speeds(content: &str, letter: char) -> Vec<&str> {
+    content
+        .lines()
+        .filter_map(|line| spindle_number(line, letter).map(|range| &line[range]))
+        .collect()
+}
+
+/// The number of the first spindle command in `content`, as written.
+pub fn current_spindle_speed(content: &str, letter: char) -> Option<&str> {
+    current_target_speed(content, SpindleTarget::Main, letter)
+}
+
+/// Like [`current_spindle_speed`], for the first spindle word `target` addresses.
+fn current_target_speed(content: &str, target: SpindleTarget, letter: char) -> Option<&str> {
+    if target == SpindleTarget::Main {
+        return content
+            .lines()
+            .find_map(|line| spindle_number(line, letter).map(|range| &line[range]));
+    }
+    content.lines().find_map(|line| {
+        word_offsets(line).into_iter().find_map(|(_, word)| {
+            indexed_spindle_word(word, letter)
+                .filter(|(index, _)| target.addresses(*index))
+                .map(|(_, range)| &word[range])
+        })
+    })
+}
+
+/// The number of the first spindle word `options` would rewrite, as written: the one
+/// `spindle_target` addresses, after the first tool change if that is required.
+fn current_speed_text<'a>(content: &'a str, options: &UpdateOptions) -> Option<&'a str> {
+    let start = first_updatable_line(content, options).ok()?;
+    current_target_speed(
+        from_line(content, start),
+        options.spindle_target,
+        options.spindle_letter,
+    )
+}
+
+/// [`current_speed_text`] as a value, e.g. 8000 for `S8000.`; `None` when it isn't a
+/// plain number.
+pub fn current_speed_value(content: &str, options: &UpdateOptions) -> Option<f64> {
+    current_speed_text(content, options)?.parse().ok()
+}
+
+/// Indices of the lines holding a spindle word `target` addresses, for display. Only the
+/// first of them (per spindle) is ever rewritten.
+pub fn spindle_lines(content: &str, target: SpindleTarget, letter: char) -> Vec<usize> {
+    content
+        .lines()
+        .enumerate()
+        .filter(|(_, line)| match target {
+            SpindleTarget::Main => spindle_number(line, letter).is_some(),
+            _ => word_offsets(line).into_iter().any(|(_, word)| {
+                indexed_spindle_word(word, letter).is_some_and(|(index, _)| target.addresses(index))
+            }),
+        })
+        .map(|(index, _)| index)
+        .collect()
+}
+
+/// Byte range of the spindle direction word, `M3` or `M4` (`M03`, `M04`) in either case,
+/// since some posts write `m3`.
+fn direction_word(line: &str) -> Option<Range<usize>> {
+    word_offsets(line).into_iter().find_map(|(offset, word)| {
+        matches!(word_number(&word.to_ascii_uppercase(), 'M'), Some(3 | 4))
+            .then(|| offset..offset + word.len())
+    })
+}
+
+/// The direction word as written, or in capitals when `options` asks for that.
+fn direction_text(word: &str, options: &UpdateOptions) -> String {
+    if options.uppercase_m_codes {
+        word.to_ascii_uppercase()
+    } else {
+        word.to_string()
+    }
+}
+
+/// `spindle_speed` as written in place of `old`, the number it replaces. With
+/// `pad_speed_width` it is zero-padded to the digits `old` has before any decimal point,
+/// so `S01000` becomes `S01200`; a speed with more digits is never cut.
+fn speed_text(spindle_speed: u32, old: &str, options: &UpdateOptions) -> String {
+    let width = if options.pad_speed_width {
+        old.split('.').next().unwrap_or_default().len()
+    } else {
+        0
+    };
+    format!("{:0width$}", spindle_speed, width = width)
+}
+
+pub fn is_plain_number(text: &str) -> bool {
+    text.chars().all(|c| c.is_ascii_digit() || c == '.')
+}
+
+/// Returns the rewritten line if it carries the spindle word, `None` otherwise.
+///
+/// A line starting with `S` is reformatted as `S{speed}` followed by the words after its
+/// number, such as a gear range `M42`, single-spaced and in their order, then its comment.
+/// `spindle_on` is added after them when the line has no `M3`/`M4`. Otherwise the words
+/// of the line are scanned, split on spaces and tabs, and only the number of the first `S` word
+/// is replaced so block numbers, other words and the original separators are preserved.
+/// Scanning stops at the first comment. A number in any other form than digits and a
+/// decimal point, such as `S1.0E3`, is reported instead of rewritten. `S` stands for
+/// `options.spindle_letter` throughout. With `uppercase_m_codes` the direction word of
+/// the rewritten line is written in capitals either way. The speed is written by
+/// [`speed_text`], and with `spindle_word_first` a mid-line spindle word is moved by
+/// [`spindle_word_first`].
+fn rewrite_spindle_line(
+    line: &str,
+    spindle_speed: u32,
+    options: &UpdateOptions,
+) -> Option<SpindleLine> {
+    let letter = options.spindle_letter;
+    let number = spindle_number(line, letter);
+    if let Some(range) = &number {
+        let text = &line[range.clone()];
+        if !is_plain_number(text) {
+            return Some(SpindleLine::Unsupported(format!("{}{}", letter, text)));
+        }
+    }
+
+    // An indexed word such as `S1=1000` addresses another spindle, not this one.
+    let starts_indexed = word_offsets(line)
+        .first()
+        .is_some_and(|&(_, word)| indexed_spindle_word(word, letter).is_some());
+    let starts_with_word = line.trim_start().starts_with(letter) && !starts_indexed;
+
+    // Only the number changes, so tabs, column alignment and indentation survive.
+    number.map(|range| {
+        let speed = speed_text(spindle_speed, &line[range.clone()], options);
+        let mut rewritten = format!("{}{}{}", &line[..range.start], speed, &line[range.end..]);
+        let speed_end = range.start + speed.len();
+        if let Some(direction) = direction_word(&rewritten) {
+            let text = direction_text(&rewritten[direction.clone()], options);
+            rewritten.replace_range(direction, &text);
+        } else if starts_with_word {
+            // A line that is only the spindle word gets the direction it was missing.
+            let rest = &rewritten[speed_end..];
+            let comment = comment_start(rest);
+            let code_end = speed_end + rest[..comment].trim_end().len();
+            rewritten.insert_str(code_end, &format!(" {}", options.spindle_on));
+        }
+        if !starts_with_word && options.spindle_word_first {
+            if let Some(reordered) = spindle_word_first(&rewritten, letter) {
+                rewritten = reordered;
+            }
+        }
+        SpindleLine::Rewritten(rewritten)
+    })
+}
+
+/// `line` with its spindle word moved in front of the `M3`/`M4` before it, as in
+/// `N30 M3 S 9000` to `N30 S9000 M3`, and written without a space after the letter.
+/// `None` when the spindle word already comes first or the line has no direction word.
+fn spindle_word_first(line: &str, letter: char) -> Option<String> {
+    let number = spindle_number(line, letter)?;
+    let direction = direction_word(line)?;
+    let word_start = line[..number.start].trim_end().len() - letter.len_utf8();
+    if word_start < direction.start {
+        return None;
+    }
+    Some(format!(
+        "{}{}{} {}{}",
+        &line[..direction.start],
+        letter,
+        &line[number.clone()],
+        line[direction.start..word_start].trim_end(),
+        &line[number.end..]
+    ))
+}
+
+/// Plain-language description of which lines [`rewrite_content`] treats as the spindle
+/// command, for the in-app help. Keep in step with [`rewrite_spindle_line`].
+pub fn detection_rules(options: &UpdateOptions, language: Language) -> Vec<String> {
+    let t = |text: Text| text.get(language);
+    let mut rules = if options.spindle_target == SpindleTarget::Main {
+        single_spindle_rules(options, language)
+    } else {
+        indexed_detection_rules(options, language)
+    };
+    if let Some(speed_override) = &options.speed_override {
+        rules.push(fill(
+            t(Text::RuleSpeedOverride),
+            &[
+                &speed_override.marker,
+                &speed_override.marker,
+                speed_override.speed_range.start(),
+                speed_override.speed_range.end(),
+            ],
+        ));
+    }
+    if options.each_tool_change {
+        rules.insert(0, t(Text::RuleEachToolChange).to_string());
+    } else if options.after_tool_change {
+        rules.insert(0, t(Text::RuleAfterToolChange).to_string());
+    }
+    if options.spindle_letter != 'S' {
+        rules.insert(
+            0,
+            fill(
+                t(Text::RuleSpindleLetter),
+                &[&options.spindle_letter, &options.spindle_letter],
+            ),
+        );
+    }
+    rules
+}
+
+/// [`detection_rules`] for the bare spindle word of a single-spindle machine.
+fn single_spindle_rules(options: &UpdateOptions, language: Language) -> Vec<String> {
+    let t = |text: Text| text.get(language);
+    let mut rules = vec![
+        t(Text::RuleIndexedLeftAlone).to_string(),
+        t(Text::RuleFirstOnly).to_string(),
+        fill(t(Text::RuleLineStartsWithS), &[&options.spindle_on]),
+        t(Text::RuleFirstSWord).to_string(),
+        t(Text::RuleScientific).to_string(),
+        t(Text::RuleGluedWords).to_string(),
+        t(Text::RuleComments).to_string(),
+        t(Text::RuleContinuedBlock).to_string(),
+    ];
+    if let Some(expected) = options.match_current_speed {
+        rules.push(fill(t(Text::RuleMatchCurrent), &[&expected]));
+    }
+    if options.trim_rewritten_lines {
+        rules.push(t(Text::RuleTrim).to_string());
+    }
+    if options.uppercase_m_codes {
+        rules.push(t(Text::RuleUppercase).to_string());
+    }
+    if options.pad_speed_width {
+        rules.push(t(Text::RulePadWidth).to_string());
+    }
+    if options.spindle_word_first {
+        rules.push(fill(
+            t(Text::RuleWordFirst),
+            &[&options.spindle_letter, &options.spindle_letter],
+        ));
+    }
+    rules.push(match (&options.insert_missing, &options.insertion_marker) {
+        (Some(_), Some(marker)) if marker.required => fill(
+            t(Text::RuleInsertAfterMarkerOnly),
+            &[&options.spindle_on, &marker.text],
+        ),
+        (Some(point), Some(marker)) => fill(
+            t(Text::RuleInsertAfterMarker),
+            &[&options.spindle_on, &marker.text, &point.label(language)],
+        ),
+        (Some(point), None) => fill(
+            t(Text::RuleInsert),
+            &[&options.spindle_on, &point.label(language)],
+        ),
+        (None, _) => t(Text::RuleMissingSkipped).to_string(),
+    });
+    rules
+}
+
+/// [`detection_rules`] when indexed spindle words are targeted.
+fn indexed_detection_rules(options: &UpdateOptions, language: Language) -> Vec<String> {
+    let t = |text: Text| text.get(language);
+    let target = match options.spindle_target {
+        SpindleTarget::Indexed(index) => fill(t(Text::RuleIndexedTarget), &[&index]),
+        _ => t(Text::RuleEachIndexed).to_string(),
+    };
+    let mut rules = vec![
+        fill(t(Text::RuleIndexedFirst), &[&target]),
+        t(Text::RuleOtherSpindles).to_string(),
+        t(Text::RuleIndexedScientific).to_string(),
+        t(Text::RuleComments).to_string(),
+        t(Text::RuleIndexedNoInsert).to_string(),
+    ];
+    if let Some(expected) = options.match_current_speed {
+        rules.push(fill(t(Text::RuleIndexedMatchCurrent), &[&expected]));
+    }
+    rules
+}
+
+/// Words of a line before any comment.
+fn words(line: &str) -> impl Iterator<Item = &str> {
+    line.split_whitespace()
+        .take_while(|word| !word.starts_with('(') && !word.starts_with(';'))
+}
+
+fn is_motion_block(line: &str) -> bool {
+    words(line).any(|word| {
+        word.strip_prefix('G')
+            .map(|rest| {
+                let digits: String = rest.chars().take_while(char::is_ascii_digit).collect();
+                matches!(digits.parse::<u32>(), Ok(0..=3))
+            })
+            .unwrap_or(false)
+    })
+}
+
+/// The number of `word` when it is `letter` followed by digits only, e.g. 6 for `M06`.
+fn word_number(word: &str, letter: char) -> Option<u64> {
+    word.strip_prefix(letter)
+        .filter(|rest| !rest.is_empty() && rest.chars().all(|c| c.is_ascii_digit()))
+        .and_then(|rest| rest.parse().ok())
+}
+
+/// A block with an `M6` (`M06`) tool change.
+fn is_m6_block(line: &str) -> bool {
+    words(line).any(|word| word_number(word, 'M') == Some(6))
+}
+
+/// A block selecting or changing a tool: a `T` word or `M6` (`M06`).
+fn is_tool_change_block(line: &str) -> bool {
+    is_m6_block(line) || words(line).any(|word| word_number(word, 'T').is_some())
+}
+
+/// Index of the first line spindle words are looked for on: the first `M6` block with
+/// `each_tool_change`, the first tool-change block with `after_tool_change`, so its own
+/// spindle word counts, otherwise 0.
+fn first_updatable_line(content: &str, options: &UpdateOptions) -> Result<usize, SkipReason> {
+    let is_start: fn(&str) -> bool = if options.each_tool_change {
+        is_m6_block
+    } else if options.after_tool_change {
+        is_tool_change_block
+    } else {
+        return Ok(0);
+    };
+    content
+        .lines()
+        .position(is_start)
+        .ok_or(SkipReason::NoToolChange)
+}
+
+/// `content` from line `index` on.
+fn from_line(content: &str, index: usize) -> &str {
+    let offset: usize = content
+        .split_inclusive('\n')
+        .take(index)
+        .map(str::len)
+        .sum();
+    &content[offset..]
+}
+
+/// `lines` joined with the line endings of `original`, which they replace one for one
+/// apart from inserted lines. An inserted line gets the ending of the original's first
+/// line, and the last line keeps the original's final newline or lack of one.
+fn join_lines<S: AsRef<str>>(original: &str, lines: &[S]) -> String {
+    let old: Vec<&str> = original.split_inclusive('\n').collect();
+    let ending = |line: &str| -> usize {
+        match line.strip_suffix('\n') {
+            Some(text) if text.ends_with('\r') => 2,
+            Some(_) => 1,
+            None => 0,
+        }
+    };
+    let default_ending = match old.first().map(|line| ending(line)) {
+        Some(2) => "\r\n",
+        _ => "\n",
+    };
+
+    let unterminated = !original.is_empty() && !original.ends_with('\n');
+
+    let mut joined = String::with_capacity(original.len() + 64);
+    let mut i = 0;
+    for (j, line) in lines.iter().enumerate() {
+        let line = line.as_ref();
+        // A line appended after an unterminated last line ends the file instead of it.
+        if !joined.is_empty() && !joined.ends_with('\n') {
+            joined.push_str(default_ending);
+        }
+        joined.push_str(line);
+        let same = old
+            .get(i)
+            .is_some_and(|old| old[..old.len() - ending(old)] == *line);
+        if !same && lines.len() - j > old.len() - i {
+            if !(unterminated && j + 1 == lines.len()) {
+                joined.push_str(default_ending);
+            }
+        } else {
+            joined.push_str(&old[i][old[i].len() - ending(old[i])..]);
+            i += 1;
+        }
+    }
+    joined
+}
+
+fn is_header_line(line: &str) -> bool {
+    let line = line.trim();
+    line.is_empty()
+        || line == "%"
+        || line.starts_with('O')
+        || line.starts_with('(')
+        || line.starts_with(';')
+}
+
+/// Index of the line the inserted spindle command should come before.
+fn insertion_index(lines: &[&str], point: InsertionPoint) -> usize {
+    let after_header = lines
+        .iter()
+        .position(|line| !is_header_line(line))
+        .unwrap_or(lines.len());
+
+    match point {
+        InsertionPoint::AfterFirstMotion => lines
+            .iter()
+            .position(|line| is_motion_block(line))
+            .map_or(after_header, |index| index + 1),
+        InsertionPoint::AfterHeader => after_header,
+    }
+}
+
+/// Index of the line after the first one containing `marker`, ignoring case.
+fn marker_index(lines: &[&str], marker: &str) -> Option<usize> {
+    let marker = marker.to_lowercase();
+    lines
+        .iter()
+        .position(|line| line.to_lowercase().contains(&marker))
+        .map(|index| index + 1)
+}
+
+/// Applies the spindle speed to the first spindle command of `content`, and stamps the
+/// program if that changed it and [`UpdateOptions::change_stamp`] is set.
+///
+/// Fails when the program has no spindle command and inserting one is disabled, or when
+/// the first spindle command uses a notation that can't be rewritten safely.
+pub fn rewrite_content(
+    content: &str,
+    spindle_speed: u32,
+    options: &UpdateOptions,
+) -> Result<ContentUpdate, SkipReason> {
+    let mut update = rewrite_spindle_commands(content, spindle_speed, options)?;
+    let stamp = options
+        .change_stamp
+        .as_ref()
+        .filter(|stamp| update.changed && ChangeStamp::is_comment(&stamp.template));
+    if let Some(stamp) = stamp {
+        update.content = stamp.apply(&update.content, update.speed);
+    }
+    if options.check_structure && update.changed {
+        let letter = options.spindle_letter;
+        let inserted = update.inserted;
+        if let Some(line) = structure_violation(content, &update.content, letter, inserted, stamp) {
+            return Err(SkipReason::StructureChanged { line });
+        }
+    }
+    Ok(update)
+}
+
+/// Whether `line` holds a spindle word, bare (`S1000`, `S 1000`) or indexed (`S2=1500`).
+fn has_spindle_word(line: &str, letter: char) -> bool {
+    spindle_number(line, letter).is_some()
+        || word_offsets(line)
+            .iter()
+            .any(|&(_, word)| indexed_spindle_word(word, letter).is_some())
+}
+
+/// The first line of `updated`, counted from 1, that breaks the rule every update keeps:
+/// it is `original` line for line, apart from lines with a spindle word both before and
+/// after, the one spindle line an insertion adds, and the `stamp` line, added or in
+/// place of an earlier stamp. A line missing at the end counts as the line after the
+/// last. Line endings aren't compared.
+pub fn structure_violation(
+    original: &str,
+    updated: &str,
+    letter: char,
+    inserted: bool,
+    stamp: Option<&ChangeStamp>,
+) -> Option<usize> {
+    let original: Vec<&str> = original.lines().collect();
+    let is_stamp = |line: &str| stamp.is_some_and(|stamp| stamp.matches(line));
+    let mut next = 0;
+    let mut may_insert = inserted;
+    let mut may_stamp = stamp.is_some();
+    let mut count = 0;
+    for (index, line) in updated.lines().enumerate() {
+        count = index + 1;
+        match original.get(next) {
+            Some(&old) if old == line => next += 1,
+            Some(&old) if has_spindle_word(old, letter) && has_spindle_word(line, letter) => {
+                next += 1
+            }
+            Some(&old) if is_stamp(old) && is_stamp(line) => next += 1,
+            _ if may_stamp && is_stamp(line) => may_stamp = false,
+            _ if may_insert && has_spindle_word(line, letter) => may_insert = false,
+            _ => return Some(index + 1),
+        }
+    }
+    (next < original.len()).then_some(count + 1)
+}
+
+fn rewrite_spindle_commands(
+    content: &str,
+    spindle_speed: u32,
+    options: &UpdateOptions,
+) -> Result<ContentUpdate, SkipReason> {
+    if content.trim().is_empty() {
+        return Err(SkipReason::Empty);
+    }
+    let spindle_speed = match &options.speed_override {
+        Some(speed_override) => speed_override.apply(content, spindle_speed)?,
+        None => spindle_speed,
+    };
+    let start = first_updatable_line(content, options)?;
+
+    if let Some(expected) = options.match_current_speed {
+        let matches =
+            current_speed_value(content, options).is_some_and(|speed| speed == f64::from(expected));
+        if !matches {
+            return Err(SkipReason::SpeedMismatch {
+                current: current_speed_text(content, options)
+                    .unwrap_or("none")
+                    .to_string(),
+                expected,
+            });
+        }
+    }
+
+    if options.spindle_target != SpindleTarget::Main {
+        return rewrite_indexed_spindles(content, spindle_speed, options, start);
+    }
+
+    let mut updated_lines: Vec<String> = Vec::new();
+    let mut found_s_command = false;
+    let mut found_any = false;
+    let mut line_changes = Vec::new();
+    let mut inserted = false;
+
+    let mut previous_line = "";
+    for (index, line) in content.lines().enumerate() {
+        if options.each_tool_change && is_m6_block(line) {
+            found_s_command = false;
+        }
+        if found_s_command || index < start {
+            updated_lines.push(line.to_string());
+        } else if let Some(spindle_line) = rewrite_spindle_line(line, spindle_speed, options) {
+            if continues_on_next_line(previous_line) {
+                return Err(SkipReason::ContinuedBlock { line: index + 1 });
+            }
+            let new_line = match spindle_line {
+                SpindleLine::Rewritten(new_line) => new_line,
+                SpindleLine::Unsupported(notation) => {
+                    return Err(SkipReason::UnsupportedNotation { notation })
+                }
+            };
+            found_s_command = true;
+            found_any = true;
+            push_rewritten(
+                line,
+                new_line,
+                options,
+                &mut updated_lines,
+                &mut line_changes,
+            );
+        } else {
+            updated_lines.push(line.to_string());
+        }
+        previous_line = line;
+    }
+
+    if !found_any {
+        let point = options.insert_missing.ok_or(SkipReason::NoSpindleCommand)?;
+        let lines: Vec<&str> = content.lines().collect();
+        let index = match &options.insertion_marker {
+            Some(marker) => match marker_index(&lines, &marker.text) {
+                Some(index) => index,
+                None if marker.required => {
+                    return Err(SkipReason::MarkerNotFound {
+                        marker: marker.text.clone(),
+                    })
+                }
+                None => insertion_index(&lines, point),
+            },
+            None => insertion_index(&lines, point),
+        }
+        .max(if options.after_tool_change || options.each_tool_change {
+            start + 1
+        } else {
+            0
+        });
+        let new_line = format!(
+            "{}{} {}",
+            options.spindle_letter, spindle_speed, options.spindle_on
+        );
+        line_changes.push(LineChange {
+            old: String::new(),
+            new: new_line.clone(),
+        });
+        updated_lines.insert(index, new_line);
+        inserted = true;
+    }
+
+    Ok(ContentUpdate {
+        content: join_lines(content, &updated_lines),
+        speed: spindle_speed,
+        changed: !line_changes.is_empty(),
+        inserted,
+        line_changes,
+    })
+}
+
+/// Pushes the rewritten form of `line`, recording the change unless only surrounding
+/// whitespace differs.
+fn push_rewritten(
+    line: &str,
+    new_line: String,
+    options: &UpdateOptions,
+    updated_lines: &mut Vec<String>,
+    line_changes: &mut Vec<LineChange>,
+) {
+    if line.trim() == new_line.trim() {
+        updated_lines.push(line.to_string());
+        return;
+    }
+    let new_line = if options.trim_rewritten_lines {
+        new_line.trim_end().to_string()
+    } else {
+        new_line
+    };
+    line_changes.push(LineChange {
+        old: line.to_string(),
+        new: new_line.clone(),
+    });
+    updated_lines.push(new_line);
+}
+
+/// [`rewrite_content`] for indexed spindle words (`S1=1000`): the number of the first
+/// word of every targeted spindle is replaced, and bare `S` words are left alone. Nothing
+/// is ever inserted, since there is no telling which spindle a missing word belongs to.
+fn rewrite_indexed_spindles(
+    content: &str,
+    spindle_speed: u32,
+    options: &UpdateOptions,
+    start: usize,
+) -> Result<ContentUpdate, SkipReason> {
+    let mut updated_spindles: Vec<u8> = Vec::new();
+    let mut found_any = false;
+    let mut updated_lines: Vec<String> = Vec::new();
+    let mut line_changes = Vec::new();
+
+    for (line_index, line) in content.lines().enumerate() {
+        if line_index < start {
+            updated_lines.push(line.to_string());
+            continue;
+        }
+        if options.each_tool_change && is_m6_block(line) {
+            updated_spindles.clear();
+        }
+        let mut numbers = Vec::new();
+        for (offset, word) in word_offsets(line) {
+            let Some((index, range)) = indexed_spindle_word(word, options.spindle_letter) else {
+                continue;
+            };
+            if !options.spindle_target.addresses(index) || updated_spindles.contains(&index) {
+                continue;
+            }
+            let number = &word[range.clone()];
+            if !is_plain_number(number) {
+                return Err(SkipReason::UnsupportedNotation {
+                    notation: format!("{}{}={}", options.spindle_letter, index, number),
+                });
+            }
+            updated_spindles.push(index);
+            found_any = true;
+            numbers.push(offset + range.start..offset + range.end);
+        }
+
+        if numbers.is_empty() {
+            updated_lines.push(line.to_string());
+            continue;
+        }
+        let mut new_line = line.to_string();
+        for range in numbers.into_iter().rev() {
+            let speed = speed_text(spindle_speed, &line[range.clone()], options);
+            new_line.replace_range(range, &speed);
+        }
+        push_rewritten(
+            line,
+            new_line,
+            options,
+            &mut updated_lines,
+            &mut line_changes,
+        );
+    }
+
+    if !found_any {
+        return Err(SkipReason::NoSpindleCommand);
+    }
+    Ok(ContentUpdate {
+        content: join_lines(content, &updated_lines),
+        speed: spindle_speed,
+        changed: !line_changes.is_empty(),
+        inserted: false,
+        line_changes,
+    })
+}
+
+pub fn update_spindle_speed_in_content(
+    content: &str,
+    spindle_speed: u32,
+    options: &UpdateOptions,
+) -> Result<String, SpindleSpeedUpdaterError> {
+    rewrite_content(content, spindle_speed, options)
+        .map(|update| update.content)
+        .map_err(|reason| SpindleSpeedUpdaterError::InvalidSpindleSpeed(reason.to_string()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn rewrite(content: &str) -> String {
+        update_spindle_speed_in_content(content, 12000, &UpdateOptions::default()).unwrap()
+    }
+
+    #[test]
+    fn tab_separated_words_keep_their_tabs() {
+        assert_eq!(rewrite("N100\tS1000\tM3"), "N100\tS12000\tM3");
+    }
+
+    #[test]
+    fn leading_spindle_word_is_rewritten_in_place() {
+        assert_eq!(rewrite("\tS1000\tM3 (SPINDLE)"), "\tS12000\tM3 (SPINDLE)");
+        assert_eq!(rewrite("  S1000"), "  S12000 M3");
+        assert_eq!(rewrite("S1000 (SPINDLE)"), "S12000 M3 (SPINDLE)");
+    }
+
+    #[test]
+    fn crlf_endings_and_final_newline_survive_a_rewrite() {
+        assert_eq!(
+            rewrite("G21\r\nS1000 M3\r\nM30\r\n"),
+            "G21\r\nS12000 M3\r\nM30\r\n"
+        );
+        assert_eq!(rewrite("G21\nS1000 M3\r\nM30"), "G21\nS12000 M3\r\nM30");
+    }
+
+    #[test]
+    fn inserted_lines_take_the_files_line_ending() {
+        let options = UpdateOptions {
+            insert_missing: Some(InsertionPoint::AfterFirstMotion),
+            ..UpdateOptions::default()
+        };
+        let update = rewrite_content("G21\r\nG0 X0\r\nM30\r\n", 12000, &options).unwrap();
+        assert_eq!(update.content, "G21\r\nG0 X0\r\nS12000 M3\r\nM30\r\n");
+    }
+
+    fn inserting(point: InsertionPoint) -> UpdateOptions {
+        UpdateOptions {
+            insert_missing: Some(point),
+            ..UpdateOptions::default()
+        }
+    }
+
+    #[test]
+    fn missing_spindle_command_is_inserted_at_the_chosen_point() {
+        let program = "%\nO1000\n(FACE)\nG21 G90\nG0 X0 Y0\nG1 X10\nM30\n%\n";
+        let after_motion =
+            rewrite_content(program, 12000, &inserting(InsertionPoint::AfterFirstMotion));
+        assert_eq!(
+            after_motion.unwrap().content,
+            "%\nO1000\n(FACE)\nG21 G90\nG0 X0 Y0\nS12000 M3\nG1 X10\nM30\n%\n"
+        );
+        let after_header = rewrite_content(program, 12000, &inserting(InsertionPoint::AfterHeader));
+        assert_eq!(
+            after_header.unwrap().content,
+            "%\nO1000\n(FACE)\nS12000 M3\nG21 G90\nG0 X0 Y0\nG1 X10\nM30\n%\n"
+        );
+    }
+
+    #[test]
+    fn program_without_motion_gets_the_command_after_its_header() {
+        let update = rewrite_content(
+            "%\nO1000\nG21\nM30\n",
+            12000,
+            &inserting(InsertionPoint::AfterFirstMotion),
+        );
+        assert_eq!(update.unwrap().content, "%\nO1000\nS12000 M3\nG21\nM30\n");
+    }
+
+    #[test]
+    fn insertion_leaves_programs_with_a_spindle_command_alone() {
+        let options = inserting(InsertionPoint::AfterFirstMotion);
+        let update = rewrite_content("G21\nG0 X0\nS1000 M3\nM30\n", 12000, &options).unwrap();
+        assert!(!update.inserted);
+        assert_eq!(update.content, "G21\nG0 X0\nS12000 M3\nM30\n");
+
+        let update = rewrite_content("G21\nG0 X0\nS12000 M3\nM30\n", 12000, &options).unwrap();
+        assert!(!update.changed);
+    }
+
+    #[test]
+    fn missing_spindle_command_is_skipped_without_the_option() {
+        assert_eq!(
+            rewrite_content("G21\nG0 X0\nM30\n", 12000, &UpdateOptions::default()).err(),
+            Some(SkipReason::NoSpindleCommand)
+        );
+    }
+
+    #[test]
+    fn line_appended_after_an_unterminated_last_line_ends_the_file() {
+        assert_eq!(
+            join_lines("G21\nM30", &["G21", "M30", "S12000 M3"]),
+            "G21\nM30\nS12000 M3"
+        );
+    }
+
+    fn stamped(template: &str, content: &str) -> Result<ContentUpdate, SkipReason> {
+        let options = UpdateOptions {
+            change_stamp: Some(ChangeStamp {
+                template: template.to_string(),
+                date: "2024-06-01".to_string(),
+            }),
+            ..UpdateOptions::default()
+        };
+        rewrite_content(content, 12000, &options)
+    }
+
+    #[test]
+    fn stamp_that_is_not_a_comment_is_never_applied() {
+        let update = stamped("{speed}", "G21\nS1000 M3\nM30\n").unwrap();
+        assert_eq!(update.content, "G21\nS12000 M3\nM30\n");
+    }
+
+    #[test]
+    fn stamp_only_replaces_a_whole_line_with_a_speed_and_a_date() {
+        let template = "(SPEED SET TO {speed} ON {date})";
+        let update = stamped(
+            template,
+            "(SPEED SET TO FAST ON MONDAY)\nG21 (SPEED SET TO 9000 ON 2023-01-15)\nS1000 M3\n",
+        )
+        .unwrap();
+        assert_eq!(
+            update.content,
+            "(SPEED SET TO 12000 ON 2024-06-01)\n(SPEED SET TO FAST ON MONDAY)\n\
+             G21 (SPEED SET TO 9000 ON 2023-01-15)\nS12000 M3\n"
+        );
+
+        let update = stamped(template, "(SPEED SET TO 9000 ON 2023-01-15)\nS1000 M3\n").unwrap();
+        assert_eq!(
+            update.content,
+            "(SPEED SET TO 12000 ON 2024-06-01)\nS12000 M3\n"
+        );
+    }
+
+    #[test]
+    fn semicolon_stamp_passes_the_structure_check() {
+        let update = stamped("; S{speed} {date}", "%\nO1000\nS1000 M3\nM30\n%\n").unwrap();
+        assert_eq!(
+            update.content,
+            "%\nO1000\n; S12000 2024-06-01\nS12000 M3\nM30\n%\n"
+        );
+    }
+
+    #[test]
+    fn trimming_only_touches_the_rewritten_line() {
+        let options = UpdateOptions {
+            trim_rewritten_lines: true,
+            ..UpdateOptions::default()
+        };
+        let update = rewrite_content("G21  \nS1000 M3   \nM30 \t\n", 12000, &options);
+        assert_eq!(update.unwrap().content, "G21  \nS12000 M3\nM30 \t\n");
+
+        let untrimmed = rewrite("G21  \nS1000 M3   \nM30 \t\n");
+        assert_eq!(untrimmed, "G21  \nS12000 M3   \nM30 \t\n");
+    }
+
+    fn targeting(spindle_target: SpindleTarget) -> UpdateOptions {
+        UpdateOptions {
+            spindle_target,
+            ..UpdateOptions::default()
+        }
+    }
+
+    const TWO_SPINDLES: &str = "G18\nS1=1000 S2=2000 M3\nS1=1500\nS1000 M3\nM30\n";
+
+    #[test]
+    fn each_indexed_spindle_can_be_targeted_alone() {
+        let first = rewrite_content(TWO_SPINDLES, 12000, &targeting(SpindleTarget::Indexed(1)));
+        assert_eq!(
+            first.unwrap().content,
+            "G18\nS1=12000 S2=2000 M3\nS1=1500\nS1000 M3\nM30\n"
+        );
+        let second = rewrite_content(TWO_SPINDLES, 12000, &targeting(SpindleTarget::Indexed(2)));
+        assert_eq!(
+            second.unwrap().content,
+            "G18\nS1=1000 S2=12000 M3\nS1=1500\nS1000 M3\nM30\n"
+        );
+    }
+
+    #[test]
+    fn every_indexed_spindle_is_targeted_together() {
+        let update = rewrite_content(TWO_SPINDLES, 12000, &targeting(SpindleTarget::AllIndexed));
+        assert_eq!(
+            update.unwrap().content,
+            "G18\nS1=12000 S2=12000 M3\nS1=1500\nS1000 M3\nM30\n"
+        );
+    }
+
+    #[test]
+    fn bare_spindle_word_ignores_indexed_ones() {
+        assert_eq!(
+            rewrite(TWO_SPINDLES),
+            "G18\nS1=1000 S2=2000 M3\nS1=1500\nS12000 M3\nM30\n"
+        );
+    }
+
+    #[test]
+    fn program_without_the_targeted_spindle_is_skipped() {
+        let update = rewrite_content("S1=1000 M3\n", 12000, &targeting(SpindleTarget::Indexed(2)));
+        assert_eq!(update.err(), Some(SkipReason::NoSpindleCommand));
+    }
+
+    #[test]
+    fn configured_letter_is_detected_and_replaced() {
+        let options = UpdateOptions {
+            spindle_letter: 'P',
+            ..UpdateOptions::default()
+        };
+        let update = rewrite_content("G21\nS500 M3\nN20 P1000 M3\nM30\n", 12000, &options);
+        assert_eq!(
+            update.unwrap().content,
+            "G21\nS500 M3\nN20 P12000 M3\nM30\n"
+        );
+    }
+
+    #[test]
+    fn preamble_spindle_word_is_left_before_the_first_tool_change() {
+        let options = UpdateOptions {
+            after_tool_change: true,
+            ..UpdateOptions::default()
+        };
+        let program = "%\nS0 M5\nG21\nT1 M6\nS1000 M3\nM30\n";
+        let update = rewrite_content(program, 12000, &options).unwrap();
+        assert_eq!(update.content, "%\nS0 M5\nG21\nT1 M6\nS12000 M3\nM30\n");
+
+        let update = rewrite_content("G21\nT1 M6 S1000 M3\nM30\n", 12000, &options).unwrap();
+        assert_eq!(update.content, "G21\nT1 M6 S12000 M3\nM30\n");
+
+        let skipped = rewrite_content("S0 M5\nS1000 M3\n", 12000, &options);
+        assert_eq!(skipped.err(), Some(SkipReason::NoToolChange));
+    }
+
+    #[test]
+    fn word_starting_with_the_letter_but_no_number_is_left_alone() {
+        assert_eq!(rewrite("SPOS=0\nS8000 M3"), "SPOS=0\nS12000 M3");
+    }
+
+    #[test]
+    fn padded_speed_keeps_the_old_numbers_width() {
+        let padded = UpdateOptions {
+            pad_speed_width: true,
+            ..UpdateOptions::default()
+        };
+        let update = |options: &UpdateOptions| {
+            update_spindle_speed_in_content("S01000 M3", 1200, options).unwrap()
+        };
+        assert_eq!(update(&padded), "S01200 M3");
+        assert_eq!(update(&UpdateOptions::default()), "S1200 M3");
+        let wider = update_spindle_speed_in_content("S0900.0 M3", 12000, &padded).unwrap();
+        assert_eq!(wider, "S12000 M3");
+    }
+
+    #[test]
+    fn spindle_word_after_the_direction_is_found_and_left_in_place() {
+        assert_eq!(rewrite("M3 S1000"), "M3 S12000");
+        assert_eq!(rewrite("N30 M3 S1000"), "N30 M3 S12000");
+    }
+
+    #[test]
+    fn spindle_word_first_reorders_only_the_spindle_words() {
+        let reordering = UpdateOptions {
+            spindle_word_first: true,
+            ..UpdateOptions::default()
+        };
+        let update =
+            |content: &str| update_spindle_speed_in_content(content, 12000, &reordering).unwrap();
+        assert_eq!(update("M3 S1000"), "S12000 M3");
+        assert_eq!(update("N30 M3 S 9000"), "N30 S12000 M3");
+        assert_eq!(update("G0 X1 Y2 M3 S1000 (ON)"), "G0 X1 Y2 S12000 M3 (ON)");
+        assert_eq!(update("S1000 M3"), "S12000 M3");
+    }
+
+    #[test]
+    fn direction_and_gear_words_after_the_speed_are_kept() {
+        assert_eq!(rewrite("G21\nS1000 m4\nM30\n"), "G21\nS12000 m4\nM30\n");
+        assert_eq!(
+            rewrite("G18\nS1000 M42 M3 (HIGH GEAR)\nM30\n"),
+            "G18\nS12000 M42 M3 (HIGH GEAR)\nM30\n"
+        );
+        assert_eq!(rewrite("G21\nS001000 M3\nM30\n"), "G21\nS12000 M3\nM30\n");
+    }
+
+    #[test]
+    fn programs_without_a_spindle_word_outside_comments_are_skipped() {
+        let reason =
+            |content: &str| rewrite_content(content, 12000, &UpdateOptions::default()).unwrap_err();
+        assert_eq!(
+            reason("G21 G90\nG0 X0 Y0\nM30\n"),
+            SkipReason::NoSpindleCommand
+        );
+        assert_eq!(
+            reason("(S5000 IN A COMMENT)\nG0 X0\nM30\n"),
+            SkipReason::NoSpindleCommand
+        );
+        assert_eq!(reason("\n  \n\n"), SkipReason::Empty);
+    }
+
+    fn overriding(minimum: u32) -> UpdateOptions {
+        UpdateOptions {
+            speed_override: Some(SpeedOverride {
+                marker: "SPEED OVERRIDE".to_string(),
+                speed_range: minimum..=24000,
+            }),
+            ..UpdateOptions::default()
+        }
+    }
+
+    #[test]
+    fn override_comment_scales_the_speed_and_its_absence_keeps_it() {
+        let options = overriding(1);
+        let update = |content: &str| rewrite_content(content, 12000, &options).unwrap().content;
+        assert_eq!(
+            update("(SPEED OVERRIDE 80%)\nS1000 M3\nM30\n"),
+            "(SPEED OVERRIDE 80%)\nS9600 M3\nM30\n"
+        );
+        assert_eq!(
+            update("(TOOL 1)\nS1000 M3\nM30\n"),
+            "(TOOL 1)\nS12000 M3\nM30\n"
+        );
+    }
+
+    #[test]
+    fn override_that_stops_the_spindle_or_leaves_the_range_is_skipped() {
+        let reason = |percent: &str, minimum: u32| {
+            let content = format!("(SPEED OVERRIDE {}%)\nS1000 M3\nM30\n", percent);
+            rewrite_content(&content, 12000, &overriding(minimum)).unwrap_err()
+        };
+        assert!(matches!(
+            reason("0", 1),
+            SkipReason::OverrideStopsSpindle { line: 1, .. }
+        ));
+        assert!(matches!(
+            reason("5", 1000),
+            SkipReason::OverrideBelowMinimum {
+                speed: 600,
+                minimum: 1000,
+                ..
+            }
+        ));
+        assert!(matches!(
+            reason("300", 1),
+            SkipReason::OverrideOutOfRange {
+                speed: 36000,
+                maximum: 24000,
+                ..
+            }
+        ));
+    }
+
+    #[test]
+    fn file_already_at_the_speed_gets_no_stamp() {
+        let update = stamped("(SPEED SET TO {speed} ON {date})", "G21\nS12000 M3\nM30\n").unwrap();
+        assert!(!update.changed);
+        assert_eq!(update.content, "G21\nS12000 M3\nM30\n");
+    }
+
+    /// A second run at the same speed must leave every output alone, so it reports the
+    /// file as already correct instead of rewriting it over spacing or line endings.
+    #[test]
+    fn second_pass_changes_nothing() {
+        let stamping = UpdateOptions {
+            change_stamp: Some(ChangeStamp {
+                template: "(SPEED SET TO {speed} ON {date})".to_string(),
+                date: "2024-06-01".to_string(),
+            }),
+            ..UpdateOptions::default()
+        };
+        let reordering = UpdateOptions {
+            spindle_word_first: true,
+            ..UpdateOptions::default()
+        };
+        let cases = [
+            ("G21\r\n  S1000\r\nM30", UpdateOptions::default()),
+            ("N10 G0 X1. M3 S 1000 M8\n", reordering),
+            ("%\nO1000\nS1000 M3\nM30\n%\n", stamping),
+            ("(SPEED OVERRIDE 80%)\nS1000 M3\n", overriding(1)),
+            (
+                "G21\nG0 X0\nM30\n",
+                inserting(InsertionPoint::AfterFirstMotion),
+            ),
+        ];
+        for (content, options) in cases {
+            let first = rewrite_content(content, 12000, &options).unwrap();
+            assert!(first.changed, "{:?}", content);
+            let second = rewrite_content(&first.content, 12000, &options).unwrap();
+            assert!(!second.changed, "{:?} changed again", first.content);
+            assert_eq!(second.content, first.content);
+        }
+    }
+
+    #[test]
+    fn structure_check_flags_the_first_line_a_parser_bug_would_break() {
+        let violation =
+            |updated: &str| structure_violation("G21\nS1000 M3\nM30\n", updated, 'S', false, None);
+        assert_eq!(violation("G21\nS12000 M3\nM30\n"), None);
+        assert_eq!(violation("G20\nS12000 M3\nM30"), Some(1));
+        assert_eq!(violation("G21\nS12000 M3"), Some(3));
+        assert_eq!(violation("G21\nS12000 M3\nG0 X0\nM30"), Some(3));
+    }
+}
@@ -0,0 +1,3626 @@
+//! The window: folder selection, the speed input, previews, the confirmation dialog and
+//! the run's progress and results, all on top of the library.
+
+mod notify;
+mod open;
+mod viewer;
+
+use eframe::egui;
+use spindle_speed_manager::audit::{audit_csv, audit_files, AuditEntry};
+use spindle_speed_manager::backup::{
+    backed_up_runs, plan_restore, restore_files, BackedUpRun, RestoreAction, RestoreItem,
+    RestoreSummary,
+};
+use spindle_speed_manager::compare::{compare_folders, FolderComparison};
+use spindle_speed_manager::gcode::{
+    detection_rules, rewrite_content, ChangeStamp, InsertionPoint, SpindleTarget,
+};
+use spindle_speed_manager::history::{append_run, history_path, HISTORY_FILE_NAME};
+use spindle_speed_manager::mapping::{
+    compare_mapping, load_mapping, rule_for, MappingReport, SpeedMapping, SpeedPlan,
+};
+use spindle_speed_manager::patch::build_patch;
+use spindle_speed_manager::preview::{
+    build_preview, PreflightSummary, PreviewEntry, PreviewOutcome,
+};
+use spindle_speed_manager::progress::Progress;
+use spindle_speed_manager::project::{
+    load_project_rules, ProjectConfigStatus, ProjectRules, CONFIG_FILE_NAME,
+};
+use spindle_speed_manager::report::{RunComparison, RunReport};
+use spindle_speed_manager::safety::{
+    file_cap_refusal, missing_spindle_refusal, preflight_warning, self_check_warning,
+    speed_drop_warning,
+};
+use spindle_speed_manager::scan::{
+    canonical_roots, dedupe_roots, executable_folder, list_files, modified_within, picked_folders,
+    pin_to_preview, scan_folders_cancellable, FileInfo, ScanResult,
+};
+use spindle_speed_manager::settings::{
+    EnterAction, OutputMode, PathDisplay, Settings, SuccessCount,
+};
+use spindle_speed_manager::speed::{
+    format_rpm, parse_decimal, parse_spindle_speed, percent_speed, surface_speed_rpm, SpeedError,
+    SpeedMode, MIN_SPINDLE_SPEED,
+};
+use spindle_speed_manager::strings::{fill, Language, Text};
+use spindle_speed_manager::summary::{matched_count, success_message, summarize_by_root};
+use spindle_speed_manager::timestamp::UtcDateTime;
+use spindle_speed_manager::update::{
+    new_run_id, overlapping_folder, update_spindle_speed, FileOutcome, RunOptions,
+};
+use spindle_speed_manager::SpindleSpeedUpdaterError;
+use std::collections::{HashMap, VecDeque};
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc::{channel, Receiver, Sender};
+use std::sync::Arc;
+use std::time::{Duration, Instant, SystemTime};
+use tokio::sync::oneshot::{self};
+use tracing::{error, info, info_span, warn, Instrument};
+use viewer::{load_file_view, FileView};
+
+/// Lines kept in the run log pane; older ones are dropped first.
+const EVENT_LOG_LIMIT: usize = 1000;
+
+pub const WINDOW_TITLE: &str = "Spindle Speed Updater";
+
+/// What a run was started with, kept so its failed files can be retried the same way
+/// even after the speed or settings have been edited.
+#[derive(Clone)]
+struct RunSetup {
+    plan: SpeedPlan,
+    options: RunOptions,
+    target: String,
+    folders: Vec<PathBuf>,
+}
+
+/// Results of the restore panel's background work.
+enum RestoreMessage {
+    Runs(io::Result<Vec<BackedUpRun>>),
+    /// What restoring to before the named run would do.
+    Plan(String, io::Result<Vec<RestoreItem>>),
+    /// Done, with the run the replaced files were backed up as.
+    Restored(String, RestoreSummary),
+}
+
+#[allow(dead_code)]
+pub struct MainApp {
+    spindle_speed_input: String,
+    /// Name typed for the next profile to save.
+    profile_name_input: String,
+    /// The inputs of [`SpeedMode::Percent`] and [`SpeedMode::SurfaceSpeed`].
+    percent_input: String,
+    surface_speed_input: String,
+    diameter_input: String,
+    validated_spindle_speed: Option<u32>,
+    processing: bool,
+    progress: Arc<Progress>,
+    error_message: Option<String>,
+    error_sender: Sender<SpindleSpeedUpdaterError>,
+    error_receiver: Receiver<SpindleSpeedUpdaterError>,
+    completion_sender: Sender<RunReport>,
+    completion_receiver: Receiver<RunReport>,
+    preview_sender: Sender<Vec<PreviewEntry>>,
+    preview_receiver: Receiver<Vec<PreviewEntry>>,
+    previewing: bool,
+    preview: Option<Vec<PreviewEntry>>,
+    audit_sender: Sender<Vec<AuditEntry>>,
+    audit_receiver: Receiver<Vec<AuditEntry>>,
+    auditing: bool,
+    /// Every file of the last audit, in path order.
+    audit: Option<Vec<AuditEntry>>,
+    /// The preview being built is the pre-flight check of an update.
+    preflight_pending: bool,
+    safety_warning: Option<String>,
+    self_check_sender: Sender<Vec<PreviewEntry>>,
+    self_check_receiver: Receiver<Vec<PreviewEntry>>,
+    self_checking: bool,
+    /// Files the startup self-check found without a spindle command.
+    self_check_warning: Option<String>,
+    show_confirmation_dialog: bool,
+    /// Which confirmation button has the keyboard focus. It opens on No, the safe choice.
+    confirmation_focus_yes: bool,
+    /// The pending run was started with Enter set to [`EnterAction::Run`], so it starts
+    /// once the pre-flight checks pass instead of asking.
+    skip_confirmation: bool,
+    /// Why the pending run's dry pass doesn't meet [`Settings::clean_criteria`], if it
+    /// doesn't.
+    unclean_dry_pass: Option<String>,
+    /// Folders for the audit comparison, old then new.
+    compare_roots: [Option<PathBuf>; 2],
+    comparing: bool,
+    comparison: Option<FolderComparison>,
+    /// Pasted G-code and speed for the "Try it" panel, which never touches a file.
+    try_it_input: String,
+    try_it_speed: String,
+    comparison_sender: Sender<Result<FolderComparison, SpindleSpeedUpdaterError>>,
+    comparison_receiver: Receiver<Result<FolderComparison, SpindleSpeedUpdaterError>>,
+    restore_sender: Sender<RestoreMessage>,
+    restore_receiver: Receiver<RestoreMessage>,
+    restore_busy: bool,
+    /// Runs with backups in the selected folders, newest first, once looked up.
+    backed_up_runs: Option<Vec<BackedUpRun>>,
+    /// The run picked to restore to and what restoring would do to each file.
+    restore_plan: Option<(String, Vec<RestoreItem>)>,
+    /// The restore button was clicked and waits for Yes or No.
+    confirming_restore: bool,
+    exporting_patch: bool,
+    /// Where the patch went, or why it couldn't be written.
+    patch_sender: Sender<Result<PathBuf, SpindleSpeedUpdaterError>>,
+    patch_receiver: Receiver<Result<PathBuf, SpindleSpeedUpdaterError>>,
+    /// Root folders to process; overlapping entries are merged when scanning.
+    folders: Vec<PathBuf>,
+    /// Files picked one by one, processed instead of a scan. `folders` then holds the
+    /// folders they are in.
+    picked_files: Option<Vec<PathBuf>>,
+    /// The folders chosen before files were picked, restored by Back to folders.
+    folders_before_pick: Vec<PathBuf>,
+    /// No folder is remembered yet, so the first-launch prompt replaces the main view.
+    choosing_first_folder: bool,
+    /// Limits, extensions and M-code for this session, from a project config if found.
+    project_rules: ProjectRules,
+    project_config: ProjectConfigStatus,
+    speed_mapping: Option<SpeedMapping>,
+    mapping_report: Option<MappingReport>,
+    file_cache: HashMap<PathBuf, FileInfo>,
+    /// Paths left out of the cache because they lead to a file already in it.
+    duplicate_files: Vec<PathBuf>,
+    scan_sender: Sender<(Arc<AtomicBool>, ScanResult)>,
+    scan_receiver: Receiver<(Arc<AtomicBool>, ScanResult)>,
+    /// Cancel flag of the scan in progress; only that scan's result is applied.
+    scan_cancel: Option<Arc<AtomicBool>>,
+    /// Run the startup self-check once the first scan has finished.
+    self_check_after_scan: bool,
+    cancel_sender: Option<oneshot::Sender<()>>,
+    /// Cancel was requested; the run stops after the file being written.
+    cancelling: bool,
+    /// The window was asked to close while a run was in progress.
+    close_requested: bool,
+    /// Close the window as soon as the cancelled run has stopped.
+    close_after_stop: bool,
+    success_message: Option<String>,
+    success_shown_at: Option<Instant>,
+    /// Full breakdown of the last run, shown when its status chip is expanded.
+    run_summary: Option<String>,
+    /// Outcome of a run that just finished, for the window title and a desktop
+    /// notification when it isn't focused.
+    completion_notice: Option<String>,
+    /// The title shows a [`Self::completion_notice`] until the window is focused again.
+    title_shows_notice: bool,
+    status_expanded: bool,
+    run_id: Option<String>,
+    /// The last completed run; cleared when the next one starts.
+    last_run: Option<RunReport>,
+    /// What [`Self::last_run`] was started with.
+    last_run_setup: Option<RunSetup>,
+    /// The run in progress retries the failed files of [`Self::last_run`].
+    retrying: bool,
+    /// The latest run on the current folders, by their canonical paths, kept for
+    /// comparing the next run against.
+    previous_run: Option<(Vec<PathBuf>, RunReport)>,
+    /// How the last run differed from the one before it on the same folders.
+    run_changes: Option<RunComparison>,
+    event_sender: Sender<String>,
+    event_receiver: Receiver<String>,
+    /// One line per processed file of the current or last run, newest last.
+    event_log: VecDeque<String>,
+    file_view_sender: Sender<FileView>,
+    file_view_receiver: Receiver<FileView>,
+    /// The file shown in the read-only viewer window.
+    file_view: Option<FileView>,
+    last_enter_press: Instant,
+    settings: Settings,
+}
+
+impl MainApp {
+    pub fn new(cc: &eframe::CreationContext<'_>) -> Self {
+        Self::with_settings(
+            cc.storage
+                .and_then(|storage| eframe::get_value(storage, eframe::APP_KEY))
+                .unwrap_or_default(),
+        )
+    }
+
+    /// The app as it starts with `settings`, without a window; tests drive it from here.
+    fn with_settings(settings: Settings) -> Self {
+        let (error_sender, error_receiver) = channel();
+        let (completion_sender, completion_receiver) = channel();
+        let (file_view_sender, file_view_receiver) = channel();
+        let (event_sender, event_receiver) = channel();
+        let (scan_sender, scan_receiver) = channel();
+        let (preview_sender, preview_receiver) = channel();
+        let (audit_sender, audit_receiver) = channel();
+        let (self_check_sender, self_check_receiver) = channel();
+        let (comparison_sender, comparison_receiver) = channel();
+        let (restore_sender, restore_receiver) = channel();
+        let (patch_sender, patch_receiver) = channel();
+
+        let mut app = Self {
+            spindle_speed_input: String::new(),
+            profile_name_input: String::new(),
+            percent_input: String::new(),
+            surface_speed_input: String::new(),
+            diameter_input: String::new(),
+            validated_spindle_speed: None,
+            processing: false,
+            progress: Arc::new(Progress::default()),
+            error_message: None,
+            error_sender,
+            error_receiver,
+            completion_sender,
+            completion_receiver,
+            preview_sender,
+            preview_receiver,
+            previewing: false,
+            preview: None,
+            audit_sender,
+            audit_receiver,
+            auditing: false,
+            audit: None,
+            preflight_pending: false,
+            safety_warning: None,
+            self_check_sender,
+            self_check_receiver,
+            self_checking: false,
+            self_check_warning: None,
+            show_confirmation_dialog: false,
+            confirmation_focus_yes: false,
+            skip_confirmation: false,
+            unclean_dry_pass: None,
+            compare_roots: [None, None],
+            comparing: false,
+            comparison: None,
+            try_it_input: String::new(),
+            try_it_speed: String::new(),
+            comparison_sender,
+            comparison_receiver,
+            restore_sender,
+            restore_receiver,
+            restore_busy: false,
+            backed_up_runs: None,
+            restore_plan: None,
+            confirming_restore: false,
+            exporting_patch: false,
+            patch_sender,
+            patch_receiver,
+            folders: Vec::new(),
+            picked_files: None,
+            folders_before_pick: Vec::new(),
+            choosing_first_folder: false,
+            project_rules: ProjectRules::default(),
+            project_config: ProjectConfigStatus::None,
+            speed_mapping: None,
+            mapping_report: None,
+            file_cache: HashMap::new(),
+            duplicate_files: Vec::new(),
+            cancel_sender: None,
+            cancelling: false,
+            close_requested: false,
+            close_after_stop: false,
+            success_message: None,
+            success_shown_at: None,
+            run_summary: None,
+            completion_notice: None,
+            title_shows_notice: false,
+            status_expanded: false,
+            run_id: None,
+            last_run: None,
+            last_run_setup: None,
+            retrying: false,
+            previous_run: None,
+            run_changes: None,
+            event_sender,
+            event_receiver,
+            event_log: VecDeque::new(),
+            scan_sender,
+            scan_receiver,
+            scan_cancel: None,
+            self_check_after_scan: false,
+            file_view_sender,
+            file_view_receiver,
+            file_view: None,
+            last_enter_press: Instant::now(),
+            settings,
+        };
+
+        app.folders = app
+            .settings
+            .last_folders
+            .iter()
+            .filter(|folder| folder.is_dir())
+            .cloned()
+            .collect();
+        if app.folders.is_empty() {
+            info!("No folder remembered, asking for one");
+            app.choosing_first_folder = true;
+        } else {
+            info!("Initializing MainApp, updating file cache");
+            app.refresh_file_cache();
+        }
+        app.restore_last_target();
+        app.self_check_after_scan = app.settings.startup_self_check;
+
+        app
+    }
+
+    /// Reapplies the speed mode and its inputs, or the mapping, of the last session. Both
+    /// are validated again, since the speed limits or the CSV may have changed since they
+    /// were saved; inputs that no longer give a valid speed are dropped.
+    fn restore_last_target(&mut self) {
+        let restored =
+            |value: Option<f64>| value.map(|value| value.to_string()).unwrap_or_default();
+        if let Some(speed) = self.settings.last_speed {
+            self.spindle_speed_input = speed.to_string();
+        }
+        self.percent_input = restored(self.settings.last_percent);
+        self.surface_speed_input = restored(self.settings.last_surface_speed);
+        self.diameter_input = restored(self.settings.last_diameter);
+        for mode in SpeedMode::ALL {
+            let inputs = self.speed_inputs(mode);
+            if inputs.iter().all(|input| input.is_empty()) {
+                continue;
+            }
+            if let Err(error) = self.typed_speed(mode) {
+                warn!(
+                    "Not restoring the last {:?} speed {:?}: {}",
+                    mode, inputs, error
+                );
+                match mode {
+                    SpeedMode::Absolute => self.spindle_speed_input.clear(),
+                    SpeedMode::Percent => self.percent_input.clear(),
+                    SpeedMode::SurfaceSpeed => {
+                        self.surface_speed_input.clear();
+                        self.diameter_input.clear();
+                    }
+                }
+            }
+        }
+        if self.typed_speed(self.settings.speed_mode).is_ok() {
+            let _ = self.validate_spindle_speed();
+        }
+
+        if let Some(path) = self.settings.last_mapping.clone() {
+            match load_mapping(&path, &self.project_rules.speed_range) {
+                Ok(mapping) => {
+                    info!("Restored speed mapping {:?}", path);
+                    self.mapping_report = Some(compare_mapping(&mapping.speeds, &self.file_cache));
+                    self.speed_mapping = Some(mapping);
+                }
+                Err(error) => warn!("Not restoring speed mapping {:?}: {}", path, error),
+            }
+        }
+    }
+
+    /// Copies what the window holds now, the speed inputs, mapping and folders, into the
+    /// settings, for saving them or a profile of them.
+    fn store_session(&mut self) {
+        self.settings.last_speed = self.typed_speed(SpeedMode::Absolute).ok();
+        self.settings.last_percent = parse_decimal(&self.percent_input).ok();
+        self.settings.last_surface_speed = parse_decimal(&self.surface_speed_input).ok();
+        self.settings.last_diameter = parse_decimal(&self.diameter_input).ok();
+        self.settings.last_mapping = self
+            .speed_mapping
+            .as_ref()
+            .map(|mapping| mapping.source.clone());
+        if self.picked_files.is_some() {
+            self.settings.last_folders = self.folders_before_pick.clone();
+        } else if !self.choosing_first_folder {
+            self.settings.last_folders = self.folders.clone();
+        }
+    }
+
+    fn save_profile(&mut self, name: &str) {
+        self.store_session();
+        self.settings.save_profile(name);
+        info!("Saved profile {:?}", name.trim());
+    }
+
+    /// Switches to the saved profile `name`: its settings, speed inputs and folders, which
+    /// are rescanned so its fingerprint is checked against what they hold now. A profile
+    /// without folders keeps the current ones.
+    fn load_profile(&mut self, name: &str) {
+        if !self.settings.load_profile(name) {
+            return;
+        }
+        info!("Loaded profile {:?}", name);
+        self.speed_mapping = None;
+        self.mapping_report = None;
+        self.preview = None;
+        self.validated_spindle_speed = None;
+        for input in [
+            &mut self.spindle_speed_input,
+            &mut self.percent_input,
+            &mut self.surface_speed_input,
+            &mut self.diameter_input,
+        ] {
+            input.clear();
+        }
+        let folders: Vec<PathBuf> = self
+            .settings
+            .last_folders
+            .iter()
+            .filter(|folder| folder.is_dir())
+            .cloned()
+            .collect();
+        if !folders.is_empty() {
+            self.picked_files = None;
+            self.folders_before_pick.clear();
+            self.choosing_first_folder = false;
+            self.folders = folders;
+        }
+        if !self.choosing_first_folder {
+            self.refresh_file_cache();
+        }
+        self.restore_last_target();
+    }
+
+    /// Rescans the selected folders, defaulting to the executable's folder, and shows
+    /// failures in the UI rather than leaving an unexplained empty file list.
+    fn refresh_file_cache(&mut self) {
+        if let Err(e) = self.update_file_cache() {
+            error!("Failed to update file cache: {:?}", e);
+            let language = self.settings.language;
+            self.error_message = Some(match e {
+                SpindleSpeedUpdaterError::NoParentDirectory => {
+                    Text::NoProgramFolder.get(language).to_string()
+                }
+                e => fill(Text::ScanFailed.get(language), &[&e]),
+            });
+        }
+    }
+
+    /// Empties the cache and starts scanning the folders on a blocking task, replacing any
+    /// scan still running. The result arrives through `poll_background`.
+    #[allow(dead_code)]
+    fn update_file_cache(&mut self) -> Result<(), SpindleSpeedUpdaterError> {
+        self.cancel_scan();
+        self.file_cache.clear();
+        self.duplicate_files.clear();
+        self.preview = None;
+        self.backed_up_runs = None;
+        self.restore_plan = None;
+        self.confirming_restore = false;
+
+        if let Some(picked) = &self.picked_files {
+            self.folders = picked_folders(picked);
+        }
+        if self.folders.is_empty() {
+            self.folders.push(executable_folder()?);
+        }
+        self.folders = dedupe_roots(&self.folders);
+        let roots = canonical_roots(&self.folders);
+        if self
+            .previous_run
+            .as_ref()
+            .is_some_and(|(previous_roots, _)| *previous_roots != roots)
+        {
+            self.previous_run = None;
+            self.run_changes = None;
+        }
+        (self.project_rules, self.project_config) = load_project_rules(&self.folders);
+
+        let cancel = Arc::new(AtomicBool::new(false));
+        self.scan_cancel = Some(Arc::clone(&cancel));
+        let scan_sender = self.scan_sender.clone();
+        let folders = self.folders.clone();
+        let follow_symlinks = self.settings.follow_symlinks;
+        let rules = self.project_rules.clone();
+        let picked = self.picked_files.clone();
+        tokio::task::spawn_blocking(move || {
+            let result = match picked {
+                Some(picked) => {
+                    list_files(picked, &folders, &rules, Language::English).map(|list| {
+                        // Checked when picked; these went missing or changed since.
+                        for rejected in &list.rejected {
+                            warn!("{}", rejected);
+                        }
+                        (list.file_cache, list.duplicates)
+                    })
+                }
+                None => scan_folders_cancellable(&folders, follow_symlinks, &rules, &cancel),
+            };
+            if let Err(send_error) = scan_sender.send((cancel, result)) {
+                error!("Failed to send scan result to main thread: {}", send_error);
+            }
+        });
+
+        Ok(())
+    }
+
+    /// Adds files from the file dialog to the picked ones, switching over from the folders
+    /// on the first pick. Files the extensions turn away, or that are already picked under
+    /// another name, are left out and named in the error message.
+    fn add_picked_files(&mut self, paths: Vec<PathBuf>) {
+        let mut candidates = self.picked_files.clone().unwrap_or_default();
+        for path in paths {
+            if !candidates.contains(&path) {
+                candidates.push(path);
+            }
+        }
+        let folders = dedupe_roots(&picked_folders(&candidates));
+        let (rules, _) = load_project_rules(&folders);
+        let list = match list_files(candidates.clone(), &folders, &rules, self.settings.language) {
+            Ok(list) => list,
+            Err(error) => {
+                error!("Failed to check the picked files: {:?}", error);
+                self.error_message = Some(fill(
+                    Text::PickedFilesCheckFailed.get(self.settings.language),
+                    &[&error],
+                ));
+                return;
+            }
+        };
+        let mut left_out = list.rejected;
+        for path in &list.duplicates {
+            left_out.push(fill(
+                Text::AlreadyPicked.get(self.settings.language),
+                &[&path.display()],
+            ));
+        }
+        candidates.retain(|path| list.file_cache.contains_key(path));
+        for line in &left_out {
+            warn!("{}", line);
+        }
+        self.error_message = (!left_out.is_empty()).then(|| left_out.join("\n"));
+        if candidates.is_empty() {
+            return;
+        }
+        info!("{} files picked", candidates.len());
+        if self.picked_files.is_none() {
+            self.folders_before_pick = std::mem::take(&mut self.folders);
+        }
+        self.picked_files = Some(candidates);
+        self.refresh_file_cache();
+    }
+
+    /// Stops the scan in progress, if any, leaving the cache empty.
+    fn cancel_scan(&mut self) {
+        if let Some(cancel) = self.scan_cancel.take() {
+            cancel.store(true, Ordering::Relaxed);
+            info!("Folder scan cancelled");
+        }
+    }
+
+    fn scanning(&self) -> bool {
+        self.scan_cancel.is_some()
+    }
+
+    #[allow(dead_code)]
+    fn cancel_operation(&mut self) -> Result<(), SpindleSpeedUpdaterError> {
+        if let Some(cancel_sender) = self.cancel_sender.take() {
+            cancel_sender.send(()).map_err(|_| {
+                SpindleSpeedUpdaterError::CancelError("Failed to send cancel signal".to_string())
+            })?;
+            // `processing` stays set until the task reports back after its current file.
+            self.cancelling = true;
+            Ok(())
+        } else {
+            Err(SpindleSpeedUpdaterError::CancelError(
+                "No operation in progress".to_string(),
+            ))
+        }
+    }
+
+    #[allow(dead_code)]
+    fn show_feedback(&mut self, ui: &mut egui::Ui) {
+        let language = self.settings.language;
+        let t = |text: Text| text.get(language);
+        if self.processing {
+            let (processed, total) = self.progress.counts();
+            if total > 0 {
+                let progress = processed as f32 / total as f32;
+                ui.add(egui::ProgressBar::new(progress).show_percentage());
+                ui.label(fill(t(Text::ProcessedOf), &[&processed, &total]));
+            }
+
+            if ui.button(t(Text::Cancel)).clicked() {
+                if let Err(e) = self.cancel_operation() {
+                    self.error_message = Some(fill(t(Text::CancelFailed), &[&e]));
+                }
+            }
+        }
+
+        if let Some(error_message) = &self.error_message {
+            ui.colored_label(egui::Color32::RED, error_message);
+        }
+
+        if !self.processing && ui.button(t(Text::ClearError)).clicked() {
+            self.error_message = None;
+        }
+    }
+
+    /// What the operator typed for `mode`, in the order its fields are shown.
+    fn speed_inputs(&self, mode: SpeedMode) -> Vec<&str> {
+        match mode {
+            SpeedMode::Absolute => vec![&self.spindle_speed_input],
+            SpeedMode::Percent => vec![&self.percent_input],
+            SpeedMode::SurfaceSpeed => vec![&self.surface_speed_input, &self.diameter_input],
+        }
+    }
+
+    /// The speed the inputs of `mode` give, within the project's speed range.
+    fn typed_speed(&self, mode: SpeedMode) -> Result<u32, SpeedError> {
+        let speed_range = &self.project_rules.speed_range;
+        match mode {
+            SpeedMode::Absolute => parse_spindle_speed(
+                &self.spindle_speed_input,
+                speed_range,
+                self.settings.language,
+            ),
+            SpeedMode::Percent => percent_speed(&self.percent_input, speed_range),
+            SpeedMode::SurfaceSpeed => {
+                surface_speed_rpm(&self.surface_speed_input, &self.diameter_input, speed_range)
+            }
+        }
+    }
+
+    #[allow(dead_code)]
+    fn validate_spindle_speed(&mut self) -> Result<(), String> {
+        let mode = self.settings.speed_mode;
+        info!(
+            "Validating spindle speed ({:?}): {:?}",
+            mode,
+            self.speed_inputs(mode)
+        );
+        match self.typed_speed(mode) {
+            Ok(speed) => {
+                self.validated_spindle_speed = Some(speed);
+                info!("Spindle speed validated: {}", speed);
+                Ok(())
+            }
+            Err(err) => {
+                info!("Validation failed: {}", err);
+                Err(err.message(self.settings.language))
+            }
+        }
+    }
+
+    #[allow(dead_code)]
+    fn show_confirmation_dialog(&mut self, ctx: &egui::Context) {
+        let language = self.settings.language;
+        let t = |text: Text| text.get(language);
+        let mut user_choice: Option<bool> = None;
+        let target = self.target_description();
+        let foreign_folders = self.folders_outside_executable();
+        let fingerprint_mismatch = self.fingerprint_mismatch();
+        let active_profile = self.settings.active_profile.clone();
+        let output_folder = self
+            .settings
+            .output_folder
+            .clone()
+            .filter(|_| self.settings.output_mode == OutputMode::OutputFolder);
+        let not_applied = self
+            .unclean_dry_pass
+            .clone()
+            .filter(|_| self.settings.auto_apply_if_clean);
+        // The pre-flight dry pass already computed every change; the first one, in path
+        // order, serves as a concrete sample of what detection picked up.
+        let example = self
+            .preview
+            .iter()
+            .flatten()
+            .filter(|entry| entry.outcome == PreviewOutcome::Change)
+            .find_map(|entry| {
+                entry
+                    .line_changes
+                    .first()
+                    .map(|change| (&entry.path, change))
+            })
+            .map(|(path, change)| {
+                let old = if change.old.is_empty() {
+                    t(Text::Inserted)
+                } else {
+                    change.old.trim()
+                };
+                fill(
+                    t(Text::ExampleChange),
+                    &[
+                        &path.file_name().unwrap_or_default().to_string_lossy(),
+                        &old,
+                        &change.new.trim(),
+                    ],
+                )
+            });
+
+        egui::Window::new(t(Text::ConfirmTitle))
+            .collapsible(false)
+            .resizable(false)
+            .open(&mut self.show_confirmation_dialog)
+            .show(ctx, |ui| {
+                if let Some(mismatch) = &fingerprint_mismatch {
+                    ui.colored_label(
+                        egui::Color32::RED,
+                        egui::RichText::new(fill(t(Text::WrongFolder), &[mismatch]))
+                            .strong()
+                            .size(16.0),
+                    );
+                    if let Some(profile) = &active_profile {
+                        ui.colored_label(
+                            egui::Color32::RED,
+                            fill(t(Text::WrongFolderForProfile), &[profile]),
+                        );
+                    }
+                }
+                ui.label(fill(t(Text::ConfirmQuestion), &[&target]));
+                if let Some(folder) = &output_folder {
+                    ui.label(fill(t(Text::WritingToOutput), &[&folder.display()]));
+                }
+                if let Some(reason) = &not_applied {
+                    ui.colored_label(
+                        egui::Color32::YELLOW,
+                        fill(t(Text::NotAppliedAutomatically), &[reason]),
+                    );
+                }
+                if let Some(example) = &example {
+                    ui.monospace(example);
+                }
+                if !foreign_folders.is_empty() {
+                    let paths: Vec<String> = foreign_folders
+                        .iter()
+                        .map(|folder| folder.display().to_string())
+                        .collect();
+                    ui.colored_label(
+                        egui::Color32::YELLOW,
+                        egui::RichText::new(fill(t(Text::OutsideFolders), &[&paths.join("\n")]))
+                            .strong(),
+                    );
+                }
+
+                // Any of these moves between the two buttons. The focus is locked on the
+                // button that has it, so egui's own Tab order can't take it out of the dialog.
+                let moved = ui.input(|i| {
+                    [
+                        egui::Key::Tab,
+                        egui::Key::ArrowLeft,
+                        egui::Key::ArrowRight,
+                        egui::Key::ArrowUp,
+                        egui::Key::ArrowDown,
+                    ]
+                    .into_iter()
+                    .any(|key| i.key_pressed(key))
+                });
+                if moved {
+                    self.confirmation_focus_yes = !self.confirmation_focus_yes;
+                }
+                if ui.input(|i| i.key_pressed(egui::Key::Escape)) {
+                    user_choice = Some(false);
+                }
+                if self.settings.enter_confirms_dialog {
+                    // Taken before the buttons see it, so it can't also press a focused No.
+                    let now = Instant::now();
+                    if ui.input_mut(|i| i.consume_key(egui::Modifiers::NONE, egui::Key::Enter))
+                        && now.duration_since(self.last_enter_press).as_millis() > 500
+                    {
+                        user_choice = Some(true);
+                        self.last_enter_press = now;
+                    }
+                }
+
+                ui.horizontal(|ui| {
+                    let yes = ui.add(
+                        egui::Button::new(egui::RichText::new(t(Text::Yes)).strong())
+                            .fill(egui::Color32::from_rgb(108, 108, 108)),
+                    );
+                    let yes_consequence = fill(t(Text::YesConsequence), &[&target]);
+                    yes.widget_info(|| {
+                        egui::WidgetInfo::labeled(egui::WidgetType::Button, &yes_consequence)
+                    });
+                    let no = ui.button(t(Text::No));
+                    no.widget_info(|| {
+                        egui::WidgetInfo::labeled(egui::WidgetType::Button, t(Text::NoConsequence))
+                    });
+                    if yes.clicked() {
+                        user_choice = Some(true);
+                    }
+                    if no.clicked() {
+                        user_choice = Some(false);
+                    }
+
+                    let focused = if self.confirmation_focus_yes {
+                        &yes
+                    } else {
+                        &no
+                    };
+                    if !focused.has_focus() {
+                        focused.request_focus();
+                        ui.ctx().request_repaint();
+                    }
+                    ui.memory_mut(|memory| memory.lock_focus(focused.id, true));
+                });
+
+                if self.settings.enter_confirms_dialog {
+                    ui.label(t(Text::PressEnter));
+                } else {
+                    ui.label(t(Text::DialogKeys));
+                }
+            });
+
+        if let Some(choice) = user_choice {
+            self.show_confirmation_dialog = false;
+            if choice {
+                self.start_confirmed_update();
+            }
+        }
+    }
+
+    fn start_confirmed_update(&mut self) {
+        if let Err(error) = self.start_update_process() {
+            self.error_message = Some(error.to_string());
+            error!("Failed to start spindle speed update: {:?}", error);
+        } else {
+            info!("Started spindle speed update process");
+            self.error_message = None;
+        }
+    }
+
+    /// Asks for confirmation once the pre-flight checks have passed or been overridden,
+    /// unless the run was started with Enter set to run directly or the dry pass is clean
+    /// with auto-apply on. A folder that fails its fingerprint always asks.
+    fn proceed_after_preflight(&mut self) {
+        let skip_confirmation = std::mem::take(&mut self.skip_confirmation);
+        if let Some(mismatch) = self.fingerprint_mismatch() {
+            warn!("Folder fingerprint doesn't match: {}", mismatch);
+            self.show_confirmation_dialog = true;
+            self.confirmation_focus_yes = false;
+        } else if skip_confirmation {
+            info!("Starting without confirmation, as set for Enter");
+            self.start_confirmed_update();
+        } else if self.settings.auto_apply_if_clean && self.unclean_dry_pass.is_none() {
+            info!("Starting without confirmation, the dry pass is clean");
+            self.start_confirmed_update();
+        } else {
+            if let Some(reason) = self
+                .unclean_dry_pass
+                .as_ref()
+                .filter(|_| self.settings.auto_apply_if_clean)
+            {
+                info!("Not applying automatically: {}", reason);
+            }
+            self.show_confirmation_dialog = true;
+            self.confirmation_focus_yes = false;
+        }
+    }
+
+    /// How the chosen folders differ from the pinned fingerprint, one difference per line,
+    /// or `None` when they match or the check is off.
+    fn fingerprint_mismatch(&self) -> Option<String> {
+        if !self.settings.check_folder_fingerprint {
+            return None;
+        }
+        let language = self.settings.language;
+        let mut differences = Vec::new();
+        let sentinel = self.settings.sentinel_file.trim();
+        if !sentinel.is_empty() {
+            for folder in &self.folders {
+                if !folder.join(sentinel).exists() {
+                    differences.push(fill(
+                        Text::SentinelMissing.get(language),
+                        &[&folder.display(), &sentinel],
+                    ));
+                }
+            }
+        }
+        let expected = self.settings.expected_file_count;
+        if expected > 0 && self.file_cache.len() != expected as usize {
+            differences.push(fill(
+                Text::FileCountMismatch.get(language),
+                &[&self.file_cache.len(), &expected],
+            ));
+        }
+        (!differences.is_empty()).then(|| differences.join("\n"))
+    }
+
+    /// Absolute paths of the chosen folders that aren't the executable's own folder, which
+    /// the tool historically worked on.
+    fn folders_outside_executable(&self) -> Vec<PathBuf> {
+        let executable = executable_folder()
+            .ok()
+            .and_then(|path| std::fs::canonicalize(path).ok());
+        self.folders
+            .iter()
+            .filter_map(|folder| {
+                let canonical = std::fs::canonicalize(folder).unwrap_or_else(|_| folder.clone());
+                if executable.as_ref() == Some(&canonical) {
+                    return None;
+                }
+                // Shown to the user, so avoid the `\\?\` prefix canonicalize adds on Windows.
+                Some(std::path::absolute(folder).unwrap_or(canonical))
+            })
+            .collect()
+    }
+
+    /// Shown instead of the main view until a first folder is chosen, so a new user picks
+    /// where the programs are rather than finding the executable's folder scanned.
+    fn show_first_folder_prompt(&mut self, ctx: &egui::Context) {
+        let language = self.settings.language;
+        let t = |text: Text| text.get(language);
+        let mut chosen = None;
+        egui::CentralPanel::default().show(ctx, |ui| {
+            ui.heading(t(Text::AppTitle));
+            ui.label(t(Text::FirstFolderPrompt));
+            ui.horizontal(|ui| {
+                if ui.button(t(Text::Browse)).clicked() {
+                    chosen = rfd::FileDialog::new().pick_folder().map(Ok);
+                }
+                if ui.button(t(Text::UseProgramFolder)).clicked() {
+                    chosen = Some(executable_folder());
+                }
+            });
+            if let Some(error) = &self.error_message {
+                ui.colored_label(egui::Color32::RED, error);
+            }
+        });
+
+        match chosen {
+            Some(Ok(folder)) => {
+                info!("First folder selected: {:?}", folder);
+                self.choosing_first_folder = false;
+                self.folders = vec![folder];
+                self.settings.last_folders = self.folders.clone();
+                self.error_message = None;
+                self.refresh_file_cache();
+            }
+            Some(Err(error)) => {
+                error!("Failed to use the program folder: {:?}", error);
+                self.error_message = Some(
+                    Text::NoProgramFolder
+                        .get(self.settings.language)
+                        .to_string(),
+                );
+            }
+            None => {}
+        }
+    }
+
+    fn show_close_dialog(&mut self, ctx: &egui::Context) {
+        let language = self.settings.language;
+        let t = |text: Text| text.get(language);
+        egui::Window::new(t(Text::CloseTitle))
+            .collapsible(false)
+            .resizable(false)
+            .show(ctx, |ui| {
+                ui.label(t(Text::CloseQuestion));
+                ui.horizontal(|ui| {
+                    if ui.button(t(Text::CancelAndClose)).clicked() {
+                        self.close_requested = false;
+                        self.close_after_stop = true;
+                        if !self.cancelling {
+                            if let Err(error) = self.cancel_operation() {
+                                error!("Failed to cancel before closing: {}", error);
+                            }
+                        }
+                    }
+                    if ui.button(t(Text::KeepRunning)).clicked() {
+                        self.close_requested = false;
+                    }
+                });
+            });
+    }
+
+    fn start_update_process(&mut self) -> Result<(), SpindleSpeedUpdaterError> {
+        let run_id = new_run_id();
+        let _run = info_span!("run", id = %run_id).entered();
+        info!("Starting update process");
+
+        self.success_message = None;
+        self.success_shown_at = None;
+        self.run_summary = None;
+        self.run_id = Some(run_id.clone());
+        self.last_run = None;
+        self.event_log.clear();
+        if let Some(entries) = self.preview.take() {
+            pin_to_preview(&mut self.file_cache, &entries);
+        }
+
+        let setup = RunSetup {
+            plan: self.speed_plan()?,
+            options: self
+                .settings
+                .run_options(&self.project_rules, &self.folders),
+            target: self.target_description(),
+            folders: self.folders.clone(),
+        };
+        info!("Target: {}", setup.target);
+        self.last_run_setup = Some(setup.clone());
+        let file_cache = self.files_to_process();
+        self.spawn_run(run_id, setup, file_cache);
+        Ok(())
+    }
+
+    /// Runs the failed and in-use files of the last run again with the speeds and options
+    /// it had. Its report takes in the new outcomes when the retry finishes.
+    fn retry_failed(&mut self) {
+        let (Some(report), Some(mut setup)) = (&self.last_run, self.last_run_setup.clone()) else {
+            return;
+        };
+        // The merge needs every retried file's result back.
+        setup.options.result_chunks = None;
+        // Some failures come after the write (a read-back that didn't match, times that
+        // couldn't be restored), so each file is looked at afresh rather than trusting
+        // what the cache held before the run.
+        let file_cache: HashMap<PathBuf, FileInfo> = report
+            .retryable()
+            .map(|result| {
+                let metadata = std::fs::metadata(&result.path).ok();
+                let info = FileInfo {
+                    path: result.path.clone(),
+                    root: result.root.clone(),
+                    last_modified: metadata
+                        .as_ref()
+                        .and_then(|metadata| metadata.modified().ok())
+                        .unwrap_or_else(SystemTime::now),
+                    size: metadata.map_or(0, |metadata| metadata.len()),
+                };
+                (result.path.clone(), info)
+            })
+            .collect();
+        let run_id = new_run_id();
+        info_span!("run", id = %run_id).in_scope(|| {
+            info!(
+                "Retrying {} failed or in-use files of run {}",
+                file_cache.len(),
+                report.run_id
+            )
+        });
+        self.success_message = None;
+        self.success_shown_at = None;
+        self.error_message = None;
+        self.run_id = Some(run_id.clone());
+        self.retrying = true;
+        self.spawn_run(run_id, setup, file_cache);
+    }
+
+    /// Starts `setup` on `file_cache` in the background; the report or error comes back
+    /// through the channels [`Self::poll_background`] reads.
+    fn spawn_run(
+        &mut self,
+        run_id: String,
+        setup: RunSetup,
+        file_cache: HashMap<PathBuf, FileInfo>,
+    ) {
+        let RunSetup {
+            plan,
+            options,
+            target,
+            folders,
+        } = setup;
+        self.processing = true;
+        let progress = Arc::clone(&self.progress);
+        let error_sender = self.error_sender.clone();
+        let completion_sender = self.completion_sender.clone();
+        let event_sender = self.event_sender.clone();
+        let started = (UtcDateTime::now(), Instant::now());
+        let run = info_span!("run", id = %run_id);
+        let history_file = history_path(self.settings.run_history_file.as_deref())
+            .map_err(|error| run.in_scope(|| error!("No run history file: {}", error)))
+            .ok();
+
+        let (cancel_sender, cancel_receiver) = oneshot::channel();
+        self.cancel_sender = Some(cancel_sender);
+
+        tokio::spawn(async move {
+            let result = update_spindle_speed(
+                &run_id,
+                &plan,
+                options,
+                progress,
+                &file_cache,
+                cancel_receiver,
+                Some(event_sender),
+            )
+            .await;
+            // What follows the run is logged in its span too.
+            async move {
+                match result {
+                    Ok(results) => {
+                        let report =
+                            RunReport::new(run_id.clone(), target, folders, started, results);
+                        if let Some(path) = &history_file {
+                            if let Err(error) = append_run(path, &report).await {
+                                error!("Failed to add the run to {:?}: {}", path, error);
+                            }
+                        }
+                        if let Err(send_error) = completion_sender.send(report) {
+                            error!("Failed to send completion to main thread: {}", send_error);
+                        }
+                    }
+                    Err(error) => {
+                        error!("Error updating spindle speed: {:?}", error);
+                        if let Err(send_error) = error_sender.send(error) {
+                            error!("Failed to send error to main thread: {}", send_error);
+                        }
+                    }
+                }
+            }
+            .instrument(run)
+            .await
+        });
+    }
+
+    /// Whether the speed rules of the project config give the speeds: they are turned on,
+    /// there are some, and no mapping takes precedence.
+    fn speed_rules_active(&self) -> bool {
+        self.speed_mapping.is_none()
+            && self.settings.use_speed_rules
+            && !self.project_rules.speed_rules.is_empty()
+    }
+
+    /// Whether each file gets its own speed, so the typed speed isn't needed.
+    fn per_file_speeds(&self) -> bool {
+        self.speed_mapping.is_some() || self.speed_rules_active()
+    }
+
+    /// The speeds a run would apply: the mapping when one is loaded, then the speed rules
+    /// when they are on, the typed speed otherwise.
+    fn speed_plan(&self) -> Result<SpeedPlan, SpindleSpeedUpdaterError> {
+        match &self.speed_mapping {
+            Some(mapping) => Ok(SpeedPlan::PerFile(Arc::clone(&mapping.speeds))),
+            None if self.speed_rules_active() => Ok(SpeedPlan::Rules(Arc::new(
+                self.project_rules.speed_rules.clone(),
+            ))),
+            None => self.validated_spindle_speed.map(SpeedPlan::Uniform).ok_or(
+                SpindleSpeedUpdaterError::InvalidSpindleSpeed(
+                    "No validated spindle speed".to_string(),
+                ),
+            ),
+        }
+    }
+
+    fn target_description(&self) -> String {
+        let language = self.settings.language;
+        if self.speed_rules_active() {
+            return fill(Text::TargetSpeedRules.get(language), &[&CONFIG_FILE_NAME]);
+        }
+        match (&self.speed_mapping, self.validated_spindle_speed) {
+            (Some(mapping), _) => fill(
+                Text::TargetMapping.get(language),
+                &[&mapping
+                    .source
+                    .file_name()
+                    .unwrap_or_default()
+                    .to_string_lossy()],
+            ),
+            (None, Some(speed)) => format_rpm(speed),
+            (None, None) => Text::TargetUnknown.get(language).to_string(),
+        }
+    }
+
+    /// Picking a saved profile loads it; the name field saves the settings as one.
+    fn show_profiles(&mut self, ui: &mut egui::Ui) {
+        let language = self.settings.language;
+        let t = |text: Text| text.get(language);
+        let mut load = None;
+        let mut save = false;
+        ui.add_enabled_ui(!self.processing && !self.scanning(), |ui| {
+            ui.horizontal(|ui| {
+                ui.label(t(Text::Profile));
+                let active = self.settings.active_profile.clone();
+                egui::ComboBox::from_id_source("profile")
+                    .selected_text(active.as_deref().unwrap_or(t(Text::NoProfile)))
+                    .show_ui(ui, |ui| {
+                        for profile in &self.settings.profiles {
+                            let selected = active.as_deref() == Some(profile.name.as_str());
+                            if ui.selectable_label(selected, &profile.name).clicked() {
+                                load = Some(profile.name.clone());
+                            }
+                        }
+                    });
+                if let Some(active) = active {
+                    if ui.button(t(Text::DeleteProfile)).clicked() {
+                        info!("Deleted profile {:?}", active);
+                        self.settings.delete_profile(&active);
+                    }
+                }
+            });
+            ui.horizontal(|ui| {
+                ui.add(
+                    egui::TextEdit::singleline(&mut self.profile_name_input)
+                        .hint_text(t(Text::ProfileName))
+                        .desired_width(160.0),
+                );
+                save = ui
+                    .add_enabled(
+                        !self.profile_name_input.trim().is_empty(),
+                        egui::Button::new(t(Text::SaveProfile)),
+                    )
+                    .clicked();
+            });
+        });
+        if let Some(name) = load {
+            self.load_profile(&name);
+        }
+        if save {
+            let name = std::mem::take(&mut self.profile_name_input);
+            self.save_profile(&name);
+        }
+    }
+
+    /// Checks everything a preview or an update needs before it can start.
+    fn prepare_run(&mut self) -> Result<(), String> {
+        if self.folders.is_empty() {
+            return Err(Text::NoProgramFolder
+                .get(self.settings.language)
+                .to_string());
+        }
+        if self.settings.output_mode == OutputMode::OutputFolder {
+            let language = self.settings.language;
+            let Some(output) = &self.settings.output_folder else {
+                return Err(Text::ChooseOutputFolder.get(language).to_string());
+            };
+            if let Some(folder) = overlapping_folder(output, &self.folders) {
+                return Err(fill(
+                    Text::OutputOverlapsFolder.get(language),
+                    &[&output.display(), &folder.display()],
+                ));
+            }
+        }
+        if self.per_file_speeds() {
+            return Ok(());
+        }
+        self.validate_spindle_speed()
+    }
+
+    /// The folder list: Browse… replaces it, Add folder… appends, ✕ removes one entry.
+    fn show_folders(&mut self, ui: &mut egui::Ui) {
+        let language = self.settings.language;
+        let t = |text: Text| text.get(language);
+        match &self.project_config {
+            ProjectConfigStatus::None => {}
+            ProjectConfigStatus::Loaded(path) => {
+                ui.label(fill(t(Text::ProjectConfig), &[&path.display()]));
+            }
+            ProjectConfigStatus::Invalid(path, error) => {
+                ui.colored_label(
+                    egui::Color32::YELLOW,
+                    fill(t(Text::IgnoringProjectConfig), &[&path.display(), error]),
+                );
+            }
+        }
+        let mut removed = None;
+        let mut unpicked = None;
+        if let Some(picked) = &self.picked_files {
+            ui.label(fill(t(Text::PickedFiles), &[&picked.len()]));
+            egui::ScrollArea::vertical()
+                .id_source("picked_files")
+                .max_height(120.0)
+                .show(ui, |ui| {
+                    for (index, path) in picked.iter().enumerate() {
+                        ui.horizontal(|ui| {
+                            if ui
+                                .add_enabled(!self.processing, egui::Button::new("✕"))
+                                .clicked()
+                            {
+                                unpicked = Some(index);
+                            }
+                            let name = path.file_name().unwrap_or(path.as_os_str());
+                            let folder = path.parent().unwrap_or(path);
+                            ui.label(fill(
+                                t(Text::PickedFile),
+                                &[&Path::new(name).display(), &folder.display()],
+                            ));
+                        });
+                    }
+                });
+        } else {
+            if self.folders.is_empty() {
+                ui.colored_label(egui::Color32::RED, t(Text::NoFolderSelected));
+            }
+            for (index, folder) in self.folders.iter().enumerate() {
+                ui.horizontal(|ui| {
+                    ui.label(fill(t(Text::Folder), &[&folder.display()]));
+                    if self.folders.len() > 1
+                        && ui
+                            .add_enabled(!self.processing, egui::Button::new("✕"))
+                            .clicked()
+                    {
+                        removed = Some(index);
+                    }
+                });
+            }
+        }
+
+        let mut picked = None;
+        let mut picked_files = None;
+        let mut back_to_folders = false;
+        ui.horizontal(|ui| {
+            ui.add_enabled_ui(!self.processing, |ui| {
+                if ui.button(t(Text::Browse)).clicked() {
+                    picked = rfd::FileDialog::new()
+                        .pick_folder()
+                        .map(|folder| (folder, false));
+                }
+                if ui.button(t(Text::AddFolder)).clicked() {
+                    picked = rfd::FileDialog::new()
+                        .pick_folder()
+                        .map(|folder| (folder, true));
+                }
+                if ui.button(t(Text::PickFiles)).clicked() {
+                    let extensions = self.project_rules.extensions.clone();
+                    picked_files = rfd::FileDialog::new()
+                        .add_filter("G-code", &extensions)
+                        .pick_files();
+                }
+                if self.picked_files.is_some() && ui.button(t(Text::BackToFolders)).clicked() {
+                    back_to_folders = true;
+                }
+            });
+        });
+
+        if let Some(index) = removed {
+            info!("Folder removed: {:?}", self.folders[index]);
+            self.folders.remove(index);
+            self.refresh_file_cache();
+        }
+        if let Some(index) = unpicked {
+            if let Some(picked) = &mut self.picked_files {
+                info!("File removed: {:?}", picked.remove(index));
+                if picked.is_empty() {
+                    back_to_folders = true;
+                } else {
+                    self.refresh_file_cache();
+                }
+            }
+        }
+        if let Some(paths) = picked_files {
+            self.add_picked_files(paths);
+        }
+        if back_to_folders || picked.is_some() {
+            if self.picked_files.take().is_some() {
+                info!("Back to the folders");
+                self.folders = std::mem::take(&mut self.folders_before_pick);
+            }
+            if picked.is_none() {
+                self.refresh_file_cache();
+            }
+        }
+        if let Some((folder, append)) = picked {
+            info!("Folder selected: {:?}", folder);
+            if !append {
+                self.folders.clear();
+            }
+            self.folders.push(folder);
+            self.error_message = None;
+            self.refresh_file_cache();
+        }
+    }
+
+    fn show_speed_mapping(&mut self, ui: &mut egui::Ui) {
+        let language = self.settings.language;
+        let t = |text: Text| text.get(language);
+        ui.horizontal(|ui| {
+            match &self.speed_mapping {
+                Some(mapping) => ui.label(fill(
+                    t(Text::SpeedMapping),
+                    &[&mapping.source.display(), &mapping.speeds.len()],
+                )),
+                None => ui.label(t(Text::NoSpeedMapping)),
+            };
+            ui.add_enabled_ui(!self.processing, |ui| {
+                if ui.button(t(Text::LoadCsv)).clicked() {
+                    if let Some(path) = rfd::FileDialog::new()
+                        .add_filter("CSV", &["csv"])
+                        .pick_file()
+                    {
+                        match load_mapping(&path, &self.project_rules.speed_range) {
+                            Ok(mapping) => {
+                                info!("Loaded speed mapping {:?}", path);
+                                self.mapping_report =
+                                    Some(compare_mapping(&mapping.speeds, &self.file_cache));
+                                self.speed_mapping = Some(mapping);
+                                self.preview = None;
+                                self.error_message = None;
+                            }
+                            Err(error) => {
+                                error!("Failed to load speed mapping {:?}: {}", path, error);
+                                self.error_message = Some(error.to_string());
+                            }
+                        }
+                    }
+                }
+                if self.speed_mapping.is_some() && ui.button(t(Text::Clear)).clicked() {
+                    self.speed_mapping = None;
+                    self.mapping_report = None;
+                    self.preview = None;
+                }
+            });
+        });
+
+        let rules = &self.project_rules.speed_rules;
+        if !rules.is_empty() {
+            ui.add_enabled(
+                !self.processing && self.speed_mapping.is_none(),
+                egui::Checkbox::new(
+                    &mut self.settings.use_speed_rules,
+                    fill(t(Text::UseSpeedRules), &[&rules.len(), &CONFIG_FILE_NAME]),
+                ),
+            )
+            .on_hover_text(t(Text::UseSpeedRulesHint));
+        }
+        if self.speed_rules_active() {
+            ui.collapsing(t(Text::WhichRule), |ui| {
+                let mut paths: Vec<&PathBuf> = self.file_cache.keys().collect();
+                paths.sort();
+                for path in paths {
+                    let applied = match rule_for(rules, path) {
+                        Some((index, rule)) => fill(
+                            t(Text::RuleApplied),
+                            &[&(index + 1), &rule.pattern, &format_rpm(rule.speed)],
+                        ),
+                        None => t(Text::NoRuleApplied).to_string(),
+                    };
+                    ui.label(format!("{}: {}", self.shown_path(path), applied));
+                }
+            });
+        }
+
+        if let Some(report) = &self.mapping_report {
+            if !report.missing_files.is_empty() {
+                ui.collapsing(
+                    fill(t(Text::MappedFilesMissing), &[&report.missing_files.len()]),
+                    |ui| {
+                        for name in &report.missing_files {
+                            ui.label(name);
+                        }
+                    },
+                );
+            }
+            if !report.unmapped_files.is_empty() {
+                ui.collapsing(
+                    fill(t(Text::FilesNotMapped), &[&report.unmapped_files.len()]),
+                    |ui| {
+                        for path in &report.unmapped_files {
+                            ui.label(self.shown_path(path));
+                        }
+                    },
+                );
+            }
+        }
+    }
+
+    /// Runs the dry pass in the background; the result arrives through `poll_background`.
+    fn start_preview(&mut self) -> Result<(), SpindleSpeedUpdaterError> {
+        let plan = self.speed_plan()?;
+        info!("Building preview for {}", self.target_description());
+
+        self.previewing = true;
+        self.preview = None;
+        let preview_sender = self.preview_sender.clone();
+        let options = self.settings.update_options(&self.project_rules);
+        let file_cache = self.files_to_process();
+
+        tokio::spawn(async move {
+            let entries = build_preview(&plan, options, &file_cache).await;
+            if let Err(send_error) = preview_sender.send(entries) {
+                error!("Failed to send preview to main thread: {}", send_error);
+            }
+        });
+
+        Ok(())
+    }
+
+    /// Reads every file for an audit in the background. Nothing is written and no speed
+    /// is needed.
+    fn start_audit(&mut self) {
+        info!("Starting audit");
+        self.auditing = true;
+        self.audit = None;
+        let audit_sender = self.audit_sender.clone();
+        let file_cache = self.files_to_process();
+        let rules = self.project_rules.clone();
+
+        tokio::spawn(async move {
+            let entries = audit_files(&file_cache, &rules).await;
+            if let Err(send_error) = audit_sender.send(entries) {
+                error!("Failed to send audit to main thread: {}", send_error);
+            }
+        });
+    }
+
+    fn show_audit(&mut self, ui: &mut egui::Ui) {
+        let language = self.settings.language;
+        let t = |text: Text| text.get(language);
+        if self.auditing {
+            ui.horizontal(|ui| {
+                ui.spinner();
+                ui.label(t(Text::Auditing));
+            });
+        }
+        let Some(entries) = &self.audit else {
+            return;
+        };
+        let flagged = entries
+            .iter()
+            .filter(|entry| !entry.anomalies.is_empty())
+            .count();
+        let title = fill(t(Text::AuditTitle), &[&flagged, &entries.len()]);
+        let mut view = None;
+        let mut save = false;
+        egui::CollapsingHeader::new(title)
+            .default_open(true)
+            .show(ui, |ui| {
+                save = ui
+                    .button(t(Text::SaveCsv))
+                    .on_hover_text(t(Text::SaveAuditHint))
+                    .clicked();
+                egui::ScrollArea::vertical()
+                    .max_height(200.0)
+                    .id_source("audit")
+                    .show(ui, |ui| {
+                        for entry in entries {
+                            let speeds = match entry.speeds.as_slice() {
+                                [] => t(Text::NoSpeedWord).to_string(),
+                                speeds => speeds
+                                    .iter()
+                                    .map(|speed| format!("S{}", speed))
+                                    .collect::<Vec<_>>()
+                                    .join(", "),
+                            };
+                            let name = self.shown_path(&entry.path);
+                            let text = if entry.anomalies.is_empty() {
+                                egui::RichText::new(format!("{}: {}", name, speeds))
+                            } else {
+                                let anomalies: Vec<String> =
+                                    entry.anomalies.iter().map(ToString::to_string).collect();
+                                egui::RichText::new(format!(
+                                    "{}: {} ({})",
+                                    name,
+                                    speeds,
+                                    anomalies.join(", ")
+                                ))
+                                .color(egui::Color32::YELLOW)
+                            };
+                            if file_row(ui, text, &entry.path, language) {
+                                view = Some(entry.path.clone());
+                            }
+                        }
+                    });
+            });
+        let csv = save.then(|| audit_csv(entries, self.settings.path_display));
+        if let Some(path) = view {
+            self.start_file_view(path);
+        }
+        if let Some(csv) = csv {
+            if let Some(path) = rfd::FileDialog::new()
+                .add_filter("CSV", &["csv"])
+                .set_file_name("spindle-speed-audit.csv")
+                .save_file()
+            {
+                match std::fs::write(&path, csv) {
+                    Ok(()) => {
+                        info!("Audit written to {:?}", path);
+                        self.success_message =
+                            Some(fill(t(Text::AuditWritten), &[&path.display()]));
+                        self.success_shown_at = Some(Instant::now());
+                    }
+                    Err(error) => {
+                        error!("Failed to write the audit to {:?}: {}", path, error);
+                        self.error_message = Some(fill(t(Text::AuditSaveFailed), &[&error]));
+                    }
+                }
+            }
+        }
+    }
+
+    /// Dry pass without a speed or insertion, only to find files the parser can't
+    /// update. The result arrives through `poll_background`.
+    fn start_self_check(&mut self) {
+        info!(
+            "Running startup self-check on {} files",
+            self.file_cache.len()
+        );
+        self.self_checking = true;
+        let self_check_sender = self.self_check_sender.clone();
+        let file_cache = self.file_cache.clone();
+        let options = self.settings.update_options(&self.project_rules);
+
+        tokio::spawn(async move {
+            let plan = SpeedPlan::Uniform(MIN_SPINDLE_SPEED);
+            let entries = build_preview(&plan, options, &file_cache).await;
+            if let Err(send_error) = self_check_sender.send(entries) {
+                error!("Failed to send self-check to main thread: {}", send_error);
+            }
+        });
+    }
+
+    fn show_preview(&mut self, ui: &mut egui::Ui) {
+        let language = self.settings.language;
+        let t = |text: Text| text.get(language);
+        if self.previewing {
+            ui.horizontal(|ui| {
+                ui.spinner();
+                ui.label(t(Text::BuildingPreview));
+            });
+        }
+
+        let Some(entries) = &self.preview else {
+            return;
+        };
+        let changes = entries
+            .iter()
+            .filter(|entry| entry.outcome == PreviewOutcome::Change)
+            .count();
+        let mismatches = entries
+            .iter()
+            .filter(|entry| entry.line_count_mismatch())
+            .count();
+
+        let mut title = fill(t(Text::PreviewTitle), &[&changes, &entries.len()]);
+        if let Some(speed) = self
+            .settings
+            .update_options(&self.project_rules)
+            .match_current_speed
+        {
+            let matched = entries
+                .iter()
+                .filter(|entry| entry.outcome != PreviewOutcome::NotMatched)
+                .count();
+            title.push_str(&fill(
+                t(Text::PreviewMatched),
+                &[&matched, &format_rpm(speed)],
+            ));
+        }
+        let mut view = None;
+        let mut export = false;
+        egui::CollapsingHeader::new(title)
+            .default_open(true)
+            .show(ui, |ui| {
+                ui.horizontal(|ui| {
+                    export = ui
+                        .add_enabled(
+                            changes > 0 && !self.exporting_patch,
+                            egui::Button::new(t(Text::ExportPatch)),
+                        )
+                        .on_hover_text(t(Text::ExportPatchHint))
+                        .clicked();
+                    if self.exporting_patch {
+                        ui.spinner();
+                    }
+                });
+                if mismatches > 0 {
+                    ui.colored_label(
+                        egui::Color32::RED,
+                        fill(t(Text::LineCountChanges), &[&mismatches]),
+                    );
+                }
+                egui::ScrollArea::vertical()
+                    .max_height(200.0)
+                    .show(ui, |ui| {
+                        for entry in entries {
+                            let name = self.shown_path(&entry.path);
+                            let status = match &entry.outcome {
+                                PreviewOutcome::Change if entry.inserted => {
+                                    t(Text::OutcomeInserted).to_string()
+                                }
+                                PreviewOutcome::Change => t(Text::OutcomeChange).to_string(),
+                                PreviewOutcome::Unchanged => t(Text::OutcomeUnchanged).to_string(),
+                                PreviewOutcome::Empty => t(Text::OutcomeEmpty).to_string(),
+                                PreviewOutcome::NoSpindleCommand => {
+                                    t(Text::OutcomeNoSpindle).to_string()
+                                }
+                                PreviewOutcome::Unsupported(reason) => reason.clone(),
+                                PreviewOutcome::NotInMapping => {
+                                    t(Text::OutcomeNotInMapping).to_string()
+                                }
+                                PreviewOutcome::NoMatchingRule => {
+                                    t(Text::OutcomeNoMatchingRule).to_string()
+                                }
+                                PreviewOutcome::NotMatched => {
+                                    t(Text::OutcomeNotMatched).to_string()
+                                }
+                                PreviewOutcome::Error(error) => {
+                                    fill(t(Text::OutcomeError), &[error])
+                                }
+                            };
+                            let text = fill(
+                                t(Text::PreviewLine),
+                                &[
+                                    &name,
+                                    &entry.original_lines,
+                                    &entry.new_lines,
+                                    &entry.original_bytes,
+                                    &entry.new_bytes,
+                                    &status,
+                                ],
+                            );
+                            let text = if entry.line_count_mismatch() {
+                                egui::RichText::new(text).color(egui::Color32::RED)
+                            } else {
+                                egui::RichText::new(text)
+                            };
+                            if file_row(ui, text, &entry.path, language) {
+                                view = Some(entry.path.clone());
+                            }
+                        }
+                    });
+            });
+        if let Some(path) = view {
+            self.start_file_view(path);
+        }
+        if export {
+            if let Some(path) = rfd::FileDialog::new()
+                .add_filter(t(Text::Patch), &["patch", "diff"])
+                .set_file_name("spindle-speed.patch")
+                .save_file()
+            {
+                if let Err(error) = self.start_patch_export(path) {
+                    self.error_message = Some(error.to_string());
+                }
+            }
+        }
+    }
+
+    /// Reads `path` in the background for the viewer window.
+    fn start_file_view(&mut self, path: PathBuf) {
+        let file_view_sender = self.file_view_sender.clone();
+        let target = self.settings.spindle_target;
+        let letter = self.project_rules.spindle_letter;
+        tokio::spawn(async move {
+            let view = load_file_view(&path, target, letter).await;
+            if let Err(send_error) = file_view_sender.send(view) {
+                error!("Failed to send file view to main thread: {}", send_error);
+            }
+        });
+    }
+
+    /// Read-only: the viewer never writes, it only marks the detected spindle lines.
+    fn show_file_view(&mut self, ctx: &egui::Context) {
+        let Some(view) = &self.file_view else {
+            return;
+        };
+        let language = self.settings.language;
+        let t = |text: Text| text.get(language);
+        let mut open = true;
+        egui::Window::new(fill(
+            t(Text::ViewTitle),
+            &[&view.path.file_name().unwrap_or_default().to_string_lossy()],
+        ))
+        .open(&mut open)
+        .default_size(egui::vec2(480.0, 360.0))
+        .show(ctx, |ui| {
+            let lines = match &view.lines {
+                Ok(lines) => lines,
+                Err(error) => {
+                    ui.colored_label(egui::Color32::RED, fill(t(Text::CantReadFile), &[error]));
+                    return;
+                }
+            };
+            ui.label(match view.spindle_lines.len() {
+                0 => t(Text::NoSpindleDetected).to_string(),
+                count => fill(t(Text::SpindleLinesDetected), &[&count]),
+            });
+            let width = lines.len().to_string().len();
+            egui::ScrollArea::both().show(ui, |ui| {
+                for (index, line) in lines.iter().enumerate() {
+                    let text = egui::RichText::new(format!(
+                        "{:>width$}  {}",
+                        index + 1,
+                        line,
+                        width = width
+                    ))
+                    .monospace();
+                    if view.spindle_lines.contains(&index) {
+                        ui.label(
+                            text.background_color(egui::Color32::from_rgb(90, 80, 20))
+                                .strong(),
+                        );
+                    } else {
+                        ui.label(text);
+                    }
+                }
+            });
+        });
+        if !open {
+            self.file_view = None;
+        }
+    }
+
+    /// Read-only audit of two folders; see [`compare_folders`].
+    fn show_comparison(&mut self, ui: &mut egui::Ui) {
+        let language = self.settings.language;
+        let t = |text: Text| text.get(language);
+        ui.collapsing(t(Text::CompareFolders), |ui| {
+            for (index, label) in [Text::OldFolder, Text::NewFolder].into_iter().enumerate() {
+                ui.horizontal(|ui| {
+                    let folder = self.compare_roots[index]
+                        .as_ref()
+                        .map_or(t(Text::NoneChosen).to_string(), |folder| {
+                            folder.display().to_string()
+                        });
+                    ui.label(fill(t(label), &[&folder]));
+                    if ui.button(t(Text::Browse)).clicked() {
+                        if let Some(folder) = rfd::FileDialog::new().pick_folder() {
+                            self.compare_roots[index] = Some(folder);
+                            self.comparison = None;
+                        }
+                    }
+                });
+            }
+
+            let [Some(old), Some(new)] = self.compare_roots.clone() else {
+                return;
+            };
+            if ui
+                .add_enabled(!self.comparing, egui::Button::new(t(Text::Compare)))
+                .clicked()
+            {
+                self.start_comparison(old, new);
+            }
+            if self.comparing {
+                ui.spinner();
+            }
+
+            let Some(comparison) = &self.comparison else {
+                return;
+            };
+            ui.label(fill(
+                t(Text::ComparisonSummary),
+                &[
+                    &comparison.differences.len(),
+                    &comparison.matching,
+                    &comparison.only_in_old.len(),
+                    &comparison.only_in_new.len(),
+                ],
+            ));
+            let speed = |speed: &Option<String>| match speed {
+                Some(number) => format!("S{}", number),
+                None => t(Text::NoSpeedWord).to_string(),
+            };
+            egui::ScrollArea::vertical()
+                .id_source("comparison")
+                .max_height(200.0)
+                .show(ui, |ui| {
+                    for difference in &comparison.differences {
+                        ui.colored_label(
+                            egui::Color32::YELLOW,
+                            format!(
+                                "{}: {} → {}",
+                                difference.name,
+                                speed(&difference.old_speed),
+                                speed(&difference.new_speed)
+                            ),
+                        );
+                    }
+                    for name in &comparison.only_in_old {
+                        ui.label(fill(t(Text::OnlyInOld), &[name]));
+                    }
+                    for name in &comparison.only_in_new {
+                        ui.label(fill(t(Text::OnlyInNew), &[name]));
+                    }
+                });
+        });
+    }
+
+    /// Undoing runs from the backup manifests: pick a run, see what restoring to before it
+    /// does to each file, and confirm. See [`plan_restore`].
+    fn show_restore(&mut self, ui: &mut egui::Ui) {
+        let idle = !self.processing && !self.restore_busy;
+        let mut list = false;
+        let mut picked = None;
+        let mut confirm = None;
+        let language = self.settings.language;
+        let t = |text: Text| text.get(language);
+        ui.collapsing(t(Text::RestoreFromBackups), |ui| {
+            ui.horizontal(|ui| {
+                list = ui
+                    .add_enabled(idle, egui::Button::new(t(Text::FindBackedUpRuns)))
+                    .clicked();
+                if self.restore_busy {
+                    ui.spinner();
+                }
+            });
+            let Some(runs) = &self.backed_up_runs else {
+                return;
+            };
+            if runs.is_empty() {
+                ui.label(t(Text::NoBackups));
+                return;
+            }
+            egui::ScrollArea::vertical()
+                .id_source("backed_up_runs")
+                .max_height(120.0)
+                .show(ui, |ui| {
+                    for run in runs {
+                        let selected = self
+                            .restore_plan
+                            .as_ref()
+                            .is_some_and(|(run_id, _)| *run_id == run.run_id);
+                        let label = fill(t(Text::BackedUpRun), &[&run.run_id, &run.files]);
+                        if ui.selectable_label(selected, label).clicked() {
+                            picked = Some(run.run_id.clone());
+                        }
+                    }
+                });
+
+            let Some((run_id, items)) = &self.restore_plan else {
+                return;
+            };
+            ui.label(fill(t(Text::RestoringTo), &[run_id]));
+            egui::ScrollArea::vertical()
+                .id_source("restore_plan")
+                .max_height(200.0)
+                .show(ui, |ui| {
+                    for item in items {
+                        let name = self.shown_path(&item.original);
+                        match item.action {
+                            RestoreAction::Restore => {
+                                ui.label(fill(t(Text::RestoreCopy), &[&name, &item.from_run]));
+                            }
+                            RestoreAction::Recreate => {
+                                ui.label(fill(t(Text::RestoreRecreate), &[&name, &item.from_run]));
+                            }
+                            RestoreAction::BackupMissing => {
+                                ui.colored_label(
+                                    egui::Color32::YELLOW,
+                                    fill(t(Text::RestoreMissing), &[&name]),
+                                );
+                            }
+                        }
+                    }
+                });
+            let restorable = items
+                .iter()
+                .filter(|item| item.action != RestoreAction::BackupMissing)
+                .count();
+            if self.confirming_restore {
+                ui.colored_label(
+                    egui::Color32::YELLOW,
+                    fill(t(Text::RestoreQuestion), &[&restorable, run_id]),
+                );
+                ui.horizontal(|ui| {
+                    if ui.button(t(Text::YesRestore)).clicked() {
+                        confirm = Some(true);
+                    }
+                    if ui.button(t(Text::No)).clicked() {
+                        confirm = Some(false);
+                    }
+                });
+            } else if ui
+                .add_enabled(
+                    idle && restorable > 0,
+                    egui::Button::new(fill(t(Text::RestoreFiles), &[&restorable])),
+                )
+                .clicked()
+            {
+                self.confirming_restore = true;
+            }
+        });
+
+        if list {
+            self.start_restore_listing();
+        }
+        if let Some(run_id) = picked.filter(|_| idle) {
+            self.start_restore_plan(run_id);
+        }
+        match confirm {
+            Some(true) => self.start_restore(),
+            Some(false) => self.confirming_restore = false,
+            None => {}
+        }
+    }
+
+    fn start_restore_listing(&mut self) {
+        self.restore_busy = true;
+        self.restore_plan = None;
+        self.confirming_restore = false;
+        let restore_sender = self.restore_sender.clone();
+        let folders = self.folders.clone();
+
+        tokio::spawn(async move {
+            let runs = backed_up_runs(&folders).await;
+            if let Err(send_error) = restore_sender.send(RestoreMessage::Runs(runs)) {
+                error!(
+                    "Failed to send backed-up runs to main thread: {}",
+                    send_error
+                );
+            }
+        });
+    }
+
+    fn start_restore_plan(&mut self, run_id: String) {
+        self.restore_busy = true;
+        self.confirming_restore = false;
+        let restore_sender = self.restore_sender.clone();
+        let folders = self.folders.clone();
+
+        tokio::spawn(async move {
+            let items = plan_restore(&folders, &run_id).await;
+            if let Err(send_error) = restore_sender.send(RestoreMessage::Plan(run_id, items)) {
+                error!("Failed to send restore plan to main thread: {}", send_error);
+            }
+        });
+    }
+
+    /// Carries out the confirmed restore plan in the background.
+    fn start_restore(&mut self) {
+        self.confirming_restore = false;
+        let Some((run_id, items)) = self.restore_plan.clone() else {
+            return;
+        };
+        let restore_run_id = new_run_id();
+        info_span!("run", id = %restore_run_id)
+            .in_scope(|| info!("Restoring {} files to before run {}", items.len(), run_id));
+        self.restore_busy = true;
+        let restore_sender = self.restore_sender.clone();
+
+        tokio::spawn(async move {
+            let summary = restore_files(&items, &restore_run_id).await;
+            let message = RestoreMessage::Restored(restore_run_id, summary);
+            if let Err(send_error) = restore_sender.send(message) {
+                error!(
+                    "Failed to send restore result to main thread: {}",
+                    send_error
+                );
+            }
+        });
+    }
+
+    /// Writes the previewed changes to `path` as a unified diff in the background.
+    fn start_patch_export(&mut self, path: PathBuf) -> Result<(), SpindleSpeedUpdaterError> {
+        let Some(entries) = self.preview.clone() else {
+            return Ok(());
+        };
+        let plan = self.speed_plan()?;
+        info!("Exporting patch to {:?}", path);
+        self.exporting_patch = true;
+        let patch_sender = self.patch_sender.clone();
+        let options = self.settings.update_options(&self.project_rules);
+        let file_cache = self.files_to_process();
+
+        tokio::spawn(async move {
+            let result = match build_patch(&plan, &options, &entries, &file_cache).await {
+                Ok(patch) => tokio::fs::write(&path, patch)
+                    .await
+                    .map(|()| path.clone())
+                    .map_err(SpindleSpeedUpdaterError::file_io(&path)),
+                Err(error) => Err(SpindleSpeedUpdaterError::Io(error)),
+            };
+            if let Err(send_error) = patch_sender.send(result) {
+                error!("Failed to send patch export to main thread: {}", send_error);
+            }
+        });
+        Ok(())
+    }
+
+    fn start_comparison(&mut self, old: PathBuf, new: PathBuf) {
+        info!("Comparing {:?} with {:?}", old, new);
+        self.comparing = true;
+        self.comparison = None;
+        let comparison_sender = self.comparison_sender.clone();
+        let follow_symlinks = self.settings.follow_symlinks;
+        let rules = self.project_rules.clone();
+
+        tokio::spawn(async move {
+            let result = compare_folders(&old, &new, follow_symlinks, &rules).await;
+            if let Err(send_error) = comparison_sender.send(result) {
+                error!("Failed to send comparison to main thread: {}", send_error);
+            }
+        });
+    }
+
+    /// Per-file events of the current or last run, following the newest line.
+    fn show_event_log(&self, ui: &mut egui::Ui) {
+        if self.event_log.is_empty() {
+            return;
+        }
+        let title = fill(
+            Text::RunLog.get(self.settings.language),
+            &[&self.event_log.len()],
+        );
+        ui.collapsing(title, |ui| {
+            egui::ScrollArea::vertical()
+                .id_source("event_log")
+                .max_height(150.0)
+                .stick_to_bottom(true)
+                .show(ui, |ui| {
+                    for event in &self.event_log {
+                        ui.monospace(event);
+                    }
+                });
+        });
+    }
+
+    /// The cached files a preview or run processes, after the age limit.
+    fn files_to_process(&self) -> HashMap<PathBuf, FileInfo> {
+        modified_within(
+            &self.file_cache,
+            self.settings.recent_window(),
+            SystemTime::now(),
+        )
+    }
+
+    /// `path` as the path display setting asks, relative to the folder it was found under.
+    fn shown_path(&self, path: &Path) -> String {
+        let root = self.file_cache.get(path).map(|info| info.root.as_path());
+        let root = root.or_else(|| {
+            self.folders
+                .iter()
+                .find(|folder| path.starts_with(folder))
+                .map(PathBuf::as_path)
+        });
+        self.settings.path_display.show(path, root)
+    }
+
+    /// Flashes the taskbar entry (bounces the dock icon on macOS), puts the outcome in the
+    /// title and shows it in a desktop notification, with the run summary and a sound,
+    /// when a run finished while the operator was in another window. The title goes back
+    /// once the window is focused.
+    fn show_completion_notice(&mut self, ctx: &egui::Context, frame: &mut eframe::Frame) {
+        let focused = ctx.input(|input| input.focused);
+        if let Some(notice) = self.completion_notice.take() {
+            if !focused {
+                frame.request_user_attention(egui::UserAttentionType::Informational);
+                frame.set_window_title(&format!("{} \u{2014} {}", notice, WINDOW_TITLE));
+                notify::notify(&notice, self.run_summary.as_deref().unwrap_or_default());
+                self.title_shows_notice = true;
+            }
+        } else if focused && self.title_shows_notice {
+            frame.set_window_title(WINDOW_TITLE);
+            self.title_shows_notice = false;
+        }
+    }
+
+    /// One line for the outcome of the last run, e.g. "✔ 58 • ↪ 2 • ✖ 1" for files that
+    /// ended at the target, were skipped and failed, coloured by the worst of them. A
+    /// click shows the full breakdown and the change hash below it.
+    fn show_status_chip(&mut self, ui: &mut egui::Ui) {
+        let Some(report) = &self.last_run else {
+            return;
+        };
+        let totals = &report.totals;
+        let on_target = totals.changed + totals.unchanged;
+        let skipped = totals.skipped_files();
+        let color = if totals.failed > 0 {
+            egui::Color32::RED
+        } else if skipped > 0 {
+            egui::Color32::YELLOW
+        } else {
+            egui::Color32::GREEN
+        };
+        // The symbols are in egui's bundled fonts; the spoken label says the same in words.
+        let chip = format!(
+            "\u{2714} {} \u{2022} \u{21AA} {} \u{2022} \u{2716} {}",
+            on_target, skipped, totals.failed
+        );
+        let language = self.settings.language;
+        let description = fill(
+            Text::LastRunStatus.get(language),
+            &[
+                &on_target,
+                &totals.changed,
+                &totals.unchanged,
+                &skipped,
+                &totals.failed,
+            ],
+        );
+        let response = ui
+            .add(egui::Button::new(egui::RichText::new(chip).color(color)).small())
+            .on_hover_text(fill(Text::ClickForDetails.get(language), &[&description]));
+        response.widget_info(|| egui::WidgetInfo::labeled(egui::WidgetType::Button, &description));
+        if response.clicked() {
+            self.status_expanded = !self.status_expanded;
+        }
+        if self.status_expanded {
+            if let Some(summary) = &self.run_summary {
+                ui.colored_label(egui::Color32::GREEN, summary);
+            }
+        }
+    }
+
+    /// Rewrites a pasted snippet on every frame with the current settings, so a line from
+    /// a new post can be checked before a real run.
+    fn show_try_it(&mut self, ui: &mut egui::Ui) {
+        let language = self.settings.language;
+        let t = |text: Text| text.get(language);
+        ui.collapsing(t(Text::TryIt), |ui| {
+            ui.label(t(Text::TryItHint));
+            ui.add(
+                egui::TextEdit::multiline(&mut self.try_it_input)
+                    .code_editor()
+                    .desired_rows(6)
+                    .desired_width(f32::INFINITY),
+            );
+            ui.horizontal(|ui| {
+                ui.label(t(Text::TryItSpeed));
+                ui.text_edit_singleline(&mut self.try_it_speed);
+            });
+            if self.try_it_input.trim().is_empty() || self.try_it_speed.is_empty() {
+                return;
+            }
+            let speed = match parse_spindle_speed(
+                &self.try_it_speed,
+                &self.project_rules.speed_range,
+                self.settings.language,
+            ) {
+                Ok(speed) => speed,
+                Err(reason) => {
+                    ui.colored_label(egui::Color32::RED, reason.message(self.settings.language));
+                    return;
+                }
+            };
+            let options = self.settings.update_options(&self.project_rules);
+            match rewrite_content(&self.try_it_input, speed, &options) {
+                Ok(update) => {
+                    ui.label(fill(t(Text::LinesChanged), &[&update.line_changes.len()]));
+                    let mut output = update.content;
+                    ui.add(
+                        egui::TextEdit::multiline(&mut output)
+                            .code_editor()
+                            .interactive(false)
+                            .desired_width(f32::INFINITY),
+                    );
+                }
+                Err(reason) => {
+                    ui.colored_label(egui::Color32::RED, fill(t(Text::LeftUnchanged), &[&reason]));
+                }
+            }
+        });
+    }
+
+    fn show_run_changes(&mut self, ui: &mut egui::Ui) {
+        let Some(comparison) = &self.run_changes else {
+            return;
+        };
+        let language = self.settings.language;
+        let t = |text: Text| text.get(language);
+        let mut view = None;
+        let heading = fill(
+            t(Text::ChangesSinceRun),
+            &[&comparison.previous_run_id, &comparison.files.len()],
+        );
+        ui.collapsing(heading, |ui| {
+            if let Some(report) = &self.last_run {
+                if report.target != comparison.previous_target {
+                    ui.label(fill(
+                        t(Text::TargetChanged),
+                        &[&comparison.previous_target, &report.target],
+                    ));
+                }
+            }
+            if comparison.files.is_empty() {
+                ui.label(t(Text::SameAsPreviousRun));
+            }
+            egui::ScrollArea::vertical()
+                .id_source("run_changes")
+                .max_height(150.0)
+                .show(ui, |ui| {
+                    for file in &comparison.files {
+                        let text = format!(
+                            "{}: {} \u{2192} {}",
+                            self.shown_path(&file.path),
+                            file.before.as_deref().unwrap_or(t(Text::NotInRun)),
+                            file.after.as_deref().unwrap_or(t(Text::NotInRun))
+                        );
+                        if file_row(ui, egui::RichText::new(text), &file.path, language) {
+                            view = Some(file.path.clone());
+                        }
+                    }
+                });
+        });
+        if let Some(path) = view {
+            self.start_file_view(path);
+        }
+    }
+
+    fn show_failures(&mut self, ui: &mut egui::Ui) {
+        let Some(report) = &self.last_run else {
+            return;
+        };
+        let failures = report.failures().count();
+        if failures == 0 {
+            return;
+        }
+        let mut view = None;
+        let language = self.settings.language;
+        ui.collapsing(fill(Text::FilesFailed.get(language), &[&failures]), |ui| {
+            egui::ScrollArea::vertical()
+                .id_source("failures")
+                .max_height(150.0)
+                .show(ui, |ui| {
+                    for failure in report.failures() {
+                        if let FileOutcome::Error(error) = &failure.outcome {
+                            let text = format!("{}: {}", self.shown_path(&failure.path), error);
+                            if file_row(
+                                ui,
+                                egui::RichText::new(text).color(egui::Color32::RED),
+                                &failure.path,
+                                language,
+                            ) {
+                                view = Some(failure.path.clone());
+                            }
+                        }
+                    }
+                });
+        });
+        if let Some(path) = view {
+            self.start_file_view(path);
+        }
+    }
+
+    /// Clears the success message once it has been shown for the configured time and
+    /// returns how long it has left otherwise. Error messages are never dismissed here.
+    fn expire_success_message(&mut self) -> Option<Duration> {
+        let timeout = self.settings.success_message_timeout_secs;
+        let shown_at = self.success_shown_at?;
+        if timeout == 0 {
+            return None;
+        }
+
+        let remaining = Duration::from_secs(timeout).saturating_sub(shown_at.elapsed());
+        if remaining.is_zero() {
+            self.success_message = None;
+            self.success_shown_at = None;
+            None
+        } else {
+            Some(remaining)
+        }
+    }
+
+    /// Drains the background task's channels and applies the outcome to the app state.
+    /// Kept free of any egui types so the cross-thread path can be driven without a window.
+    fn poll_background(&mut self) {
+        if let Ok(error) = self.error_receiver.try_recv() {
+            self.error_message = Some(error.to_string());
+            self.processing = false;
+            self.retrying = false;
+            self.cancelling = false;
+            self.cancel_sender = None;
+            info_span!("run", id = %self.run_id.as_deref().unwrap_or_default())
+                .in_scope(|| error!("Received error from background thread: {:?}", error));
+        }
+
+        if let Ok(entries) = self.audit_receiver.try_recv() {
+            self.auditing = false;
+            info!(
+                "Audit finished: {} of {} files flagged",
+                entries
+                    .iter()
+                    .filter(|entry| !entry.anomalies.is_empty())
+                    .count(),
+                entries.len()
+            );
+            self.audit = Some(entries);
+        }
+
+        if let Ok(entries) = self.preview_receiver.try_recv() {
+            self.previewing = false;
+            if std::mem::take(&mut self.preflight_pending) {
+                let summary = PreflightSummary::from_entries(&entries);
+                let language = self.settings.language;
+                self.unclean_dry_pass = self.settings.clean_criteria.unclean_reason(&entries);
+                let cap = self
+                    .settings
+                    .limit_files_to_modify
+                    .then_some(self.settings.max_files_to_modify);
+                let refusal = file_cap_refusal(&summary, cap, language).or_else(|| {
+                    let show_path = |path: &Path| self.shown_path(path);
+                    self.settings
+                        .require_spindle_command
+                        .then(|| missing_spindle_refusal(&entries, show_path, language))
+                        .flatten()
+                });
+                if let Some(refusal) = refusal {
+                    warn!("Run refused: {}", refusal);
+                    self.error_message = Some(refusal);
+                } else {
+                    let mut warnings = Vec::new();
+                    if summary.exceeds(self.settings.max_failure_percent) {
+                        warnings.push(preflight_warning(
+                            &summary,
+                            self.settings.max_failure_percent,
+                            language,
+                        ));
+                    }
+                    if self.settings.warn_on_speed_drop {
+                        warnings.extend(speed_drop_warning(
+                            &entries,
+                            self.settings.speed_drop_percent,
+                            language,
+                        ));
+                    }
+                    if warnings.is_empty() {
+                        self.proceed_after_preflight();
+                    } else {
+                        let warning = warnings.join("\n");
+                        warn!("Pre-flight check failed: {}", warning);
+                        self.safety_warning = Some(warning);
+                    }
+                }
+            }
+            self.preview = Some(entries);
+        }
+
+        if let Ok(entries) = self.self_check_receiver.try_recv() {
+            self.self_checking = false;
+            let show_path = |path: &Path| self.shown_path(path);
+            self.self_check_warning =
+                self_check_warning(&entries, show_path, self.settings.language);
+            match &self.self_check_warning {
+                Some(warning) => warn!("Startup self-check: {}", warning),
+                None => info!("Startup self-check passed"),
+            }
+        }
+
+        if let Ok(result) = self.patch_receiver.try_recv() {
+            self.exporting_patch = false;
+            match result {
+                Ok(path) => {
+                    info!("Patch written to {:?}", path);
+                    self.success_message = Some(fill(
+                        Text::PatchWritten.get(self.settings.language),
+                        &[&path.display()],
+                    ));
+                    self.success_shown_at = Some(Instant::now());
+                }
+                Err(error) => {
+                    error!("Patch export failed: {}", error);
+                    self.error_message = Some(fill(
+                        Text::PatchExportFailed.get(self.settings.language),
+                        &[&error],
+                    ));
+                }
+            }
+        }
+
+        if let Ok(result) = self.comparison_receiver.try_recv() {
+            self.comparing = false;
+            match result {
+                Ok(comparison) => self.comparison = Some(comparison),
+                Err(error) => {
+                    error!("Folder comparison failed: {}", error);
+                    self.error_message = Some(error.to_string());
+                }
+            }
+        }
+
+        if let Ok(message) = self.restore_receiver.try_recv() {
+            let language = self.settings.language;
+            self.restore_busy = false;
+            match message {
+                RestoreMessage::Runs(Ok(runs)) => self.backed_up_runs = Some(runs),
+                RestoreMessage::Plan(run_id, Ok(items)) => {
+                    self.restore_plan = Some((run_id, items))
+                }
+                RestoreMessage::Runs(Err(error)) | RestoreMessage::Plan(_, Err(error)) => {
+                    error!("Failed to read the backup manifests: {}", error);
+                    self.error_message =
+                        Some(fill(Text::ManifestsUnreadable.get(language), &[&error]));
+                }
+                RestoreMessage::Restored(restore_run_id, summary) => {
+                    info_span!("run", id = %restore_run_id)
+                        .in_scope(|| info!("Restore finished: {:?}", summary));
+                    let mut message = fill(
+                        Text::Restored.get(language),
+                        &[&summary.restored, &summary.recreated, &restore_run_id],
+                    );
+                    if !summary.missing.is_empty() {
+                        message.push_str(&fill(
+                            Text::RestoreNoCopyLeft.get(language),
+                            &[&summary.missing.len()],
+                        ));
+                    }
+                    self.success_message = Some(message);
+                    self.success_shown_at = Some(Instant::now());
+                    if !summary.failed.is_empty() {
+                        let failures: Vec<String> = summary
+                            .failed
+                            .iter()
+                            .map(|(path, error)| format!("{}: {}", self.shown_path(path), error))
+                            .collect();
+                        self.error_message = Some(fill(
+                            Text::RestoreFailed.get(language),
+                            &[&failures.len(), &failures.join("\n")],
+                        ));
+                    }
+                    // The files and the manifests both changed.
+                    self.refresh_file_cache();
+                }
+            }
+        }
+
+        if let Ok((cancel, result)) = self.scan_receiver.try_recv() {
+            // Results of a scan that was cancelled or replaced are dropped.
+            if self
+                .scan_cancel
+                .as_ref()
+                .is_some_and(|current| Arc::ptr_eq(current, &cancel))
+            {
+                self.scan_cancel = None;
+                match result {
+                    Ok((file_cache, duplicates)) => {
+                        info!("File cache updated: {} files", file_cache.len());
+                        self.file_cache = file_cache;
+                        self.duplicate_files = duplicates;
+                        self.mapping_report = self
+                            .speed_mapping
+                            .as_ref()
+                            .map(|mapping| compare_mapping(&mapping.speeds, &self.file_cache));
+                        if std::mem::take(&mut self.self_check_after_scan) {
+                            self.start_self_check();
+                        }
+                    }
+                    Err(error) => {
+                        error!("Failed to update file cache: {:?}", error);
+                        self.error_message = Some(fill(
+                            Text::ScanFailed.get(self.settings.language),
+                            &[&error],
+                        ));
+                    }
+                }
+            }
+        }
+
+        for event in self.event_receiver.try_iter() {
+            if self.event_log.len() == EVENT_LOG_LIMIT {
+                self.event_log.pop_front();
+            }
+            self.event_log.push_back(event);
+        }
+
+        if let Ok(view) = self.file_view_receiver.try_recv() {
+            self.file_view = Some(view);
+        }
+
+        if let Ok(report) = self.completion_receiver.try_recv() {
+            self.processing = false;
+            self.cancelling = false;
+            self.cancel_sender = None;
+            let retried = std::mem::take(&mut self.retrying);
+            let report = match self.last_run.take() {
+                Some(mut original) if retried => {
+                    info_span!("run", id = %report.run_id).in_scope(|| {
+                        info!(
+                            "Retry of run {}: {} changed, {} failed",
+                            original.run_id, report.totals.changed, report.totals.failed
+                        )
+                    });
+                    original.merge_retry(report);
+                    original
+                }
+                _ => report,
+            };
+            let _run = info_span!("run", id = %report.run_id).entered();
+            let language = self.settings.language;
+            let totals = &report.totals;
+            let mut message = success_message(
+                totals,
+                &report.target,
+                self.settings.success_count,
+                language,
+            );
+            if let Some(speed) = self
+                .settings
+                .update_options(&self.project_rules)
+                .match_current_speed
+            {
+                message.push_str(&fill(
+                    Text::MatchedCurrentSpeed.get(language),
+                    &[&matched_count(totals), &format_rpm(speed)],
+                ));
+            }
+            if report.folders.len() > 1 {
+                for (root, summary) in summarize_by_root(totals) {
+                    message.push_str(&format!("\n{}: {}", root.display(), summary));
+                }
+            }
+            if let Some(path) = &report.results_file {
+                message.push('\n');
+                message.push_str(&fill(
+                    Text::PerFileResults.get(language),
+                    &[&path.display()],
+                ));
+            }
+            info!("{}", message);
+            self.run_summary = Some(message);
+            if self.settings.notify_on_completion {
+                self.completion_notice = Some(fill(
+                    Text::CompletionNotice.get(language),
+                    &[&totals.changed, &totals.failed],
+                ));
+            }
+            info!("Change hash: {}", report.change_hash);
+
+            if let Some(first) = report.failures().next() {
+                if let FileOutcome::Error(error) = &first.outcome {
+                    self.error_message = Some(fill(
+                        Text::FilesFailedExample.get(language),
+                        &[
+                            &report.failures().count(),
+                            &self.shown_path(&first.path),
+                            error,
+                        ],
+                    ));
+                }
+            }
+            info!(
+                "Spindle speed update completed in {} ms",
+                report.duration_ms
+            );
+            let roots = canonical_roots(&report.folders);
+            // Runs that wrote their results out in chunks only kept some files to compare.
+            // A retry still compares the run it completed against the one before that.
+            self.run_changes = match &self.previous_run {
+                _ if retried => self.run_changes.take(),
+                Some((previous_roots, previous))
+                    if *previous_roots == roots
+                        && previous.has_every_file()
+                        && report.has_every_file() =>
+                {
+                    Some(report.changes_since(previous))
+                }
+                _ => None,
+            };
+            self.previous_run = Some((roots, report.clone()));
+            self.last_run = Some(report);
+        }
+    }
+}
+
+impl eframe::App for MainApp {
+    fn update(&mut self, ctx: &egui::Context, frame: &mut eframe::Frame) {
+        self.poll_background();
+        let language = self.settings.language;
+        let t = |text: Text| text.get(language);
+        if self.close_after_stop && !self.processing {
+            info!("Run stopped, closing");
+            frame.close();
+        }
+        if let Some(remaining) = self.expire_success_message() {
+            ctx.request_repaint_after(remaining);
+        }
+        self.show_completion_notice(ctx, frame);
+        if self.choosing_first_folder {
+            self.show_first_folder_prompt(ctx);
+            return;
+        }
+
+        egui::CentralPanel::default().show(ctx, |ui| {
+            ui.heading(t(Text::AppTitle));
+
+            self.show_folders(ui);
+            if self.scanning() {
+                ui.horizontal(|ui| {
+                    ui.spinner();
+                    ui.label(t(Text::Scanning));
+                    if ui.button(t(Text::Cancel)).clicked() {
+                        self.cancel_scan();
+                        self.error_message = Some(t(Text::ScanCancelled).to_string());
+                    }
+                });
+            } else {
+                // Counted only once the scan is done, so a launch on a large folder doesn't
+                // claim that no files were found.
+                ui.label(fill(
+                    t(Text::FilesFound),
+                    &[
+                        &self.file_cache.len(),
+                        &self.project_rules.extensions.join("/."),
+                    ],
+                ));
+                if let Some(days) = self.settings.recent_window() {
+                    ui.label(fill(
+                        t(Text::RecentFiles),
+                        &[&self.files_to_process().len(), &days],
+                    ));
+                }
+            }
+            if !self.duplicate_files.is_empty() {
+                ui.collapsing(
+                    egui::RichText::new(fill(
+                        t(Text::DuplicatePaths),
+                        &[&self.duplicate_files.len()],
+                    ))
+                    .color(egui::Color32::YELLOW),
+                    |ui| {
+                        for path in &self.duplicate_files {
+                            ui.label(self.shown_path(path));
+                        }
+                    },
+                );
+            }
+            self.show_speed_mapping(ui);
+
+            let mut update_triggered = false;
+
+            ui.horizontal(|ui| {
+                ui.label(t(Text::SpeedMode));
+                for mode in SpeedMode::ALL {
+                    ui.radio_value(&mut self.settings.speed_mode, mode, mode.label(language));
+                }
+            });
+            let mode = self.settings.speed_mode;
+
+            // Checked on every frame so bad input is flagged while typing. A loaded mapping
+            // or the speed rules supply the speeds, so the field doesn't matter then.
+            let speed_check = if self.per_file_speeds() {
+                Ok(0)
+            } else {
+                self.typed_speed(mode)
+            };
+            let show_invalid = speed_check.is_err()
+                && self
+                    .speed_inputs(mode)
+                    .iter()
+                    .any(|input| !input.is_empty());
+            ui.horizontal(|ui| {
+                let responses = match mode {
+                    SpeedMode::Absolute => {
+                        ui.label(t(Text::SpeedPrompt));
+                        vec![speed_field(ui, &mut self.spindle_speed_input, show_invalid)]
+                    }
+                    SpeedMode::Percent => {
+                        let maximum = format_rpm(*self.project_rules.speed_range.end());
+                        ui.label(fill(t(Text::PercentPrompt), &[&maximum]));
+                        let response = speed_field(ui, &mut self.percent_input, show_invalid);
+                        ui.label("%");
+                        vec![response]
+                    }
+                    SpeedMode::SurfaceSpeed => {
+                        ui.label(t(Text::SurfaceSpeedPrompt));
+                        let surface = speed_field(ui, &mut self.surface_speed_input, show_invalid);
+                        ui.label(t(Text::DiameterPrompt));
+                        let diameter = speed_field(ui, &mut self.diameter_input, show_invalid);
+                        vec![surface, diameter]
+                    }
+                };
+                match speed_check {
+                    Err(reason) if show_invalid => {
+                        ui.colored_label(egui::Color32::RED, reason.message(language));
+                    }
+                    Ok(speed) if mode != SpeedMode::Absolute && !self.per_file_speeds() => {
+                        ui.label(fill(t(Text::ComputedSpeed), &[&format_rpm(speed)]));
+                    }
+                    _ => {}
+                }
+                let entered = responses.iter().any(egui::Response::lost_focus);
+                if entered && ui.input(|i| i.key_pressed(egui::Key::Enter)) {
+                    let now = Instant::now();
+                    if now.duration_since(self.last_enter_press).as_millis() > 500 {
+                        update_triggered = true;
+                        self.last_enter_press = now;
+                    }
+                }
+            });
+
+            ui.checkbox(&mut self.settings.audit_only, t(Text::AuditOnly));
+            let mut preview_clicked = false;
+            let mut audit_clicked = false;
+            let button_clicked = ui
+                .horizontal(|ui| {
+                    if self.settings.audit_only {
+                        audit_clicked = ui
+                            .add_enabled(
+                                !self.processing && !self.auditing && !self.scanning(),
+                                egui::Button::new(egui::RichText::new(t(Text::RunAudit)).strong()),
+                            )
+                            .clicked();
+                        return false;
+                    }
+                    preview_clicked = ui
+                        .add_enabled(
+                            !self.processing && !self.previewing && !self.scanning(),
+                            egui::Button::new(t(Text::Preview)),
+                        )
+                        .clicked();
+                    ui.add_enabled(
+                        speed_check.is_ok()
+                            && !self.scanning()
+                            && !self.restore_busy
+                            && !self.processing,
+                        egui::Button::new(egui::RichText::new(t(Text::UpdateSpeeds)).strong())
+                            .fill(egui::Color32::from_rgb(108, 108, 108)),
+                    )
+                    .clicked()
+                })
+                .inner;
+
+            // An audit changes nothing, so it runs without the confirmation dialog.
+            if audit_clicked {
+                self.error_message = None;
+                self.start_audit();
+            }
+
+            if preview_clicked {
+                match self.prepare_run() {
+                    Ok(_) => {
+                        self.error_message = None;
+                        if let Err(error) = self.start_preview() {
+                            self.error_message = Some(error.to_string());
+                        }
+                    }
+                    Err(error) => {
+                        self.error_message = Some(error);
+                    }
+                }
+            }
+
+            if (button_clicked || update_triggered)
+                && !self.settings.audit_only
+                && speed_check.is_ok()
+                && !self.scanning()
+                && !self.processing
+                && !self.previewing
+                && !self.show_confirmation_dialog
+            {
+                match self.prepare_run() {
+                    Ok(_) => {
+                        self.error_message = None;
+                        self.safety_warning = None;
+                        self.skip_confirmation =
+                            update_triggered && self.settings.enter_action == EnterAction::Run;
+                        match self.start_preview() {
+                            Ok(()) => self.preflight_pending = true,
+                            Err(error) => self.error_message = Some(error.to_string()),
+                        }
+                    }
+                    Err(error) => {
+                        self.error_message = Some(error);
+                    }
+                }
+            }
+
+            // ERROR PROCESSING & PROGRESS BAR
+            if self.processing {
+                let (processed, total) = self.progress.counts();
+                if total > 0 {
+                    let progress = processed as f32 / total as f32;
+                    ui.add(egui::ProgressBar::new(progress).show_percentage());
+                    ui.label(fill(t(Text::ProcessedOf), &[&processed, &total]));
+                }
+                if let Some(current_file) = self.progress.current_file() {
+                    ui.label(fill(
+                        t(Text::CurrentFile),
+                        &[&current_file
+                            .file_name()
+                            .unwrap_or_default()
+                            .to_string_lossy()],
+                    ));
+                }
+                if self.cancelling {
+                    ui.label(t(Text::Stopping));
+                } else if ui.button(t(Text::Cancel)).clicked() {
+                    if let Err(error) = self.cancel_operation() {
+                        self.error_message = Some(error.to_string());
+                    }
+                }
+            }
+
+            if let Some(error_message) = &self.error_message {
+                ui.colored_label(egui::Color32::RED, error_message);
+            }
+
+            let failed = self
+                .last_run
+                .as_ref()
+                .filter(|_| !self.processing && self.last_run_setup.is_some())
+                .map_or(0, |report| report.retryable().count());
+            if failed > 0 && ui.button(fill(t(Text::RetryFailed), &[&failed])).clicked() {
+                self.retry_failed();
+            }
+
+            if self.self_checking {
+                ui.label(t(Text::SelfChecking));
+            }
+
+            if let Some(warning) = &self.self_check_warning {
+                ui.colored_label(egui::Color32::YELLOW, warning);
+            }
+
+            if let Some(warning) = &self.safety_warning {
+                ui.colored_label(egui::Color32::YELLOW, warning);
+                if ui.button(t(Text::ProceedAnyway)).clicked() {
+                    warn!("Pre-flight warning overridden by user");
+                    self.safety_warning = None;
+                    self.proceed_after_preflight();
+                }
+            }
+
+            if let Some(success_message) = &self.success_message {
+                ui.colored_label(egui::Color32::GREEN, success_message);
+            }
+
+            self.show_status_chip(ui);
+            if let Some(run_id) = &self.run_id {
+                ui.label(fill(t(Text::RunId), &[run_id]));
+            }
+            if let Some(hash) = self
+                .last_run
+                .as_ref()
+                .filter(|_| self.status_expanded)
+                .map(|report| &report.change_hash)
+            {
+                ui.horizontal(|ui| {
+                    ui.label(fill(t(Text::ChangeHash), &[hash]));
+                    if ui.small_button(t(Text::Copy)).clicked() {
+                        ui.output_mut(|output| output.copied_text = hash.clone());
+                    }
+                });
+            }
+
+            self.show_event_log(ui);
+            self.show_failures(ui);
+            self.show_run_changes(ui);
+            self.show_preview(ui);
+            self.show_audit(ui);
+
+            if !self.processing && ui.button(t(Text::ClearMessages)).clicked() {
+                self.error_message = None;
+                self.last_run = None;
+                self.run_changes = None;
+                self.safety_warning = None;
+                self.self_check_warning = None;
+                self.success_message = None;
+                self.success_shown_at = None;
+                self.run_summary = None;
+                self.status_expanded = false;
+            }
+
+            self.show_comparison(ui);
+            self.show_restore(ui);
+            self.show_try_it(ui);
+
+            ui.collapsing(t(Text::WhichLines), |ui| {
+                let options = self.settings.update_options(&self.project_rules);
+                for rule in detection_rules(&options, self.settings.language) {
+                    ui.label(format!("• {}", rule));
+                }
+            });
+
+            ui.collapsing(t(Text::Settings), |ui| {
+                self.show_profiles(ui);
+                egui::ComboBox::from_label(t(Text::Language))
+                    .selected_text(self.settings.language.label())
+                    .show_ui(ui, |ui| {
+                        for language in Language::ALL {
+                            ui.selectable_value(
+                                &mut self.settings.language,
+                                language,
+                                language.label(),
+                            );
+                        }
+                    });
+                ui.horizontal(|ui| {
+                    ui.label(t(Text::HideSuccessAfter));
+                    ui.add(egui::DragValue::new(
+                        &mut self.settings.success_message_timeout_secs,
+                    ));
+                });
+                egui::ComboBox::from_label(t(Text::SuccessCounts))
+                    .selected_text(self.settings.success_count.label(language))
+                    .show_ui(ui, |ui| {
+                        for count in SuccessCount::ALL {
+                            ui.selectable_value(
+                                &mut self.settings.success_count,
+                                count,
+                                count.label(language),
+                            );
+                        }
+                    });
+                egui::ComboBox::from_label(t(Text::EnterInSpeedField))
+                    .selected_text(self.settings.enter_action.label(language))
+                    .show_ui(ui, |ui| {
+                        for action in EnterAction::ALL {
+                            ui.selectable_value(
+                                &mut self.settings.enter_action,
+                                action,
+                                action.label(language),
+                            );
+                        }
+                    });
+                ui.checkbox(
+                    &mut self.settings.enter_confirms_dialog,
+                    t(Text::EnterConfirmsDialog),
+                );
+                egui::ComboBox::from_label(t(Text::PathsInLists))
+                    .selected_text(self.settings.path_display.label(language))
+                    .show_ui(ui, |ui| {
+                        for display in PathDisplay::ALL {
+                            ui.selectable_value(
+                                &mut self.settings.path_display,
+                                display,
+                                display.label(language),
+                            );
+                        }
+                    });
+                egui::ComboBox::from_label(t(Text::SpindleToUpdate))
+                    .selected_text(self.settings.spindle_target.label(language))
+                    .show_ui(ui, |ui| {
+                        for target in SpindleTarget::ALL {
+                            ui.selectable_value(
+                                &mut self.settings.spindle_target,
+                                target,
+                                target.label(language),
+                            );
+                        }
+                    });
+                ui.checkbox(&mut self.settings.insert_missing_s, t(Text::InsertMissing));
+                ui.add_enabled_ui(self.settings.insert_missing_s, |ui| {
+                    egui::ComboBox::from_label(t(Text::Insert))
+                        .selected_text(self.settings.insertion_point.label(language))
+                        .show_ui(ui, |ui| {
+                            for point in InsertionPoint::ALL {
+                                ui.selectable_value(
+                                    &mut self.settings.insertion_point,
+                                    point,
+                                    point.label(language),
+                                );
+                            }
+                        });
+                    ui.horizontal(|ui| {
+                        ui.checkbox(
+                            &mut self.settings.use_insertion_marker,
+                            t(Text::InsertAfterMarker),
+                        );
+                        ui.add_enabled(
+                            self.settings.use_insertion_marker,
+                            egui::TextEdit::singleline(&mut self.settings.insertion_marker)
+                                .desired_width(80.0),
+                        );
+                    });
+                    ui.add_enabled(
+                        self.settings.use_insertion_marker,
+                        egui::Checkbox::new(
+                            &mut self.settings.require_insertion_marker,
+                            t(Text::SkipWithoutMarker),
+                        ),
+                    );
+                });
+                ui.checkbox(
+                    &mut self.settings.trim_rewritten_lines,
+                    t(Text::TrimRewritten),
+                );
+                ui.checkbox(
+                    &mut self.settings.uppercase_m_codes,
+                    t(Text::UppercaseMCodes),
+                );
+                ui.checkbox(&mut self.settings.pad_speed_width, t(Text::PadSpeedWidth));
+                ui.checkbox(
+                    &mut self.settings.spindle_word_first,
+                    t(Text::SpindleWordFirst),
+                );
+                ui.checkbox(&mut self.settings.check_structure, t(Text::CheckStructure));
+                ui.horizontal(|ui| {
+                    ui.checkbox(
+                        &mut self.settings.use_speed_override,
+                        t(Text::ApplySpeedOverride),
+                    );
+                    ui.add_enabled(
+                        self.settings.use_speed_override,
+                        egui::TextEdit::singleline(&mut self.settings.speed_override_marker)
+                            .desired_width(120.0),
+                    );
+                });
+                ui.horizontal(|ui| {
+                    ui.checkbox(&mut self.settings.add_change_stamp, t(Text::AddChangeStamp));
+                    ui.add_enabled(
+                        self.settings.add_change_stamp,
+                        egui::TextEdit::singleline(&mut self.settings.change_stamp_template)
+                            .desired_width(240.0),
+                    );
+                })
+                .response
+                .on_hover_text(t(Text::ChangeStampPlaceholders));
+                if self.settings.add_change_stamp
+                    && !ChangeStamp::is_comment(&self.settings.change_stamp_template)
+                {
+                    ui.colored_label(egui::Color32::YELLOW, t(Text::ChangeStampNotComment));
+                }
+                ui.checkbox(
+                    &mut self.settings.after_tool_change,
+                    t(Text::AfterToolChange),
+                );
+                ui.checkbox(&mut self.settings.each_tool_change, t(Text::EachToolChange));
+                ui.horizontal(|ui| {
+                    ui.checkbox(
+                        &mut self.settings.match_current_speed,
+                        t(Text::OnlyCurrentlyAt),
+                    );
+                    ui.add_enabled(
+                        self.settings.match_current_speed,
+                        egui::DragValue::new(&mut self.settings.current_speed_filter)
+                            .clamp_range(self.project_rules.speed_range.clone())
+                            .suffix(t(Text::RpmSuffix)),
+                    );
+                });
+                if ui
+                    .checkbox(&mut self.settings.follow_symlinks, t(Text::FollowSymlinks))
+                    .changed()
+                {
+                    self.refresh_file_cache();
+                }
+                ui.checkbox(
+                    &mut self.settings.startup_self_check,
+                    t(Text::StartupSelfCheck),
+                );
+                ui.checkbox(
+                    &mut self.settings.notify_on_completion,
+                    t(Text::NotifyOnCompletion),
+                );
+                ui.checkbox(
+                    &mut self.settings.auto_apply_if_clean,
+                    t(Text::AutoApplyIfClean),
+                );
+                ui.add_enabled_ui(self.settings.auto_apply_if_clean, |ui| {
+                    ui.indent("clean_criteria", |ui| {
+                        let criteria = &mut self.settings.clean_criteria;
+                        ui.checkbox(
+                            &mut criteria.single_change_per_file,
+                            t(Text::CleanSingleChange),
+                        );
+                        ui.checkbox(&mut criteria.allow_unchanged, t(Text::CleanAllowUnchanged));
+                        ui.checkbox(
+                            &mut criteria.allow_no_spindle_command,
+                            t(Text::CleanAllowNoSpindle),
+                        );
+                        ui.checkbox(&mut criteria.allow_inserted, t(Text::CleanAllowInserted));
+                    });
+                });
+                ui.horizontal(|ui| {
+                    ui.label(t(Text::MaxFailurePercent));
+                    ui.add(
+                        egui::DragValue::new(&mut self.settings.max_failure_percent)
+                            .clamp_range(0..=100),
+                    );
+                });
+                ui.horizontal(|ui| {
+                    ui.checkbox(
+                        &mut self.settings.limit_files_to_modify,
+                        t(Text::LimitFiles),
+                    );
+                    ui.add_enabled(
+                        self.settings.limit_files_to_modify,
+                        egui::DragValue::new(&mut self.settings.max_files_to_modify)
+                            .clamp_range(1..=usize::MAX),
+                    );
+                    ui.label(t(Text::Files));
+                });
+                ui.horizontal(|ui| {
+                    ui.checkbox(
+                        &mut self.settings.limit_to_recent,
+                        t(Text::OnlyModifiedWithin),
+                    );
+                    ui.add_enabled(
+                        self.settings.limit_to_recent,
+                        egui::DragValue::new(&mut self.settings.recent_days)
+                            .clamp_range(1..=u32::MAX),
+                    );
+                    ui.label(t(Text::Days));
+                });
+                ui.checkbox(
+                    &mut self.settings.require_spindle_command,
+                    t(Text::RequireSpindleCommand),
+                );
+                ui.horizontal(|ui| {
+                    ui.checkbox(
+                        &mut self.settings.warn_on_speed_drop,
+                        t(Text::WarnOnSpeedDrop),
+                    );
+                    ui.add_enabled(
+                        self.settings.warn_on_speed_drop,
+                        egui::DragValue::new(&mut self.settings.speed_drop_percent)
+                            .clamp_range(1..=100)
+                            .suffix("%"),
+                    );
+                    ui.label(t(Text::OfCurrentSpeed));
+                });
+                ui.checkbox(
+                    &mut self.settings.check_folder_fingerprint,
+                    t(Text::CheckFolderFingerprint),
+                );
+                ui.add_enabled_ui(self.settings.check_folder_fingerprint, |ui| {
+                    ui.horizontal(|ui| {
+                        ui.label(t(Text::SentinelFile));
+                        ui.text_edit_singleline(&mut self.settings.sentinel_file);
+                    });
+                    ui.horizontal(|ui| {
+                        ui.label(t(Text::ExpectedFileCount));
+                        ui.add(egui::DragValue::new(&mut self.settings.expected_file_count));
+                        if ui
+                            .add_enabled(
+                                !self.scanning(),
+                                egui::Button::new(t(Text::UseCurrentCount)),
+                            )
+                            .clicked()
+                        {
+                            self.settings.expected_file_count =
+                                u32::try_from(self.file_cache.len()).unwrap_or(u32::MAX);
+                        }
+                    });
+                });
+                ui.collapsing(t(Text::Advanced), |ui| {
+                    ui.horizontal(|ui| {
+                        ui.label(t(Text::WriteDelay));
+                        ui.add(egui::DragValue::new(&mut self.settings.write_delay_ms));
+                    });
+                    ui.horizontal(|ui| {
+                        ui.label(t(Text::IoRetries));
+                        ui.add(
+                            egui::DragValue::new(&mut self.settings.io_retries).clamp_range(0..=10),
+                        );
+                    });
+                    ui.checkbox(&mut self.settings.verify_writes, t(Text::VerifyWrites));
+                    ui.checkbox(&mut self.settings.create_backups, t(Text::CreateBackups));
+                    ui.checkbox(
+                        &mut self.settings.preserve_metadata,
+                        t(Text::PreserveMetadata),
+                    );
+                    egui::ComboBox::from_label(t(Text::UpdatedFiles))
+                        .selected_text(self.settings.output_mode.label(language))
+                        .show_ui(ui, |ui| {
+                            for mode in OutputMode::ALL {
+                                ui.selectable_value(
+                                    &mut self.settings.output_mode,
+                                    mode,
+                                    mode.label(language),
+                                );
+                            }
+                        });
+                    if self.settings.output_mode == OutputMode::OutputFolder {
+                        ui.horizontal(|ui| {
+                            let shown = match &self.settings.output_folder {
+                                Some(folder) => folder.display().to_string(),
+                                None => t(Text::NoneChosen).to_string(),
+                            };
+                            ui.label(fill(t(Text::OutputFolder), &[&shown]));
+                            if ui.button(t(Text::Change)).clicked() {
+                                if let Some(folder) = rfd::FileDialog::new().pick_folder() {
+                                    self.settings.output_folder = Some(folder);
+                                }
+                            }
+                        });
+                        ui.checkbox(&mut self.settings.copy_unchanged, t(Text::CopyUnchanged));
+                    }
+                    ui.horizontal(|ui| {
+                        let shown = history_path(self.settings.run_history_file.as_deref())
+                            .map(|path| path.display().to_string())
+                            .unwrap_or_else(|error| error.to_string());
+                        ui.label(fill(t(Text::RunHistory), &[&shown]));
+                        if ui.button(t(Text::Change)).clicked() {
+                            if let Some(path) = rfd::FileDialog::new()
+                                .add_filter("CSV", &["csv"])
+                                .set_file_name(HISTORY_FILE_NAME)
+                                .save_file()
+                            {
+                                self.settings.run_history_file = Some(path);
+                            }
+                        }
+                        if self.settings.run_history_file.is_some()
+                            && ui.button(t(Text::UseDefault)).clicked()
+                        {
+                            self.settings.run_history_file = None;
+                        }
+                    });
+                    ui.horizontal(|ui| {
+                        ui.label(t(Text::ResultChunkSize));
+                        ui.add(egui::DragValue::new(&mut self.settings.result_chunk_size));
+                    });
+                });
+            });
+        });
+
+        if self.show_confirmation_dialog {
+            self.show_confirmation_dialog(ctx);
+        }
+
+        if self.close_requested {
+            self.show_close_dialog(ctx);
+        }
+
+        self.show_file_view(ctx);
+
+        if self.processing
+            || self.previewing
+            || self.self_checking
+            || self.comparing
+            || self.exporting_patch
+            || self.scanning()
+        {
+            ctx.request_repaint();
+        }
+    }
+
+    /// Closing mid-run is deferred: the user is asked to cancel first, and the window
+    /// closes once the run has stopped cleanly.
+    fn on_close_event(&mut self) -> bool {
+        if self.processing && !self.close_after_stop {
+            warn!("Close requested while an update is running");
+            self.close_requested = true;
+            return false;
+        }
+        true
+    }
+
+    fn save(&mut self, storage: &mut dyn eframe::Storage) {
+        self.store_session();
+        eframe::set_value(storage, eframe::APP_KEY, &self.settings);
+    }
+}
+
+/// A speed input, outlined and written in red while what it holds is invalid.
+fn speed_field(ui: &mut egui::Ui, text: &mut String, invalid: bool) -> egui::Response {
+    let response = ui.add(
+        egui::TextEdit::singleline(text)
+            .desired_width(120.0)
+            .text_color_opt(invalid.then_some(egui::Color32::RED)),
+    );
+    if invalid {
+        ui.painter().rect_stroke(
+            response.rect.expand(1.0),
+            2.0,
+            egui::Stroke::new(1.5, egui::Color32::RED),
+        );
+    }
+    response
+}
+
+/// A list row naming a file. Returns whether it was clicked, which shows the file in the
+/// built-in viewer; the context menu opens it or its folder outside the program, or
+/// copies its full path whichever way the row shows it.
+fn file_row(ui: &mut egui::Ui, text: egui::RichText, path: &Path, language: Language) -> bool {
+    let t = |text: Text| text.get(language);
+    let response = ui
+        .add(egui::Label::new(text).sense(egui::Sense::click()))
+        .on_hover_text(t(Text::FileRowHint));
+    let mut action: Option<fn(&Path) -> io::Result<()>> = None;
+    let clicked = response.clicked();
+    response.context_menu(|ui| {
+        if ui.button(t(Text::OpenFile)).clicked() {
+            action = Some(open::open_file);
+            ui.close_menu();
+        }
+        if ui.button(t(Text::OpenContainingFolder)).clicked() {
+            action = Some(open::open_containing_folder);
+            ui.close_menu();
+        }
+        if ui.button(t(Text::CopyFullPath)).clicked() {
+            ui.output_mut(|output| output.copied_text = path.display().to_string());
+            ui.close_menu();
+        }
+    });
+    if let Some(action) = action {
+        if let Err(error) = action(path) {
+            error!("Failed to open {:?}: {}", path, error);
+        }
+    }
+    clicked
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use spindle_speed_manager::report::{RunResults, RunTotals};
+    use spindle_speed_manager::scan::scan_folders;
+    use spindle_speed_manager::timestamp::UtcDateTime;
+    use spindle_speed_manager::update::{FileResult, VERIFY_MISMATCH};
+
+    /// An app in the middle of a run, as `start_update_process` leaves it.
+    fn running_app() -> MainApp {
+        let mut app = MainApp::with_settings(Settings::default());
+        app.processing = true;
+        app.run_id = Some("run1".to_string());
+        app
+    }
+
+    #[test]
+    fn error_from_the_background_ends_the_run() {
+        let mut app = running_app();
+        let sender = app.error_sender.clone();
+        std::thread::spawn(move || {
+            sender
+                .send(SpindleSpeedUpdaterError::NoParentDirectory)
+                .unwrap()
+        })
+        .join()
+        .unwrap();
+
+        app.poll_background();
+
+        assert!(!app.processing);
+        assert_eq!(
+            app.error_message,
+            Some(SpindleSpeedUpdaterError::NoParentDirectory.to_string())
+        );
+    }
+
+    #[test]
+    fn completion_from_the_background_ends_the_run() {
+        let mut app = running_app();
+        let report = RunReport::new(
+            "run1".to_string(),
+            "12,000 RPM".to_string(),
+            Vec::new(),
+            (UtcDateTime::now(), Instant::now()),
+            RunResults::default(),
+        );
+        let sender = app.completion_sender.clone();
+        std::thread::spawn(move || sender.send(report).unwrap())
+            .join()
+            .unwrap();
+
+        app.poll_background();
+
+        assert!(!app.processing);
+        assert!(app.error_message.is_none());
+        assert!(app.run_summary.is_some());
+        assert_eq!(app.last_run.map(|run| run.run_id), Some("run1".to_string()));
+    }
+
+    #[test]
+    fn completion_queues_a_notice_only_when_notifications_are_on() {
+        for notify in [false, true] {
+            let mut app = running_app();
+            app.settings.notify_on_completion = notify;
+            let report = RunReport::new(
+                "run1".to_string(),
+                "12,000 RPM".to_string(),
+                Vec::new(),
+                (UtcDateTime::now(), Instant::now()),
+                RunResults::default(),
+            );
+            app.completion_sender.send(report).unwrap();
+
+            app.poll_background();
+
+            let expected = notify.then(|| "Done: 0 changed, 0 failed".to_string());
+            assert_eq!(app.completion_notice, expected);
+        }
+    }
+
+    #[test]
+    fn completion_notice_follows_the_window_language() {
+        let mut app = running_app();
+        app.settings.notify_on_completion = true;
+        app.settings.language = Language::Spanish;
+        let report = RunReport::new(
+            "run1".to_string(),
+            "12.000 RPM".to_string(),
+            Vec::new(),
+            (UtcDateTime::now(), Instant::now()),
+            RunResults::default(),
+        );
+        app.completion_sender.send(report).unwrap();
+
+        app.poll_background();
+
+        assert_eq!(
+            app.completion_notice.as_deref(),
+            Some("Terminado: 0 cambiados, 0 con error")
+        );
+    }
+
+    #[test]
+    fn last_speed_mode_and_its_inputs_are_restored() {
+        let settings = Settings {
+            speed_mode: SpeedMode::SurfaceSpeed,
+            last_speed: Some(9000),
+            last_percent: Some(50.0),
+            last_surface_speed: Some(200.0),
+            last_diameter: Some(50.0),
+            ..Settings::default()
+        };
+
+        let app = MainApp::with_settings(settings);
+
+        assert_eq!(app.settings.speed_mode, SpeedMode::SurfaceSpeed);
+        assert_eq!(app.spindle_speed_input, "9000");
+        assert_eq!(app.percent_input, "50");
+        assert_eq!(app.surface_speed_input, "200");
+        assert_eq!(app.diameter_input, "50");
+        assert_eq!(app.validated_spindle_speed, Some(1273));
+    }
+
+    #[test]
+    fn restored_inputs_that_no_longer_give_a_valid_speed_are_dropped() {
+        let settings = Settings {
+            speed_mode: SpeedMode::Percent,
+            last_speed: Some(30000),
+            last_percent: Some(150.0),
+            last_surface_speed: Some(200.0),
+            last_diameter: Some(50.0),
+            ..Settings::default()
+        };
+
+        let app = MainApp::with_settings(settings);
+
+        assert!(app.spindle_speed_input.is_empty());
+        assert!(app.percent_input.is_empty());
+        assert_eq!(app.surface_speed_input, "200");
+        assert_eq!(app.validated_spindle_speed, None);
+    }
+
+    #[tokio::test]
+    async fn loading_a_profile_switches_folders_and_checks_its_fingerprint() {
+        let job = tempfile::tempdir().unwrap();
+        let mut app = MainApp::with_settings(Settings::default());
+        app.settings.last_folders = vec![job.path().to_path_buf()];
+        app.settings.last_speed = Some(9000);
+        app.settings.check_folder_fingerprint = true;
+        app.settings.sentinel_file = "JOB-41.txt".to_string();
+        app.settings.save_profile("Job 41");
+        app.settings = Settings {
+            profiles: std::mem::take(&mut app.settings.profiles),
+            ..Settings::default()
+        };
+
+        app.load_profile("Job 41");
+
+        assert_eq!(app.folders, vec![job.path().to_path_buf()]);
+        assert_eq!(app.spindle_speed_input, "9000");
+        assert_eq!(app.settings.active_profile.as_deref(), Some("Job 41"));
+        let mismatch = app.fingerprint_mismatch().unwrap();
+        assert!(mismatch.contains("JOB-41.txt"), "{}", mismatch);
+
+        std::fs::write(job.path().join("JOB-41.txt"), "").unwrap();
+        assert_eq!(app.fingerprint_mismatch(), None);
+    }
+
+    #[tokio::test]
+    async fn self_check_reads_the_project_spindle_letter() {
+        let job = tempfile::tempdir().unwrap();
+        std::fs::write(job.path().join("part.tap"), "G21\nN20 P1000 M3\nM30\n").unwrap();
+        let mut app = MainApp::with_settings(Settings::default());
+        app.project_rules = ProjectRules {
+            spindle_letter: 'P',
+            ..ProjectRules::default()
+        };
+        (app.file_cache, _) =
+            scan_folders(&[job.path().to_path_buf()], false, &app.project_rules).unwrap();
+
+        app.start_self_check();
+        while app.self_checking {
+            tokio::task::yield_now().await;
+            app.poll_background();
+        }
+
+        assert_eq!(app.self_check_warning, None);
+    }
+
+    #[tokio::test]
+    async fn retry_rereads_a_file_that_failed_verification() {
+        let job = tempfile::tempdir().unwrap();
+        let path = job.path().join("part.tap");
+        std::fs::write(&path, "S1000 M3\n").unwrap();
+        let folders = vec![job.path().to_path_buf()];
+        let mut app = MainApp::with_settings(Settings::default());
+        app.settings.run_history_file = Some(job.path().join("runs.csv"));
+        (app.file_cache, _) = scan_folders(&folders, false, &app.project_rules).unwrap();
+        // The run wrote the new speed, then the read-back didn't match.
+        std::fs::write(&path, "S12000 M3\n").unwrap();
+        let failed = FileResult {
+            path: path.clone(),
+            root: job.path().to_path_buf(),
+            outcome: FileOutcome::Error(VERIFY_MISMATCH.to_string()),
+        };
+        let mut totals = RunTotals::default();
+        totals.add(&failed);
+        app.last_run = Some(RunReport::new(
+            "run1".to_string(),
+            "12,000 RPM".to_string(),
+            folders.clone(),
+            (UtcDateTime::now(), Instant::now()),
+            RunResults {
+                files: vec![failed],
+                totals,
+                results_file: None,
+            },
+        ));
+        app.last_run_setup = Some(RunSetup {
+            plan: SpeedPlan::Uniform(12000),
+            options: RunOptions::default(),
+            target: "12,000 RPM".to_string(),
+            folders,
+        });
+
+        app.retry_failed();
+        while app.processing {
+            tokio::task::yield_now().await;
+            app.poll_background();
+        }
+
+        let report = app.last_run.unwrap();
+        assert_eq!(report.files.len(), 1);
+        assert_eq!(report.files[0].outcome, FileOutcome::Unchanged);
+        assert_eq!(report.totals.failed, 0);
+    }
+}
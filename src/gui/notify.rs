@@ -0,0 +1,29 @@
+//! Desktop notifications, with the platform's notification sound, for runs that finish
+//! while the window is in the background.
+
+use notify_rust::Notification;
+use tracing::warn;
+
+/// A sound every desktop has: a freedesktop sound theme name, or the system default.
+#[cfg(not(any(windows, target_os = "macos")))]
+const SOUND: &str = "complete";
+#[cfg(windows)]
+const SOUND: &str = "Default";
+#[cfg(target_os = "macos")]
+const SOUND: &str = "default";
+
+/// Shows `body` under `summary` from a thread of its own, since the notification service
+/// may take a moment to answer. A desktop without one only gets a log line.
+pub fn notify(summary: &str, body: &str) {
+    let mut notification = Notification::new();
+    notification
+        .appname(super::WINDOW_TITLE)
+        .summary(summary)
+        .body(body)
+        .sound_name(SOUND);
+    std::thread::spawn(move || {
+        if let Err(error) = notification.show() {
+            warn!("Couldn't show a desktop notification: {}", error);
+        }
+    });
+}
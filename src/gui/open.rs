@@ -0,0 +1,37 @@
+//! Opens files and folders with the platform's default application.
+
+use std::io;
+use std::path::Path;
+use std::process::Command;
+
+/// Opens `path` in the application registered for its type.
+pub fn open_file(path: &Path) -> io::Result<()> {
+    #[cfg(windows)]
+    let mut command = Command::new("explorer");
+    #[cfg(target_os = "macos")]
+    let mut command = Command::new("open");
+    #[cfg(not(any(windows, target_os = "macos")))]
+    let mut command = Command::new("xdg-open");
+
+    command.arg(path).spawn().map(drop)
+}
+
+/// Opens the folder holding `path` in the file manager, selecting the file where the
+/// platform supports it.
+pub fn open_containing_folder(path: &Path) -> io::Result<()> {
+    #[cfg(windows)]
+    {
+        let mut select = std::ffi::OsString::from("/select,");
+        select.push(path);
+        Command::new("explorer").arg(select).spawn().map(drop)
+    }
+    #[cfg(target_os = "macos")]
+    {
+        Command::new("open").arg("-R").arg(path).spawn().map(drop)
+    }
+    #[cfg(not(any(windows, target_os = "macos")))]
+    {
+        let folder = path.parent().unwrap_or(path);
+        Command::new("xdg-open").arg(folder).spawn().map(drop)
+    }
+}
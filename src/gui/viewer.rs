@@ -0,0 +1,28 @@
+//! Read-only view of one program, with the lines the updater would look at marked.
+
+use spindle_speed_manager::encoding::read_program;
+use spindle_speed_manager::gcode::{spindle_lines, SpindleTarget};
+use std::path::{Path, PathBuf};
+
+pub struct FileView {
+    pub path: PathBuf,
+    /// The file's lines, or why it couldn't be read.
+    pub lines: Result<Vec<String>, String>,
+    /// Indices into `lines` that carry a spindle word for the current target.
+    pub spindle_lines: Vec<usize>,
+}
+
+pub async fn load_file_view(path: &Path, target: SpindleTarget, letter: char) -> FileView {
+    match read_program(path).await {
+        Ok((content, _)) => FileView {
+            path: path.to_path_buf(),
+            spindle_lines: spindle_lines(&content, target, letter),
+            lines: Ok(content.lines().map(str::to_string).collect()),
+        },
+        Err(error) => FileView {
+            path: path.to_path_buf(),
+            lines: Err(error.to_string()),
+            spindle_lines: Vec::new(),
+        },
+    }
+}
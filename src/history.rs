@@ -0,0 +1,244 @@
+//! `runs.csv`: one row per finished run, kept across restarts as a record of who changed
+//! what and when. Separate from the log, which is for troubleshooting. Runs that write
+//! their per-file results out in chunks put them in `run-<run ID>.csv` next to it.
+
+use crate::report::RunReport;
+use crate::scan::executable_folder;
+use crate::settings::PathDisplay;
+use crate::update::{FileOutcome, FileResult};
+use crate::SpindleSpeedUpdaterError;
+use std::collections::hash_map::RandomState;
+use std::hash::{BuildHasher, Hasher};
+use std::io;
+use std::path::{Path, PathBuf};
+use tokio::io::AsyncWriteExt;
+
+pub const HISTORY_FILE_NAME: &str = "runs.csv";
+
+const HEADER: &str =
+    "started_at_utc,run_id,operator,folders,target,changed,unchanged,skipped,errors,change_hash\n";
+
+const RESULTS_HEADER: &str = "path,root,outcome,detail\n";
+
+/// `configured`, or `runs.csv` next to the executable.
+pub fn history_path(configured: Option<&Path>) -> Result<PathBuf, SpindleSpeedUpdaterError> {
+    match configured {
+        Some(path) => Ok(path.to_path_buf()),
+        None => Ok(executable_folder()?.join(HISTORY_FILE_NAME)),
+    }
+}
+
+/// The account the program runs under, which is who the shop means by operator.
+fn operator() -> String {
+    std::env::var("USERNAME")
+        .or_else(|_| std::env::var("USER"))
+        .unwrap_or_default()
+}
+
+/// Where run `run_id` writes its per-file results, next to `history_file`.
+pub fn results_path(history_file: &Path, run_id: &str) -> PathBuf {
+    history_file.with_file_name(format!("run-{}.csv", run_id))
+}
+
+/// One CSV row for `report`, newline included. Several folders share one field,
+/// separated by `;`.
+pub fn history_row(report: &RunReport) -> String {
+    let totals = &report.totals;
+    let folders: Vec<String> = report
+        .folders
+        .iter()
+        .map(|folder| folder.display().to_string())
+        .collect();
+    let fields = [
+        report.started_at.clone(),
+        report.run_id.clone(),
+        operator(),
+        folders.join(";"),
+        report.target.clone(),
+        totals.changed.to_string(),
+        totals.unchanged.to_string(),
+        totals.skipped_files().to_string(),
+        totals.failed.to_string(),
+        report.change_hash.clone(),
+    ];
+    csv_row(&fields)
+}
+
+/// One CSV row for a file's result, its path shown as `path_display` asks. A changed
+/// file's detail is its spindle lines as `old -> new`, separated by `;` when there are
+/// several.
+pub fn result_row(result: &FileResult, path_display: PathDisplay) -> String {
+    let detail = match &result.outcome {
+        FileOutcome::Changed(lines) => lines
+            .iter()
+            .map(|line| format!("{} -> {}", line.old.trim(), line.new.trim()))
+            .collect::<Vec<_>>()
+            .join(";"),
+        FileOutcome::Unchanged => String::new(),
+        FileOutcome::Skipped(reason) => reason.to_string(),
+        FileOutcome::Error(error) => error.clone(),
+    };
+    csv_row(&[
+        path_display.show(&result.path, Some(&result.root)),
+        result.root.display().to_string(),
+        result.outcome.label().to_lowercase(),
+        detail,
+    ])
+}
+
+pub fn csv_row(fields: &[String]) -> String {
+    let quoted: Vec<String> = fields.iter().map(|field| csv_field(field)).collect();
+    format!("{}\n", quoted.join(","))
+}
+
+/// Quotes fields that contain a separator, a quote or a line break.
+fn csv_field(field: &str) -> String {
+    if field.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", field.replace('"', "\"\""))
+    } else {
+        field.to_string()
+    }
+}
+
+/// Appends the row for `report` to `path`. Whichever process creates the file writes the
+/// header with its row before any other can append, and every row goes out in a single
+/// append, so instances finishing at the same time neither repeat the header, put it
+/// after a row, nor interleave their rows.
+pub async fn append_run(path: &Path, report: &RunReport) -> io::Result<()> {
+    append_rows(path, HEADER, &history_row(report)).await
+}
+
+/// Appends rows made by [`result_row`] to a run's results file, starting it if needed.
+pub async fn append_results(path: &Path, rows: &str) -> io::Result<()> {
+    append_rows(path, RESULTS_HEADER, rows).await
+}
+
+async fn append_rows(path: &Path, header: &str, rows: &str) -> io::Result<()> {
+    let existing = tokio::fs::OpenOptions::new().append(true).open(path).await;
+    let mut file = match existing {
+        Ok(file) => file,
+        Err(error) if error.kind() == io::ErrorKind::NotFound => {
+            if start_file(path, &format!("{}{}", header, rows)).await? {
+                return Ok(());
+            }
+            tokio::fs::OpenOptions::new()
+                .append(true)
+                .open(path)
+                .await?
+        }
+        Err(error) => return Err(error),
+    };
+    file.write_all(rows.as_bytes()).await?;
+    file.flush().await
+}
+
+/// Creates `path` holding `contents`, or returns `false` when another process created it
+/// first. The file is written under a name of its own and linked into place, so nobody
+/// can append to it before its header is there.
+async fn start_file(path: &Path, contents: &str) -> io::Result<bool> {
+    let mut temp_name = std::ffi::OsString::from(".");
+    temp_name.push(path.file_name().unwrap_or_default());
+    temp_name.push(format!(".{}-{:016x}.tmp", std::process::id(), unique()));
+    let temp_path = path.with_file_name(temp_name);
+
+    tokio::fs::write(&temp_path, contents).await?;
+    let linked = tokio::fs::hard_link(&temp_path, path).await;
+    let _ = tokio::fs::remove_file(&temp_path).await;
+    match linked {
+        Ok(()) => Ok(true),
+        Err(error) if error.kind() == io::ErrorKind::AlreadyExists => Ok(false),
+        // Without hard links, e.g. on FAT, creating the file in place is the best left.
+        Err(_) => {
+            let created = tokio::fs::OpenOptions::new()
+                .append(true)
+                .create_new(true)
+                .open(path)
+                .await;
+            let mut file = match created {
+                Ok(file) => file,
+                Err(error) if error.kind() == io::ErrorKind::AlreadyExists => return Ok(false),
+                Err(error) => return Err(error),
+            };
+            file.write_all(contents.as_bytes()).await?;
+            file.flush().await?;
+            Ok(true)
+        }
+    }
+}
+
+/// A different number on every call, for naming temporary files.
+fn unique() -> u64 {
+    RandomState::new().build_hasher().finish()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::report::RunResults;
+    use crate::timestamp::UtcDateTime;
+    use std::time::Instant;
+
+    fn report(run_id: &str) -> RunReport {
+        RunReport::new(
+            run_id.to_string(),
+            "12,000 RPM".to_string(),
+            vec![PathBuf::from("jobs")],
+            (UtcDateTime::now(), Instant::now()),
+            RunResults::default(),
+        )
+    }
+
+    #[tokio::test]
+    async fn header_is_written_once_when_appending_to_an_existing_file() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join(HISTORY_FILE_NAME);
+
+        append_run(&path, &report("run1")).await.unwrap();
+        append_run(&path, &report("run2")).await.unwrap();
+
+        let history = std::fs::read_to_string(&path).unwrap();
+        assert!(history.starts_with(HEADER));
+        assert_eq!(history.matches(HEADER).count(), 1);
+        let run_ids: Vec<&str> = history
+            .lines()
+            .skip(1)
+            .map(|row| row.split(',').nth(1).unwrap())
+            .collect();
+        assert_eq!(run_ids, ["run1", "run2"]);
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+    async fn concurrent_appends_leave_one_header_and_whole_rows() {
+        let dir = tempfile::tempdir().unwrap();
+        // Long enough that a row written in pieces would show up interleaved.
+        let first = csv_row(&["a.tap".to_string(), "a".repeat(10_000)]);
+        let second = csv_row(&["b.tap".to_string(), "b".repeat(10_000)]);
+        for attempt in 0..20 {
+            let path = dir.path().join(format!("run-{}.csv", attempt));
+
+            let (a, b) = tokio::join!(
+                append_rows(&path, RESULTS_HEADER, &first),
+                append_rows(&path, RESULTS_HEADER, &second)
+            );
+            a.unwrap();
+            b.unwrap();
+
+            let written = std::fs::read_to_string(&path).unwrap();
+            let mut rows = written.strip_prefix(RESULTS_HEADER).unwrap().to_string();
+            assert!(!rows.contains(RESULTS_HEADER));
+            for row in [&first, &second] {
+                rows = rows.replacen(row.as_str(), "", 1);
+            }
+            assert_eq!(rows, "");
+        }
+    }
+
+    #[test]
+    fn fields_with_separators_quotes_or_line_breaks_are_quoted() {
+        assert_eq!(csv_field("12000 RPM"), "12000 RPM");
+        assert_eq!(csv_field("12,000 RPM"), "\"12,000 RPM\"");
+        assert_eq!(csv_field("say \"S0\""), "\"say \"\"S0\"\"\"");
+        assert_eq!(csv_field("first\nsecond"), "\"first\nsecond\"");
+        assert_eq!(csv_field("first\r\nsecond"), "\"first\r\nsecond\"");
+    }
+}
@@ -0,0 +1,33 @@
+//! Bulk spindle speed updates for CNC programs. Everything but the window lives here:
+//! rewriting program text, finding the files, the update run with its backups and
+//! reports, and the command-line mode, so the binary only has to start one or the other.
+
+pub mod audit;
+pub mod backup;
+pub mod cli;
+pub mod compare;
+pub mod encoding;
+pub mod error;
+pub mod fingerprint;
+pub mod gcode;
+pub mod history;
+pub mod logging;
+pub mod mapping;
+pub mod patch;
+pub mod preview;
+pub mod progress;
+pub mod project;
+pub mod report;
+pub mod retry;
+pub mod safety;
+pub mod scan;
+pub mod self_test;
+pub mod settings;
+pub mod speed;
+pub mod storage;
+pub mod strings;
+pub mod summary;
+pub mod timestamp;
+pub mod update;
+
+pub use error::SpindleSpeedUpdaterError;
@@ -0,0 +1,38 @@
+//! Log output. Messages are `tracing` events, so the ones written while a run works on a
+//! file carry the `run` and `file` spans they happened in:
+//!
+//! ```text
+//! 2024-06-01T09:30:00.000000Z  INFO run{id=20240601-093000-1f2e}:file{path=/jobs/a.tap}: spindle_speed_manager: Updated the spindle speed
+//! ```
+//!
+//! `RUST_LOG` filters them like the `log` records of the libraries underneath. With
+//! `SPINDLE_LOG_FORMAT=json` every line is a JSON object instead, the spans' fields under
+//! `"spans"`, for feeding a log collector.
+
+use std::io::IsTerminal;
+use tracing_subscriber::EnvFilter;
+
+/// Environment variable choosing the log format; `json` is the only other one.
+pub const LOG_FORMAT_VAR: &str = "SPINDLE_LOG_FORMAT";
+
+/// Writes the log to standard error, defaulting to `info`.
+pub fn start_logging() {
+    let json = std::env::var(LOG_FORMAT_VAR).is_ok_and(|format| format == "json");
+    let filter = EnvFilter::try_from_default_env().unwrap_or_else(|_| EnvFilter::new("info"));
+    let logger = tracing_subscriber::fmt()
+        .with_env_filter(filter)
+        .with_writer(std::io::stderr)
+        .with_ansi(std::io::stderr().is_terminal());
+    let started = if json {
+        logger
+            .json()
+            .with_current_span(false)
+            .with_span_list(true)
+            .try_init()
+    } else {
+        logger.try_init()
+    };
+    if let Err(error) = started {
+        eprintln!("Logging not set up: {}", error);
+    }
+}
@@ -0,0 +1,262 @@
+//! Per-file target speeds read from a `filename,rpm` CSV, or given by file name patterns
+//! in the project config, as alternatives to applying one speed to every file.
+
+use crate::scan::FileInfo;
+use crate::SpindleSpeedUpdaterError;
+use std::collections::HashMap;
+use std::ops::RangeInclusive;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+/// Which speed each file should get.
+#[derive(Clone, Debug)]
+pub enum SpeedPlan {
+    Uniform(u32),
+    /// Keyed by lowercase file name; files not listed are skipped.
+    PerFile(Arc<HashMap<String, u32>>),
+    /// The first rule whose pattern matches; files matching none are skipped.
+    Rules(Arc<Vec<SpeedRule>>),
+}
+
+impl SpeedPlan {
+    pub fn speed_for(&self, path: &Path) -> Option<u32> {
+        match self {
+            SpeedPlan::Uniform(speed) => Some(*speed),
+            SpeedPlan::PerFile(speeds) => speeds.get(&mapping_key(path)?).copied(),
+            SpeedPlan::Rules(rules) => rule_for(rules, path).map(|(_, rule)| rule.speed),
+        }
+    }
+}
+
+/// A `[[speed_rules]]` entry of the project config: files whose name matches `pattern`
+/// get `speed`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct SpeedRule {
+    pub pattern: String,
+    pub speed: u32,
+}
+
+impl SpeedRule {
+    /// Matched against the file name only, ignoring case, like a mapping. `*` stands for
+    /// any run of characters and `?` for exactly one.
+    pub fn matches(&self, path: &Path) -> bool {
+        mapping_key(path).is_some_and(|name| {
+            let pattern: Vec<char> = self.pattern.to_lowercase().chars().collect();
+            let name: Vec<char> = name.chars().collect();
+            wildcard_match(&pattern, &name)
+        })
+    }
+}
+
+/// The first rule matching `path` and its index, so rules listed earlier win.
+pub fn rule_for<'a>(rules: &'a [SpeedRule], path: &Path) -> Option<(usize, &'a SpeedRule)> {
+    rules
+        .iter()
+        .enumerate()
+        .find(|(_, rule)| rule.matches(path))
+}
+
+/// Glob matching of `*` and `?`, going back to the last `*` on a mismatch.
+fn wildcard_match(pattern: &[char], name: &[char]) -> bool {
+    let (mut p, mut n) = (0, 0);
+    let mut last_star: Option<(usize, usize)> = None;
+    while n < name.len() {
+        match pattern.get(p) {
+            Some('*') => {
+                last_star = Some((p, n));
+                p += 1;
+            }
+            Some(&c) if c == '?' || c == name[n] => {
+                p += 1;
+                n += 1;
+            }
+            _ => match last_star {
+                Some((star, matched)) => {
+                    p = star + 1;
+                    n = matched + 1;
+                    last_star = Some((star, matched + 1));
+                }
+                None => return false,
+            },
+        }
+    }
+    pattern[p..].iter().all(|&c| c == '*')
+}
+
+#[derive(Clone, Debug)]
+pub struct SpeedMapping {
+    pub source: PathBuf,
+    pub speeds: Arc<HashMap<String, u32>>,
+}
+
+/// Files are matched on their name only, case-insensitively, so a mapping written on
+/// one machine still applies when the job folder is moved.
+pub fn mapping_key(path: &Path) -> Option<String> {
+    path.file_name()
+        .map(|name| name.to_string_lossy().to_lowercase())
+}
+
+/// Parses `filename,rpm` lines. Blank lines and `#` comments are ignored, and a first
+/// line whose speed column isn't a number is taken as a header. Every speed must lie in
+/// `speed_range`.
+pub fn parse_mapping(
+    content: &str,
+    speed_range: &RangeInclusive<u32>,
+) -> Result<HashMap<String, u32>, SpindleSpeedUpdaterError> {
+    let mut speeds = HashMap::new();
+
+    for (index, line) in content.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+
+        let (name, rpm) = line.split_once(',').ok_or_else(|| {
+            SpindleSpeedUpdaterError::InvalidMapping(format!(
+                "line {}: expected 'filename,rpm'",
+                index + 1
+            ))
+        })?;
+        let name = name.trim().trim_matches('"');
+        let rpm = rpm.trim().trim_matches('"');
+
+        let speed = match rpm.parse::<u32>() {
+            Ok(speed) => speed,
+            Err(_) if index == 0 => continue,
+            Err(_) => {
+                return Err(SpindleSpeedUpdaterError::InvalidMapping(format!(
+                    "line {}: '{}' is not a spindle speed",
+                    index + 1,
+                    rpm
+                )))
+            }
+        };
+        if !speed_range.contains(&speed) {
+            return Err(SpindleSpeedUpdaterError::InvalidMapping(format!(
+                "line {}: {} RPM is not between {} and {} RPM",
+                index + 1,
+                speed,
+                speed_range.start(),
+                speed_range.end()
+            )));
+        }
+
+        speeds.insert(name.to_lowercase(), speed);
+    }
+
+    Ok(speeds)
+}
+
+pub fn load_mapping(
+    path: &Path,
+    speed_range: &RangeInclusive<u32>,
+) -> Result<SpeedMapping, SpindleSpeedUpdaterError> {
+    let content = std::fs::read_to_string(path).map_err(SpindleSpeedUpdaterError::file_io(path))?;
+    Ok(SpeedMapping {
+        source: path.to_path_buf(),
+        speeds: Arc::new(parse_mapping(&content, speed_range)?),
+    })
+}
+
+/// Differences between a mapping and the files actually found.
+#[derive(Clone, Debug, Default)]
+pub struct MappingReport {
+    /// Listed in the mapping but not present in the folder.
+    pub missing_files: Vec<String>,
+    /// Present in the folder but not listed, so they will be skipped.
+    pub unmapped_files: Vec<PathBuf>,
+}
+
+pub fn compare_mapping(
+    speeds: &HashMap<String, u32>,
+    file_cache: &HashMap<PathBuf, FileInfo>,
+) -> MappingReport {
+    let found: HashMap<String, &PathBuf> = file_cache
+        .keys()
+        .filter_map(|path| Some((mapping_key(path)?, path)))
+        .collect();
+
+    let mut missing_files: Vec<String> = speeds
+        .keys()
+        .filter(|name| !found.contains_key(*name))
+        .cloned()
+        .collect();
+    missing_files.sort();
+
+    let mut unmapped_files: Vec<PathBuf> = file_cache
+        .keys()
+        .filter(|path| mapping_key(path).is_none_or(|key| !speeds.contains_key(&key)))
+        .cloned()
+        .collect();
+    unmapped_files.sort();
+
+    MappingReport {
+        missing_files,
+        unmapped_files,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::SystemTime;
+
+    const CSV: &str = "filename,rpm\n# roughing\nPART1.tap,8000\n\"part2.tap\", 9000\n\n";
+
+    fn file_cache(paths: &[&str]) -> HashMap<PathBuf, FileInfo> {
+        paths
+            .iter()
+            .map(|path| {
+                let info = FileInfo {
+                    path: PathBuf::from(path),
+                    root: PathBuf::from("jobs"),
+                    last_modified: SystemTime::UNIX_EPOCH,
+                    size: 1,
+                };
+                (PathBuf::from(path), info)
+            })
+            .collect()
+    }
+
+    #[test]
+    fn first_matching_rule_wins_and_unmatched_files_get_none() {
+        let plan = SpeedPlan::Rules(Arc::new(vec![
+            SpeedRule {
+                pattern: "rough_*".to_string(),
+                speed: 8000,
+            },
+            SpeedRule {
+                pattern: "*_FINE.tap".to_string(),
+                speed: 9000,
+            },
+        ]));
+        assert_eq!(plan.speed_for(Path::new("jobs/ROUGH_FINE.tap")), Some(8000));
+        assert_eq!(plan.speed_for(Path::new("jobs/part_fine.TAP")), Some(9000));
+        assert_eq!(plan.speed_for(Path::new("jobs/finish.tap")), None);
+    }
+
+    #[test]
+    fn mapping_looks_files_up_by_name_ignoring_case() {
+        let speeds = parse_mapping(CSV, &(1..=24000)).unwrap();
+        let plan = SpeedPlan::PerFile(Arc::new(speeds));
+        assert_eq!(plan.speed_for(Path::new("jobs/part1.TAP")), Some(8000));
+        assert_eq!(plan.speed_for(Path::new("other/PART2.tap")), Some(9000));
+        assert_eq!(plan.speed_for(Path::new("jobs/part3.tap")), None);
+    }
+
+    #[test]
+    fn mapping_refuses_bad_rows() {
+        let range = 1..=24000;
+        assert!(parse_mapping("part1.tap", &range).is_err());
+        assert!(parse_mapping("part1.tap,8000\npart2.tap,fast", &range).is_err());
+        assert!(parse_mapping("part1.tap,30000", &range).is_err());
+    }
+
+    #[test]
+    fn comparison_lists_missing_and_unmapped_files() {
+        let speeds = parse_mapping(CSV, &(1..=24000)).unwrap();
+        let report = compare_mapping(&speeds, &file_cache(&["jobs/Part1.tap", "jobs/part3.tap"]));
+        assert_eq!(report.missing_files, vec!["part2.tap".to_string()]);
+        assert_eq!(report.unmapped_files, vec![PathBuf::from("jobs/part3.tap")]);
+    }
+}
@@ -0,0 +1,213 @@
+//! Unified diffs of what a run would change, for review or for applying with `patch -p1`
+//! or `git apply` from the selected folder.
+
+use crate::encoding::read_program;
+use crate::gcode::{rewrite_content, UpdateOptions};
+use crate::mapping::SpeedPlan;
+use crate::preview::{PreviewEntry, PreviewOutcome};
+use crate::scan::FileInfo;
+use std::collections::HashMap;
+use std::io;
+use std::path::{Component, Path, PathBuf};
+
+/// Unchanged lines shown around each changed line.
+const CONTEXT_LINES: usize = 3;
+
+enum Op<'a> {
+    Context(&'a str),
+    Removed(&'a str),
+    /// With the line ending it is written with.
+    Added(String),
+}
+
+/// Diff of every file the dry pass marked as changing, with paths relative to the
+/// folder each file was found under. The files are read and rewritten again, so the
+/// patch shows what a run started now would write.
+pub async fn build_patch(
+    plan: &SpeedPlan,
+    options: &UpdateOptions,
+    entries: &[PreviewEntry],
+    file_cache: &HashMap<PathBuf, FileInfo>,
+) -> io::Result<String> {
+    let mut patch = String::new();
+    for entry in entries {
+        if entry.outcome != PreviewOutcome::Change {
+            continue;
+        }
+        let Some(speed) = plan.speed_for(&entry.path) else {
+            continue;
+        };
+        let (original, _) = read_program(&entry.path).await?;
+        let Ok(update) = rewrite_content(&original, speed, options) else {
+            continue;
+        };
+        let relative = file_cache
+            .get(&entry.path)
+            .and_then(|info| entry.path.strip_prefix(&info.root).ok())
+            .unwrap_or(&entry.path);
+        patch.push_str(&file_diff(relative, &original, &update.content));
+    }
+    Ok(patch)
+}
+
+/// Unified diff of one file; empty when the lines are the same. Line endings are taken
+/// from the original, so a CRLF program only shows its spindle lines as changed.
+pub fn file_diff(relative_path: &Path, original: &str, updated: &str) -> String {
+    let ops = diff_ops(original, updated);
+    let changes: Vec<usize> = ops
+        .iter()
+        .enumerate()
+        .filter(|(_, op)| !matches!(op, Op::Context(_)))
+        .map(|(index, _)| index)
+        .collect();
+    if changes.is_empty() {
+        return String::new();
+    }
+
+    let path = diff_path(relative_path);
+    let mut diff = format!("--- a/{}\n+++ b/{}\n", path, path);
+    let mut hunk_start = 0;
+    while hunk_start < changes.len() {
+        let mut hunk_end = hunk_start;
+        while hunk_end + 1 < changes.len()
+            && changes[hunk_end + 1] - changes[hunk_end] <= 2 * CONTEXT_LINES + 1
+        {
+            hunk_end += 1;
+        }
+        let first = changes[hunk_start].saturating_sub(CONTEXT_LINES);
+        let last = (changes[hunk_end] + CONTEXT_LINES + 1).min(ops.len());
+        push_hunk(&mut diff, &ops, first..last);
+        hunk_start = hunk_end + 1;
+    }
+    diff
+}
+
+fn push_hunk(diff: &mut String, ops: &[Op], range: std::ops::Range<usize>) {
+    let old_before = ops[..range.start]
+        .iter()
+        .filter(|op| !matches!(op, Op::Added(_)))
+        .count();
+    let new_before = ops[..range.start]
+        .iter()
+        .filter(|op| !matches!(op, Op::Removed(_)))
+        .count();
+    let hunk = &ops[range];
+    let old_count = hunk.iter().filter(|op| !matches!(op, Op::Added(_))).count();
+    let new_count = hunk
+        .iter()
+        .filter(|op| !matches!(op, Op::Removed(_)))
+        .count();
+    // An empty side is numbered by the line before it.
+    let start = |before: usize, count: usize| if count == 0 { before } else { before + 1 };
+    diff.push_str(&format!(
+        "@@ -{},{} +{},{} @@\n",
+        start(old_before, old_count),
+        old_count,
+        start(new_before, new_count),
+        new_count
+    ));
+    for op in hunk {
+        let (prefix, line) = match op {
+            Op::Context(line) => (' ', *line),
+            Op::Removed(line) => ('-', *line),
+            Op::Added(line) => ('+', line.as_str()),
+        };
+        diff.push(prefix);
+        diff.push_str(line);
+        if !line.ends_with('\n') {
+            diff.push_str("\n\\ No newline at end of file\n");
+        }
+    }
+}
+
+/// Lines of `original` against those of `updated`. A rewrite only replaces lines one for
+/// one or inserts a line, so walking both in step finds the same edit a full diff would.
+fn diff_ops<'a>(original: &'a str, updated: &str) -> Vec<Op<'a>> {
+    let old: Vec<&str> = original.split_inclusive('\n').collect();
+    let new: Vec<&str> = updated.lines().collect();
+    let default_ending = old.first().map_or("\n", |line| line_ending(line));
+    let default_ending = if default_ending.is_empty() {
+        "\n"
+    } else {
+        default_ending
+    };
+    let mut ops = Vec::with_capacity(old.len() + 1);
+    let (mut i, mut j) = (0, 0);
+    while i < old.len() || j < new.len() {
+        if i < old.len() && j < new.len() && line_text(old[i]) == new[j] {
+            ops.push(Op::Context(old[i]));
+            i += 1;
+            j += 1;
+        } else if j < new.len() && new.len() - j > old.len() - i {
+            if i == old.len() {
+                // Appended after a last line without an ending: that line gains one and
+                // the new line becomes the unterminated last line.
+                match ops.pop() {
+                    Some(Op::Context(line)) if line_ending(line).is_empty() => {
+                        ops.push(Op::Removed(line));
+                        ops.push(Op::Added(format!("{}{}", line, default_ending)));
+                    }
+                    Some(Op::Added(line)) if !line.ends_with('\n') => {
+                        ops.push(Op::Added(format!("{}{}", line, default_ending)));
+                    }
+                    Some(op) => ops.push(op),
+                    None => {}
+                }
+                let ending = if original.ends_with('\n') || original.is_empty() {
+                    default_ending
+                } else {
+                    ""
+                };
+                ops.push(Op::Added(format!("{}{}", new[j], ending)));
+            } else {
+                ops.push(Op::Added(format!("{}{}", new[j], default_ending)));
+            }
+            j += 1;
+        } else if j < new.len() {
+            ops.push(Op::Removed(old[i]));
+            ops.push(Op::Added(format!("{}{}", new[j], line_ending(old[i]))));
+            i += 1;
+            j += 1;
+        } else {
+            ops.push(Op::Removed(old[i]));
+            i += 1;
+        }
+    }
+    ops
+}
+
+fn line_text(line: &str) -> &str {
+    line.strip_suffix('\n')
+        .map(|line| line.strip_suffix('\r').unwrap_or(line))
+        .unwrap_or(line)
+}
+
+fn line_ending(line: &str) -> &str {
+    &line[line_text(line).len()..]
+}
+
+/// `/`-separated, as diff tools expect on every platform.
+fn diff_path(path: &Path) -> String {
+    path.components()
+        .filter_map(|component| match component {
+            Component::Normal(part) => Some(part.to_string_lossy()),
+            _ => None,
+        })
+        .collect::<Vec<_>>()
+        .join("/")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn crlf_program_diffs_only_its_spindle_line() {
+        let original = "G21\r\nS1000 M3\r\nM30\r\n";
+        let update = rewrite_content(original, 12000, &UpdateOptions::default()).unwrap();
+        assert_eq!(
+            file_diff(Path::new("part.nc"), original, &update.content),
+            "--- a/part.nc\n+++ b/part.nc\n@@ -1,3 +1,3 @@\n G21\r\n-S1000 M3\r\n+S12000 M3\r\n M30\r\n"
+        );
+    }
+}
@@ -0,0 +1,285 @@
+//! Dry pass over the cached files: computes what an update would do without writing.
+
+use crate::encoding::read_program;
+use crate::gcode::{current_speed_value, rewrite_content, LineChange, SkipReason, UpdateOptions};
+use crate::mapping::SpeedPlan;
+use crate::scan::FileInfo;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum PreviewOutcome {
+    Change,
+    Unchanged,
+    /// Zero bytes or only whitespace.
+    Empty,
+    NoSpindleCommand,
+    /// The spindle word uses a notation the parser won't rewrite, e.g. `S1.0E3`, or a
+    /// required insertion marker is missing.
+    Unsupported(String),
+    NotInMapping,
+    NoMatchingRule,
+    /// Skipped by the "match current speed" filter.
+    NotMatched,
+    Error(String),
+}
+
+#[derive(Clone, Debug)]
+pub struct PreviewEntry {
+    pub path: PathBuf,
+    pub original_lines: usize,
+    pub new_lines: usize,
+    pub original_bytes: usize,
+    pub new_bytes: usize,
+    /// The update adds a line on purpose, so a line count one higher is expected.
+    pub inserted: bool,
+    /// The spindle lines before and after, when the file would change.
+    pub line_changes: Vec<LineChange>,
+    /// Modification time when the file was read, to spot changes before the update.
+    pub modified: Option<SystemTime>,
+    /// Length in bytes at the same time; 0 when `modified` is `None`.
+    pub size: u64,
+    /// The speed the file runs at now, when it has a plain-number spindle word.
+    pub current_speed: Option<f64>,
+    /// The speed the update would set.
+    pub new_speed: Option<u32>,
+    pub outcome: PreviewOutcome,
+}
+
+impl PreviewEntry {
+    /// A rewrite should never change the number of lines; when it does, the parser
+    /// most likely misread the file.
+    pub fn line_count_mismatch(&self) -> bool {
+        let expected = self.original_lines + usize::from(self.inserted);
+        self.outcome == PreviewOutcome::Change && self.new_lines != expected
+    }
+}
+
+pub async fn preview_file(
+    file_path: &Path,
+    spindle_speed: u32,
+    options: &UpdateOptions,
+) -> PreviewEntry {
+    let mut entry = PreviewEntry {
+        path: file_path.to_path_buf(),
+        original_lines: 0,
+        new_lines: 0,
+        original_bytes: 0,
+        new_bytes: 0,
+        inserted: false,
+        line_changes: Vec::new(),
+        modified: None,
+        size: 0,
+        current_speed: None,
+        new_speed: Some(spindle_speed),
+        outcome: PreviewOutcome::Unchanged,
+    };
+
+    if let Ok(metadata) = tokio::fs::metadata(file_path).await {
+        entry.modified = metadata.modified().ok();
+        entry.size = metadata.len();
+    }
+
+    let content = match read_program(file_path).await {
+        Ok((content, _)) => content,
+        Err(error) => {
+            entry.outcome = PreviewOutcome::Error(error.to_string());
+            return entry;
+        }
+    };
+    entry.original_lines = content.lines().count();
+    entry.original_bytes = content.len();
+    entry.new_lines = entry.original_lines;
+    entry.new_bytes = entry.original_bytes;
+    entry.current_speed = current_speed_value(&content, options);
+
+    match rewrite_content(&content, spindle_speed, options) {
+        Ok(update) if update.changed => {
+            entry.new_speed = Some(update.speed);
+            entry.new_lines = update.content.lines().count();
+            entry.new_bytes = update.content.len();
+            entry.inserted = update.inserted;
+            entry.line_changes = update.line_changes;
+            entry.outcome = PreviewOutcome::Change;
+        }
+        Ok(update) => entry.new_speed = Some(update.speed),
+        Err(SkipReason::Empty) => entry.outcome = PreviewOutcome::Empty,
+        Err(SkipReason::NoSpindleCommand) => entry.outcome = PreviewOutcome::NoSpindleCommand,
+        Err(SkipReason::SpeedMismatch { .. }) => entry.outcome = PreviewOutcome::NotMatched,
+        Err(reason @ SkipReason::StructureChanged { .. }) => {
+            entry.outcome = PreviewOutcome::Error(reason.to_string())
+        }
+        Err(reason) => entry.outcome = PreviewOutcome::Unsupported(reason.to_string()),
+    }
+
+    entry
+}
+
+/// Previews every cached file, sorted by path.
+pub async fn build_preview(
+    plan: &SpeedPlan,
+    options: UpdateOptions,
+    file_cache: &HashMap<PathBuf, FileInfo>,
+) -> Vec<PreviewEntry> {
+    let mut paths: Vec<&PathBuf> = file_cache.keys().collect();
+    paths.sort();
+
+    let mut entries = Vec::with_capacity(paths.len());
+    for path in paths {
+        let entry = match plan.speed_for(path) {
+            Some(spindle_speed) => preview_file(path, spindle_speed, &options).await,
+            None => PreviewEntry {
+                path: path.clone(),
+                original_lines: 0,
+                new_lines: 0,
+                original_bytes: 0,
+                new_bytes: 0,
+                inserted: false,
+                line_changes: Vec::new(),
+                modified: None,
+                size: 0,
+                current_speed: None,
+                new_speed: None,
+                outcome: match plan {
+                    SpeedPlan::Rules(_) => PreviewOutcome::NoMatchingRule,
+                    _ => PreviewOutcome::NotInMapping,
+                },
+            },
+        };
+        entries.push(entry);
+    }
+    entries
+}
+
+/// Counts for the pre-flight safety gate: how many targeted files a run would fail on.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct PreflightSummary {
+    /// Files the run would touch, i.e. excluding empty files and those a mapping or the
+    /// current-speed filter skips.
+    pub targeted: usize,
+    /// Files whose content the run would rewrite.
+    pub changing: usize,
+    /// Files with no usable S command, unreadable files and rewrites that change line count.
+    pub failed: usize,
+}
+
+/// Files a run would touch: not empty, and neither a mapping, the speed rules nor the
+/// current-speed filter leaves them out.
+fn is_targeted(entry: &PreviewEntry) -> bool {
+    !matches!(
+        entry.outcome,
+        PreviewOutcome::Empty
+            | PreviewOutcome::NotInMapping
+            | PreviewOutcome::NoMatchingRule
+            | PreviewOutcome::NotMatched
+    )
+}
+
+impl PreflightSummary {
+    pub fn from_entries(entries: &[PreviewEntry]) -> Self {
+        let mut summary = Self::default();
+        for entry in entries.iter().filter(|entry| is_targeted(entry)) {
+            summary.targeted += 1;
+            summary.changing += usize::from(entry.outcome == PreviewOutcome::Change);
+            let failed = matches!(
+                entry.outcome,
+                PreviewOutcome::NoSpindleCommand
+                    | PreviewOutcome::Unsupported(_)
+                    | PreviewOutcome::Error(_)
+            ) || entry.line_count_mismatch();
+            summary.failed += usize::from(failed);
+        }
+        summary
+    }
+
+    pub fn failure_percent(&self) -> f64 {
+        if self.targeted == 0 {
+            0.0
+        } else {
+            self.failed as f64 * 100.0 / self.targeted as f64
+        }
+    }
+
+    pub fn exceeds(&self, max_failure_percent: u8) -> bool {
+        self.failure_percent() > f64::from(max_failure_percent)
+    }
+}
+
+/// When a dry pass is clean enough for a run to start without confirmation. Unreadable
+/// files, unsupported notation and rewrites that change the line count never are.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct CleanCriteria {
+    /// Every changing file has exactly one spindle line rewritten.
+    pub single_change_per_file: bool,
+    /// Files already at the target speed are fine.
+    pub allow_unchanged: bool,
+    /// Files without an S command are fine, and left alone.
+    pub allow_no_spindle_command: bool,
+    /// An inserted S line counts as a clean change.
+    pub allow_inserted: bool,
+}
+
+impl Default for CleanCriteria {
+    fn default() -> Self {
+        Self {
+            single_change_per_file: true,
+            allow_unchanged: true,
+            allow_no_spindle_command: false,
+            allow_inserted: false,
+        }
+    }
+}
+
+impl CleanCriteria {
+    /// Why the dry pass in `entries` isn't clean, naming the first targeted file that
+    /// breaks a criterion, or `None` when it is. A pass that changes nothing isn't clean.
+    pub fn unclean_reason(&self, entries: &[PreviewEntry]) -> Option<String> {
+        let problem = entries
+            .iter()
+            .filter(|entry| is_targeted(entry))
+            .find_map(|entry| {
+                self.problem(entry)
+                    .map(|problem| format!("{}: {}", entry.path.display(), problem))
+            });
+        if problem.is_some() {
+            return problem;
+        }
+        (!entries
+            .iter()
+            .any(|entry| entry.outcome == PreviewOutcome::Change))
+        .then(|| "no file would change".to_string())
+    }
+
+    fn problem(&self, entry: &PreviewEntry) -> Option<String> {
+        match &entry.outcome {
+            PreviewOutcome::Change if entry.line_count_mismatch() => {
+                Some("line count would change".to_string())
+            }
+            PreviewOutcome::Change if entry.inserted && !self.allow_inserted => {
+                Some("an S command would be inserted".to_string())
+            }
+            PreviewOutcome::Change
+                if self.single_change_per_file && entry.line_changes.len() != 1 =>
+            {
+                Some(format!(
+                    "{} spindle lines would change",
+                    entry.line_changes.len()
+                ))
+            }
+            PreviewOutcome::Change => None,
+            PreviewOutcome::Unchanged if self.allow_unchanged => None,
+            PreviewOutcome::Unchanged => Some("already at the target speed".to_string()),
+            PreviewOutcome::NoSpindleCommand if self.allow_no_spindle_command => None,
+            PreviewOutcome::NoSpindleCommand => Some("no S command".to_string()),
+            PreviewOutcome::Unsupported(reason) => Some(reason.clone()),
+            PreviewOutcome::Error(error) => Some(format!("error: {}", error)),
+            PreviewOutcome::Empty
+            | PreviewOutcome::NotInMapping
+            | PreviewOutcome::NoMatchingRule
+            | PreviewOutcome::NotMatched => None,
+        }
+    }
+}
@@ -0,0 +1,87 @@
+//! Run progress shared between the background task and the UI.
+
+use crate::SpindleSpeedUpdaterError;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Mutex;
+
+/// The counters are atomics so the UI can read them on every repaint, and tasks can
+/// bump them, without contending on a lock. Only the rarely read current file name
+/// sits behind a mutex.
+#[derive(Debug, Default)]
+pub struct Progress {
+    processed: AtomicUsize,
+    total: AtomicUsize,
+    current_file: Mutex<Option<PathBuf>>,
+}
+
+impl Progress {
+    pub fn start(&self, total: usize) {
+        self.processed.store(0, Ordering::Relaxed);
+        self.total.store(total, Ordering::Relaxed);
+    }
+
+    /// Counts one more finished file and returns the new processed count.
+    pub fn increment(&self) -> usize {
+        self.processed.fetch_add(1, Ordering::Relaxed) + 1
+    }
+
+    /// `(processed, total)`.
+    pub fn counts(&self) -> (usize, usize) {
+        (
+            self.processed.load(Ordering::Relaxed),
+            self.total.load(Ordering::Relaxed),
+        )
+    }
+
+    pub fn set_current_file(&self, path: Option<&Path>) -> Result<(), SpindleSpeedUpdaterError> {
+        let mut current_file = self
+            .current_file
+            .lock()
+            .map_err(|e| SpindleSpeedUpdaterError::ProgressLockFailure(e.to_string()))?;
+        *current_file = path.map(Path::to_path_buf);
+        Ok(())
+    }
+
+    pub fn current_file(&self) -> Option<PathBuf> {
+        self.current_file.lock().ok()?.clone()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Arc;
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+    async fn concurrent_increments_are_all_counted() {
+        let progress = Arc::new(Progress::default());
+        progress.start(8000);
+
+        let tasks: Vec<_> = (0..8)
+            .map(|_| {
+                let progress = Arc::clone(&progress);
+                tokio::spawn(async move {
+                    for _ in 0..1000 {
+                        progress.increment();
+                        tokio::task::yield_now().await;
+                    }
+                })
+            })
+            .collect();
+        for task in tasks {
+            task.await.unwrap();
+        }
+
+        assert_eq!(progress.counts(), (8000, 8000));
+    }
+
+    #[test]
+    fn start_resets_the_processed_count() {
+        let progress = Progress::default();
+        progress.start(2);
+        progress.increment();
+        progress.start(3);
+        assert_eq!(progress.counts(), (0, 3));
+    }
+}
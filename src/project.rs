@@ -0,0 +1,242 @@
+//! Per-job rules read from a `.spindle-updater.toml` in a processed folder, overriding
+//! the built-in defaults for as long as that folder is selected.
+//!
+//! ```toml
+//! min_rpm = 500
+//! max_rpm = 18000
+//! extensions = ["tap", "nc"]
+//! spindle_on = "M03"
+//! spindle_letter = "S"
+//!
+//! # Tried in order; the first pattern matching a file name gives its speed.
+//! [[speed_rules]]
+//! pattern = "ROUGH_*"
+//! rpm = 8000
+//!
+//! [[speed_rules]]
+//! pattern = "FINISH_*"
+//! rpm = 12000
+//! ```
+
+use crate::mapping::SpeedRule;
+use crate::speed::{MAX_SPINDLE_SPEED, MIN_SPINDLE_SPEED};
+use crate::SpindleSpeedUpdaterError;
+use serde::Deserialize;
+use std::ops::RangeInclusive;
+use std::path::{Path, PathBuf};
+use tracing::{info, warn};
+
+pub const CONFIG_FILE_NAME: &str = ".spindle-updater.toml";
+
+/// The file as written; every key is optional.
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+struct ProjectConfig {
+    min_rpm: Option<u32>,
+    max_rpm: Option<u32>,
+    /// File extensions to process, without the dot.
+    extensions: Option<Vec<String>>,
+    /// M-code written after the speed when a spindle line is reformatted or inserted.
+    spindle_on: Option<String>,
+    /// Address letter of the spindle word, for controllers that don't use `S`.
+    spindle_letter: Option<String>,
+    speed_rules: Vec<SpeedRuleConfig>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct SpeedRuleConfig {
+    pattern: String,
+    rpm: u32,
+}
+
+/// The rules in effect for a session, defaults filled in.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ProjectRules {
+    pub speed_range: RangeInclusive<u32>,
+    pub extensions: Vec<String>,
+    pub spindle_on: String,
+    pub spindle_letter: char,
+    /// Per-file speeds by file name pattern, in the order they are tried.
+    pub speed_rules: Vec<SpeedRule>,
+}
+
+impl Default for ProjectRules {
+    fn default() -> Self {
+        Self {
+            speed_range: MIN_SPINDLE_SPEED..=MAX_SPINDLE_SPEED,
+            extensions: vec!["tap".to_string()],
+            spindle_on: "M3".to_string(),
+            spindle_letter: 'S',
+            speed_rules: Vec::new(),
+        }
+    }
+}
+
+impl ProjectRules {
+    /// Whether `path` has one of the configured extensions, ignoring case.
+    pub fn matches_extension(&self, path: &Path) -> bool {
+        path.extension().is_some_and(|ext| {
+            self.extensions
+                .iter()
+                .any(|wanted| ext.eq_ignore_ascii_case(wanted.as_str()))
+        })
+    }
+}
+
+impl ProjectConfig {
+    fn into_rules(self) -> Result<ProjectRules, String> {
+        let defaults = ProjectRules::default();
+        let min = self.min_rpm.unwrap_or(*defaults.speed_range.start());
+        let max = self.max_rpm.unwrap_or(*defaults.speed_range.end());
+        if min == 0 || min > max {
+            return Err(format!("invalid RPM limits {}..{}", min, max));
+        }
+
+        let extensions = match self.extensions {
+            Some(extensions) => {
+                let extensions: Vec<String> = extensions
+                    .into_iter()
+                    .map(|ext| ext.trim_start_matches('.').to_string())
+                    .filter(|ext| !ext.is_empty())
+                    .collect();
+                if extensions.is_empty() {
+                    return Err("extensions is empty".to_string());
+                }
+                extensions
+            }
+            None => defaults.extensions,
+        };
+
+        let spindle_on = match self.spindle_on {
+            Some(code) if is_m_code(&code) => code,
+            Some(code) => return Err(format!("\"{}\" is not an M-code", code)),
+            None => defaults.spindle_on,
+        };
+
+        let spindle_letter = match self.spindle_letter {
+            Some(letter) => parse_spindle_letter(&letter)?,
+            None => defaults.spindle_letter,
+        };
+
+        let mut speed_rules = Vec::with_capacity(self.speed_rules.len());
+        for (index, rule) in self.speed_rules.into_iter().enumerate() {
+            if rule.pattern.trim().is_empty() {
+                return Err(format!("speed rule {} has an empty pattern", index + 1));
+            }
+            if !(min..=max).contains(&rule.rpm) {
+                return Err(format!(
+                    "speed rule {} ({}): {} RPM is not between {} and {} RPM",
+                    index + 1,
+                    rule.pattern,
+                    rule.rpm,
+                    min,
+                    max
+                ));
+            }
+            speed_rules.push(SpeedRule {
+                pattern: rule.pattern.trim().to_string(),
+                speed: rule.rpm,
+            });
+        }
+
+        Ok(ProjectRules {
+            speed_range: min..=max,
+            extensions,
+            spindle_on,
+            spindle_letter,
+            speed_rules,
+        })
+    }
+}
+
+/// A single ASCII letter, upper-cased. Block numbers, program numbers and G- and M-codes
+/// are refused: rewriting those would wreck a program.
+fn parse_spindle_letter(letter: &str) -> Result<char, String> {
+    let mut chars = letter.chars();
+    match (chars.next(), chars.next()) {
+        (Some(c), None) if c.is_ascii_alphabetic() => {
+            let c = c.to_ascii_uppercase();
+            if matches!(c, 'G' | 'M' | 'N' | 'O') {
+                Err(format!("\"{}\" can't be the spindle letter", c))
+            } else {
+                Ok(c)
+            }
+        }
+        _ => Err(format!(
+            "spindle_letter \"{}\" is not a single letter",
+            letter
+        )),
+    }
+}
+
+fn is_m_code(code: &str) -> bool {
+    code.strip_prefix('M')
+        .is_some_and(|number| !number.is_empty() && number.chars().all(|c| c.is_ascii_digit()))
+}
+
+/// Which project config, if any, the current rules came from.
+#[derive(Clone, Debug, Default)]
+pub enum ProjectConfigStatus {
+    #[default]
+    None,
+    Loaded(PathBuf),
+    /// Present but unusable; the defaults apply instead.
+    Invalid(PathBuf, String),
+}
+
+/// Loads the config of the first folder that has one. Configs in later folders are
+/// ignored, and a missing or malformed file falls back to the defaults.
+pub fn load_project_rules(folders: &[PathBuf]) -> (ProjectRules, ProjectConfigStatus) {
+    let Some(path) = folders
+        .iter()
+        .map(|folder| folder.join(CONFIG_FILE_NAME))
+        .find(|path| path.is_file())
+    else {
+        return (ProjectRules::default(), ProjectConfigStatus::None);
+    };
+
+    match read_rules(&path) {
+        Ok(rules) => {
+            info!("Loaded project config {:?}: {:?}", path, rules);
+            (rules, ProjectConfigStatus::Loaded(path))
+        }
+        Err(error) => {
+            warn!("Ignoring project config {:?}: {}", path, error);
+            (
+                ProjectRules::default(),
+                ProjectConfigStatus::Invalid(path, error),
+            )
+        }
+    }
+}
+
+fn read_rules(path: &Path) -> Result<ProjectRules, String> {
+    let content = std::fs::read_to_string(path)
+        .map_err(|error| SpindleSpeedUpdaterError::file_io(path)(error).to_string())?;
+    // The parser's own message spans several lines with a source excerpt; keep it to one
+    // line for the status label.
+    let config: ProjectConfig = toml::from_str(&content).map_err(|error| match error.span() {
+        Some(span) => format!(
+            "line {}: {}",
+            content[..span.start].matches('\n').count() + 1,
+            error.message()
+        ),
+        None => error.message().to_string(),
+    })?;
+    config.into_rules()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn spindle_letter_is_one_letter_that_isnt_an_address_of_its_own() {
+        assert_eq!(parse_spindle_letter("S"), Ok('S'));
+        assert_eq!(parse_spindle_letter("p"), Ok('P'));
+        for refused in ["", "SS", "1", "G", "m", "N", "O"] {
+            assert!(parse_spindle_letter(refused).is_err(), "{:?}", refused);
+        }
+    }
+}
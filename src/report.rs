@@ -0,0 +1,245 @@
+//! The record of a finished run, which every summary and export reads from.
+
+use crate::fingerprint::change_hash;
+use crate::gcode::SkipReason;
+use crate::timestamp::UtcDateTime;
+use crate::update::{FileOutcome, FileResult};
+use serde::Serialize;
+use std::collections::{BTreeMap, HashSet};
+use std::path::PathBuf;
+use std::time::Instant;
+
+/// Per-outcome counts of a run, kept up to date as files finish so the summaries stay
+/// exact when a chunked run lets go of the individual results.
+#[derive(Clone, Debug, Default, Serialize)]
+pub struct RunTotals {
+    pub changed: usize,
+    pub unchanged: usize,
+    pub failed: usize,
+    /// Skipped files per [`crate::gcode::SkipReason::label`].
+    pub skipped: BTreeMap<&'static str, usize>,
+    /// Changed and total files per root folder.
+    #[serde(skip)]
+    pub by_root: BTreeMap<PathBuf, (usize, usize)>,
+}
+
+impl RunTotals {
+    pub fn add(&mut self, result: &FileResult) {
+        let changed = match &result.outcome {
+            FileOutcome::Changed(_) => {
+                self.changed += 1;
+                true
+            }
+            FileOutcome::Unchanged => {
+                self.unchanged += 1;
+                false
+            }
+            FileOutcome::Skipped(reason) => {
+                *self.skipped.entry(reason.label()).or_default() += 1;
+                false
+            }
+            FileOutcome::Error(_) => {
+                self.failed += 1;
+                false
+            }
+        };
+        // Looked up first so each root is cloned once, not once per file.
+        if !self.by_root.contains_key(&result.root) {
+            self.by_root.insert(result.root.clone(), (0, 0));
+        }
+        if let Some((root_changed, root_total)) = self.by_root.get_mut(&result.root) {
+            *root_changed += usize::from(changed);
+            *root_total += 1;
+        }
+    }
+
+    pub fn skipped_files(&self) -> usize {
+        self.skipped.values().sum()
+    }
+
+    pub fn processed(&self) -> usize {
+        self.changed + self.unchanged + self.failed + self.skipped_files()
+    }
+
+    /// Adds the counts of another run.
+    fn merge(&mut self, other: &RunTotals) {
+        self.changed += other.changed;
+        self.unchanged += other.unchanged;
+        self.failed += other.failed;
+        for (label, count) in &other.skipped {
+            *self.skipped.entry(label).or_default() += count;
+        }
+        for (root, (changed, total)) in &other.by_root {
+            let (root_changed, root_total) = self.by_root.entry(root.clone()).or_default();
+            *root_changed += changed;
+            *root_total += total;
+        }
+    }
+}
+
+/// What a run hands back: the per-file results it kept and the totals over all files.
+#[derive(Debug, Default)]
+pub struct RunResults {
+    /// Every file, or only the changed and failed ones when the results were written
+    /// out in chunks to `results_file`.
+    pub files: Vec<FileResult>,
+    pub totals: RunTotals,
+    pub results_file: Option<PathBuf>,
+}
+
+#[derive(Clone, Debug, Serialize)]
+pub struct RunReport {
+    pub run_id: String,
+    /// What the files were updated to, e.g. "12,000 RPM" or the mapping file.
+    pub target: String,
+    pub folders: Vec<PathBuf>,
+    /// UTC, `YYYYMMDD-HHMMSS`.
+    pub started_at: String,
+    pub duration_ms: u64,
+    /// See [`change_hash`].
+    pub change_hash: String,
+    /// See [`RunResults::files`].
+    pub files: Vec<FileResult>,
+    pub totals: RunTotals,
+    /// The CSV holding every file's result, for a run that wrote them out in chunks.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub results_file: Option<PathBuf>,
+}
+
+impl RunReport {
+    /// `started` pairs the wall-clock start for the record with the instant the
+    /// duration is measured from.
+    pub fn new(
+        run_id: String,
+        target: String,
+        folders: Vec<PathBuf>,
+        started: (UtcDateTime, Instant),
+        results: RunResults,
+    ) -> Self {
+        Self {
+            run_id,
+            target,
+            folders,
+            started_at: started.0.compact(),
+            duration_ms: started.1.elapsed().as_millis() as u64,
+            change_hash: change_hash(&results.files),
+            files: results.files,
+            totals: results.totals,
+            results_file: results.results_file,
+        }
+    }
+
+    /// Whether `files` lists every file, i.e. the results weren't written out in chunks.
+    pub fn has_every_file(&self) -> bool {
+        self.results_file.is_none()
+    }
+
+    pub fn failures(&self) -> impl Iterator<Item = &FileResult> {
+        self.files
+            .iter()
+            .filter(|result| matches!(result.outcome, FileOutcome::Error(_)))
+    }
+
+    /// The files worth running again: the failed ones and the ones another program had
+    /// open.
+    pub fn retryable(&self) -> impl Iterator<Item = &FileResult> {
+        self.files
+            .iter()
+            .filter(|result| is_retryable(&result.outcome))
+    }
+
+    /// Folds in `retry`, a run over files [`RunReport::retryable`] listed here which kept
+    /// every file's result: their new outcomes replace the old ones, and the totals,
+    /// duration and change hash take them in. Files a cancelled retry didn't reach keep
+    /// their old outcomes. The run keeps its own ID and results file.
+    pub fn merge_retry(&mut self, retry: RunReport) {
+        let retried: HashSet<&PathBuf> = retry.files.iter().map(|result| &result.path).collect();
+        let totals = &mut self.totals;
+        self.files.retain(|result| {
+            let replace = is_retryable(&result.outcome) && retried.contains(&result.path);
+            if replace {
+                match &result.outcome {
+                    FileOutcome::Skipped(reason) => {
+                        if let Some(count) = totals.skipped.get_mut(reason.label()) {
+                            *count -= 1;
+                            if *count == 0 {
+                                totals.skipped.remove(reason.label());
+                            }
+                        }
+                    }
+                    _ => totals.failed -= 1,
+                }
+                if let Some((_, root_total)) = totals.by_root.get_mut(&result.root) {
+                    *root_total -= 1;
+                }
+            }
+            !replace
+        });
+        self.totals.merge(&retry.totals);
+        self.files.extend(retry.files);
+        self.duration_ms += retry.duration_ms;
+        self.change_hash = change_hash(&self.files);
+    }
+
+    /// Files that came out differently from `previous`, in path order: a different
+    /// status, different spindle lines written, or present in only one of the runs.
+    pub fn changes_since(&self, previous: &RunReport) -> RunComparison {
+        let mut outcomes: BTreeMap<&PathBuf, (Option<String>, Option<String>)> = BTreeMap::new();
+        for file in &previous.files {
+            outcomes.entry(&file.path).or_default().0 = Some(outcome_summary(&file.outcome));
+        }
+        for file in &self.files {
+            outcomes.entry(&file.path).or_default().1 = Some(outcome_summary(&file.outcome));
+        }
+        RunComparison {
+            previous_run_id: previous.run_id.clone(),
+            previous_target: previous.target.clone(),
+            files: outcomes
+                .into_iter()
+                .filter(|(_, (before, after))| before != after)
+                .map(|(path, (before, after))| RunDifference {
+                    path: path.clone(),
+                    before,
+                    after,
+                })
+                .collect(),
+        }
+    }
+}
+
+/// How a run differed from the one before it on the same folders.
+#[derive(Clone, Debug)]
+pub struct RunComparison {
+    pub previous_run_id: String,
+    pub previous_target: String,
+    pub files: Vec<RunDifference>,
+}
+
+/// One file's outcome in the previous run and the new one; `None` when the run didn't
+/// include the file.
+#[derive(Clone, Debug)]
+pub struct RunDifference {
+    pub path: PathBuf,
+    pub before: Option<String>,
+    pub after: Option<String>,
+}
+
+/// "changed to S12000 M3", "already correct", "skipped: no S command" or "failed: …".
+fn outcome_summary(outcome: &FileOutcome) -> String {
+    match outcome {
+        FileOutcome::Changed(lines) => {
+            let written: Vec<&str> = lines.iter().map(|line| line.new.trim()).collect();
+            format!("changed to {}", written.join(", "))
+        }
+        FileOutcome::Unchanged => "already correct".to_string(),
+        FileOutcome::Skipped(reason) => format!("skipped: {}", reason),
+        FileOutcome::Error(error) => format!("failed: {}", error),
+    }
+}
+
+fn is_retryable(outcome: &FileOutcome) -> bool {
+    matches!(
+        outcome,
+        FileOutcome::Error(_) | FileOutcome::Skipped(SkipReason::InUse)
+    )
+}
@@ -0,0 +1,185 @@
+//! Bounded retries for IO that can fail transiently on network shares.
+
+use std::future::Future;
+use std::io;
+use std::thread;
+use std::time::Duration;
+
+const INITIAL_BACKOFF: Duration = Duration::from_millis(100);
+
+/// Whether an error is worth retrying. Missing files and permission problems won't go
+/// away by waiting, so only errors that look like a busy or flaky connection qualify.
+pub fn is_transient(error: &io::Error) -> bool {
+    if matches!(
+        error.kind(),
+        io::ErrorKind::Interrupted
+            | io::ErrorKind::WouldBlock
+            | io::ErrorKind::TimedOut
+            | io::ErrorKind::ResourceBusy
+            | io::ErrorKind::NetworkDown
+            | io::ErrorKind::ConnectionReset
+            | io::ErrorKind::ConnectionAborted
+            | io::ErrorKind::BrokenPipe
+    ) {
+        return true;
+    }
+
+    #[cfg(windows)]
+    {
+        // ERROR_NETWORK_BUSY, ERROR_UNEXP_NET_ERR, ERROR_NETNAME_DELETED, ERROR_SEM_TIMEOUT
+        const TRANSIENT_WINDOWS_ERRORS: [i32; 4] = [54, 59, 64, 121];
+        if let Some(code) = error.raw_os_error() {
+            return TRANSIENT_WINDOWS_ERRORS.contains(&code);
+        }
+    }
+
+    false
+}
+
+/// Whether an error means another program has the file open and won't share it, as a
+/// text editor or a drip feed does on Windows. Such a file is skipped rather than failed.
+pub fn is_in_use(error: &io::Error) -> bool {
+    #[cfg(windows)]
+    {
+        // ERROR_SHARING_VIOLATION, ERROR_LOCK_VIOLATION
+        const IN_USE_WINDOWS_ERRORS: [i32; 2] = [32, 33];
+        if let Some(code) = error.raw_os_error() {
+            return IN_USE_WINDOWS_ERRORS.contains(&code);
+        }
+    }
+
+    error.kind() == io::ErrorKind::ResourceBusy
+}
+
+/// Runs `operation`, retrying up to `retries` more times on transient errors with a
+/// doubling delay between attempts.
+pub async fn retry_io<T, F, Fut>(retries: u32, mut operation: F) -> io::Result<T>
+where
+    F: FnMut() -> Fut,
+    Fut: Future<Output = io::Result<T>>,
+{
+    let mut backoff = INITIAL_BACKOFF;
+    let mut attempt = 0;
+    loop {
+        match operation().await {
+            Err(error) if attempt < retries && is_transient(&error) => {
+                attempt += 1;
+                tracing::warn!(
+                    "Transient IO error ({}), retry {} of {} in {:?}",
+                    error,
+                    attempt,
+                    retries,
+                    backoff
+                );
+                tokio::time::sleep(backoff).await;
+                backoff *= 2;
+            }
+            result => return result,
+        }
+    }
+}
+
+/// Blocking counterpart of [`retry_io`], for callers without a tokio runtime.
+pub fn retry_io_blocking<T, F>(retries: u32, mut operation: F) -> io::Result<T>
+where
+    F: FnMut() -> io::Result<T>,
+{
+    let mut backoff = INITIAL_BACKOFF;
+    let mut attempt = 0;
+    loop {
+        match operation() {
+            Err(error) if attempt < retries && is_transient(&error) => {
+                attempt += 1;
+                tracing::warn!(
+                    "Transient IO error ({}), retry {} of {} in {:?}",
+                    error,
+                    attempt,
+                    retries,
+                    backoff
+                );
+                thread::sleep(backoff);
+                backoff *= 2;
+            }
+            result => return result,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::cell::Cell;
+
+    /// A file on a flaky share: the first `failures` reads time out, later ones succeed.
+    struct FlakyFile {
+        failures: u32,
+        attempts: Cell<u32>,
+        error: io::ErrorKind,
+    }
+
+    impl FlakyFile {
+        fn new(failures: u32, error: io::ErrorKind) -> Self {
+            Self {
+                failures,
+                attempts: Cell::new(0),
+                error,
+            }
+        }
+
+        fn read(&self) -> io::Result<String> {
+            self.attempts.set(self.attempts.get() + 1);
+            if self.attempts.get() <= self.failures {
+                Err(self.error.into())
+            } else {
+                Ok("S1000 M3".to_string())
+            }
+        }
+    }
+
+    /// The error writing a file that another program holds open, as Windows reports it
+    /// for an editor or a drip feed; a plain permission error isn't one.
+    #[test]
+    fn locked_file_counts_as_in_use() {
+        #[cfg(windows)]
+        let locked = io::Error::from_raw_os_error(32);
+        #[cfg(not(windows))]
+        let locked = io::Error::from(io::ErrorKind::ResourceBusy);
+        assert!(is_in_use(&locked));
+        assert!(!is_in_use(&io::Error::from(
+            io::ErrorKind::PermissionDenied
+        )));
+    }
+
+    #[tokio::test]
+    async fn read_failing_twice_succeeds_on_the_third_attempt() {
+        let file = FlakyFile::new(2, io::ErrorKind::TimedOut);
+        let content = retry_io(2, || async { file.read() }).await;
+        assert_eq!(content.unwrap(), "S1000 M3");
+        assert_eq!(file.attempts.get(), 3);
+    }
+
+    #[tokio::test]
+    async fn retries_stop_at_the_limit() {
+        let file = FlakyFile::new(2, io::ErrorKind::TimedOut);
+        let error = retry_io(1, || async { file.read() }).await.unwrap_err();
+        assert_eq!(error.kind(), io::ErrorKind::TimedOut);
+        assert_eq!(file.attempts.get(), 2);
+    }
+
+    #[tokio::test]
+    async fn permanent_errors_are_not_retried() {
+        for kind in [io::ErrorKind::NotFound, io::ErrorKind::PermissionDenied] {
+            let file = FlakyFile::new(2, kind);
+            let error = retry_io(3, || async { file.read() }).await.unwrap_err();
+            assert_eq!(error.kind(), kind);
+            assert_eq!(file.attempts.get(), 1);
+        }
+    }
+
+    #[test]
+    fn blocking_read_failing_twice_succeeds_on_the_third_attempt() {
+        let file = FlakyFile::new(2, io::ErrorKind::ResourceBusy);
+        assert_eq!(retry_io_blocking(2, || file.read()).unwrap(), "S1000 M3");
+        assert_eq!(file.attempts.get(), 3);
+    }
+}
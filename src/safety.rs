@@ -0,0 +1,327 @@
+//! What a dry pass can refuse a run for or warn about before it, worded for the
+//! confirmation dialog and the command line.
+
+use crate::gcode::percent_of;
+use crate::preview::{PreflightSummary, PreviewEntry, PreviewOutcome};
+use crate::speed::format_rpm;
+use crate::strings::{fill, Language, Text};
+use std::path::Path;
+
+pub fn preflight_warning(
+    summary: &PreflightSummary,
+    max_failure_percent: u8,
+    language: Language,
+) -> String {
+    fill(
+        Text::PreflightWarning.get(language),
+        &[
+            &summary.failed,
+            &summary.targeted,
+            &format!("{:.0}", summary.failure_percent()),
+            &max_failure_percent,
+        ],
+    )
+}
+
+/// Warns when most files with a readable current speed would drop below `percent` of
+/// it. A drop that large is more often a missing zero or the wrong units than intended.
+pub fn speed_drop_warning(
+    entries: &[PreviewEntry],
+    percent: u8,
+    language: Language,
+) -> Option<String> {
+    let compared: Vec<(&PreviewEntry, f64, u32)> = entries
+        .iter()
+        .filter(|entry| entry.outcome != PreviewOutcome::NotMatched)
+        .filter_map(|entry| Some((entry, entry.current_speed?, entry.new_speed?)))
+        .filter(|&(_, current, _)| current > 0.0)
+        .collect();
+    let drops: Vec<&(&PreviewEntry, f64, u32)> = compared
+        .iter()
+        .filter(|&&(_, current, new)| f64::from(new) < percent_of(current, f64::from(percent)))
+        .collect();
+    if drops.len() * 2 <= compared.len() {
+        return None;
+    }
+    let &&(example, current, new) = drops.first()?;
+    Some(fill(
+        Text::SpeedDropWarning.get(language),
+        &[
+            &drops.len(),
+            &compared.len(),
+            &percent,
+            &example
+                .path
+                .file_name()
+                .unwrap_or_default()
+                .to_string_lossy(),
+            &format_rpm(current.round() as u32),
+            &format_rpm(new),
+        ],
+    ))
+}
+
+/// Explains why a run is refused when the dry pass found more files to change than the
+/// cap allows, or `None` when there is no cap or the run stays within it.
+pub fn file_cap_refusal(
+    summary: &PreflightSummary,
+    max_files: Option<usize>,
+    language: Language,
+) -> Option<String> {
+    let max_files = max_files?;
+    (summary.changing > max_files).then(|| {
+        fill(
+            Text::FileCapRefusal.get(language),
+            &[&summary.changing, &max_files],
+        )
+    })
+}
+
+/// Files named in [`missing_spindle_refusal`]; the rest are only counted.
+pub const LISTED_MISSING_FILES: usize = 20;
+
+/// Refuses a strict run when the dry pass found targeted files without an S command,
+/// listing them, or `None` when every one has one.
+pub fn missing_spindle_refusal(
+    entries: &[PreviewEntry],
+    show_path: impl Fn(&Path) -> String,
+    language: Language,
+) -> Option<String> {
+    let missing: Vec<&PreviewEntry> = entries
+        .iter()
+        .filter(|entry| entry.outcome == PreviewOutcome::NoSpindleCommand)
+        .collect();
+    if missing.is_empty() {
+        return None;
+    }
+    let mut refusal = fill(Text::MissingSpindleRefusal.get(language), &[&missing.len()]);
+    for entry in missing.iter().take(LISTED_MISSING_FILES) {
+        refusal.push_str(&format!("\n{}", show_path(&entry.path)));
+    }
+    if missing.len() > LISTED_MISSING_FILES {
+        refusal.push('\n');
+        refusal.push_str(&fill(
+            Text::AndMore.get(language),
+            &[&(missing.len() - LISTED_MISSING_FILES)],
+        ));
+    }
+    Some(refusal)
+}
+
+/// Lists the files a self-check found unusable, or `None` when every file is fine.
+pub fn self_check_warning(
+    entries: &[PreviewEntry],
+    show_path: impl Fn(&Path) -> String,
+    language: Language,
+) -> Option<String> {
+    let problems: Vec<&PreviewEntry> = entries
+        .iter()
+        .filter(|entry| {
+            matches!(
+                entry.outcome,
+                PreviewOutcome::NoSpindleCommand
+                    | PreviewOutcome::Unsupported(_)
+                    | PreviewOutcome::Error(_)
+            )
+        })
+        .collect();
+    let first = problems.first()?;
+    Some(fill(
+        Text::SelfCheckWarning.get(language),
+        &[&problems.len(), &entries.len(), &show_path(&first.path)],
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::path::PathBuf;
+
+    fn entry(name: &str, outcome: PreviewOutcome) -> PreviewEntry {
+        PreviewEntry {
+            path: PathBuf::from(name),
+            original_lines: 3,
+            new_lines: 3,
+            original_bytes: 0,
+            new_bytes: 0,
+            inserted: false,
+            line_changes: Vec::new(),
+            modified: None,
+            size: 0,
+            current_speed: None,
+            new_speed: None,
+            outcome,
+        }
+    }
+
+    fn change(name: &str, current: Option<f64>, new: Option<u32>) -> PreviewEntry {
+        PreviewEntry {
+            current_speed: current,
+            new_speed: new,
+            ..entry(name, PreviewOutcome::Change)
+        }
+    }
+
+    /// `drops` files going from 10,000 to 1,000 and `holds` going from 10,000 to 9,000.
+    fn drops_and_holds(drops: usize, holds: usize) -> Vec<PreviewEntry> {
+        let drop = (0..drops)
+            .map(|index| change(&format!("drop{}.tap", index), Some(10000.0), Some(1000)));
+        let hold = (0..holds)
+            .map(|index| change(&format!("hold{}.tap", index), Some(10000.0), Some(9000)));
+        drop.chain(hold).collect()
+    }
+
+    #[test]
+    fn speed_drop_warns_only_past_half_the_files() {
+        assert_eq!(
+            speed_drop_warning(&drops_and_holds(2, 2), 50, Language::English),
+            None
+        );
+
+        let warning = speed_drop_warning(&drops_and_holds(3, 1), 50, Language::English).unwrap();
+        assert!(
+            warning.starts_with("3 of 4 files would drop below 50%"),
+            "{}",
+            warning
+        );
+        assert!(
+            warning.contains("drop0.tap: 10,000 RPM → 1,000 RPM"),
+            "{}",
+            warning
+        );
+    }
+
+    #[test]
+    fn speed_drop_ignores_files_without_a_current_speed() {
+        let mut entries = drops_and_holds(1, 0);
+        entries.push(change("zero.tap", Some(0.0), Some(9000)));
+        entries.push(change("unknown.tap", None, Some(9000)));
+        entries.push(change("zero-too.tap", Some(0.0), Some(9000)));
+
+        let warning = speed_drop_warning(&entries, 50, Language::English).unwrap();
+        assert!(warning.starts_with("1 of 1 files"), "{}", warning);
+    }
+
+    #[test]
+    fn speed_drop_leaves_out_files_the_current_speed_filter_skips() {
+        let mut entries = drops_and_holds(1, 1);
+        for name in ["other0.tap", "other1.tap"] {
+            entries.push(PreviewEntry {
+                outcome: PreviewOutcome::NotMatched,
+                ..change(name, Some(10000.0), Some(1000))
+            });
+        }
+
+        assert_eq!(speed_drop_warning(&entries, 50, Language::English), None);
+    }
+
+    #[test]
+    fn speed_drop_compares_only_mapped_files() {
+        let mut entries = drops_and_holds(2, 0);
+        for name in ["unlisted0.tap", "unlisted1.tap", "unlisted2.tap"] {
+            entries.push(PreviewEntry {
+                outcome: PreviewOutcome::NotInMapping,
+                ..change(name, Some(10000.0), None)
+            });
+        }
+
+        let warning = speed_drop_warning(&entries, 50, Language::English).unwrap();
+        assert!(warning.starts_with("2 of 2 files"), "{}", warning);
+    }
+
+    #[test]
+    fn file_cap_allows_exactly_the_limit() {
+        let at_limit = PreflightSummary {
+            targeted: 10,
+            changing: 5,
+            failed: 0,
+        };
+        assert_eq!(
+            file_cap_refusal(&at_limit, Some(5), Language::English),
+            None
+        );
+        assert_eq!(file_cap_refusal(&at_limit, None, Language::English), None);
+
+        let over = PreflightSummary {
+            changing: 6,
+            ..at_limit
+        };
+        let refusal = file_cap_refusal(&over, Some(5), Language::English).unwrap();
+        assert!(
+            refusal.starts_with("6 files would be modified"),
+            "{}",
+            refusal
+        );
+    }
+
+    #[test]
+    fn missing_spindle_refusal_lists_the_first_files_and_counts_the_rest() {
+        let mut entries: Vec<PreviewEntry> = (0..LISTED_MISSING_FILES + 3)
+            .map(|index| {
+                entry(
+                    &format!("part{:02}.tap", index),
+                    PreviewOutcome::NoSpindleCommand,
+                )
+            })
+            .collect();
+        entries.push(entry("fine.tap", PreviewOutcome::Change));
+
+        let refusal = missing_spindle_refusal(
+            &entries,
+            |path| path.display().to_string(),
+            Language::English,
+        );
+
+        let refusal = refusal.unwrap();
+        let lines: Vec<&str> = refusal.lines().collect();
+        assert!(
+            lines[0].starts_with("23 files have no S command"),
+            "{}",
+            lines[0]
+        );
+        assert_eq!(lines.len(), 1 + LISTED_MISSING_FILES + 1);
+        assert_eq!(lines[1], "part00.tap");
+        assert_eq!(lines[LISTED_MISSING_FILES], "part19.tap");
+        assert_eq!(lines.last(), Some(&"…and 3 more"));
+        assert!(!refusal.contains("fine.tap"));
+
+        let show_path = |path: &Path| path.display().to_string();
+        let refusal = missing_spindle_refusal(&entries, show_path, Language::Spanish).unwrap();
+        assert!(
+            refusal.starts_with("23 archivos no tienen comando S"),
+            "{}",
+            refusal
+        );
+        assert!(refusal.ends_with("\n…y 3 más"), "{}", refusal);
+    }
+
+    #[test]
+    fn missing_spindle_refusal_lists_every_file_up_to_the_limit() {
+        let entries: Vec<PreviewEntry> = (0..LISTED_MISSING_FILES)
+            .map(|index| {
+                entry(
+                    &format!("part{}.tap", index),
+                    PreviewOutcome::NoSpindleCommand,
+                )
+            })
+            .collect();
+
+        let refusal = missing_spindle_refusal(
+            &entries,
+            |path| path.display().to_string(),
+            Language::English,
+        );
+
+        let refusal = refusal.unwrap();
+        assert_eq!(refusal.lines().count(), 1 + LISTED_MISSING_FILES);
+        assert!(!refusal.contains("more"));
+        assert_eq!(
+            missing_spindle_refusal(
+                &entries[..0],
+                |path| path.display().to_string(),
+                Language::English
+            ),
+            None
+        );
+    }
+}
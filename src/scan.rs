@@ -0,0 +1,433 @@
+//! Finding the programs to work on, by scanning the selected folders or from a list of
+//! files, as the [`FileInfo`] cache the rest of a run works from.
+
+use crate::preview::PreviewEntry;
+use crate::project::ProjectRules;
+use crate::strings::{fill, Language, Text};
+use crate::SpindleSpeedUpdaterError;
+use std::collections::{HashMap, HashSet};
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::{Duration, Instant, SystemTime};
+use tracing::{info, warn};
+use walkdir::WalkDir;
+
+#[derive(Clone)]
+pub struct FileInfo {
+    pub path: PathBuf,
+    /// The selected folder the file was found under.
+    pub root: PathBuf,
+    pub last_modified: std::time::SystemTime,
+    /// Length in bytes, from the same look at the file as `last_modified`.
+    pub size: u64,
+}
+
+impl FileInfo {
+    /// Whether `metadata` still shows the file as recorded. The size catches most writes
+    /// that land within the same tick of a coarse modification-time clock.
+    pub fn matches(&self, metadata: &std::fs::Metadata) -> bool {
+        metadata.len() == self.size
+            && metadata
+                .modified()
+                .is_ok_and(|modified| modified == self.last_modified)
+    }
+}
+
+pub fn executable_folder() -> Result<PathBuf, SpindleSpeedUpdaterError> {
+    let executable_path = std::env::current_exe().map_err(SpindleSpeedUpdaterError::Io)?;
+    executable_path
+        .parent()
+        .map(Path::to_path_buf)
+        .ok_or(SpindleSpeedUpdaterError::NoParentDirectory)
+}
+
+/// The folders' canonical paths, sorted, so the same selection made through a different
+/// path or in a different order compares equal.
+pub fn canonical_roots(folders: &[PathBuf]) -> Vec<PathBuf> {
+    let mut roots: Vec<PathBuf> = folders
+        .iter()
+        .map(|folder| std::fs::canonicalize(folder).unwrap_or_else(|_| folder.clone()))
+        .collect();
+    roots.sort();
+    roots
+}
+
+/// The folders `files` are in, each once, in the order first seen.
+pub fn picked_folders(files: &[PathBuf]) -> Vec<PathBuf> {
+    let mut folders: Vec<PathBuf> = Vec::new();
+    for folder in files.iter().filter_map(|file| file.parent()) {
+        if !folders.iter().any(|known| known == folder) {
+            folders.push(folder.to_path_buf());
+        }
+    }
+    folders
+}
+
+/// Drops duplicate roots and roots nested inside another one, so no file is scanned twice.
+pub fn dedupe_roots(roots: &[PathBuf]) -> Vec<PathBuf> {
+    let canonical: Vec<PathBuf> = roots
+        .iter()
+        .map(|root| std::fs::canonicalize(root).unwrap_or_else(|_| root.clone()))
+        .collect();
+
+    roots
+        .iter()
+        .zip(&canonical)
+        .enumerate()
+        .filter(|&(index, (root, canonical_root))| {
+            // Of two identical roots the first is kept; a nested root is always dropped.
+            let covered = canonical.iter().enumerate().any(|(other, other_root)| {
+                other != index
+                    && canonical_root.starts_with(other_root)
+                    && (canonical_root != other_root || other < index)
+            });
+            if covered {
+                info!(
+                    "Skipping folder {:?}, already covered by another folder",
+                    root
+                );
+            }
+            !covered
+        })
+        .map(|(_, (root, _))| root.clone())
+        .collect()
+}
+
+/// Scans every root into one cache; each file remembers the root it was found under.
+///
+/// A physical file reached through more than one path, via overlapping roots or followed
+/// symlinks, is only cached under the first of them so it can't be written twice. The
+/// other paths are returned for reporting.
+pub type ScanResult = Result<(HashMap<PathBuf, FileInfo>, Vec<PathBuf>), SpindleSpeedUpdaterError>;
+
+/// Files named one by one rather than found by a scan.
+pub struct FileList {
+    pub file_cache: HashMap<PathBuf, FileInfo>,
+    /// Paths left out because they lead to a file already in the list.
+    pub duplicates: Vec<PathBuf>,
+    /// One line per path that was left out for another reason, and why.
+    pub rejected: Vec<String>,
+}
+
+/// Builds the file cache from a list of paths, held to the same extensions as a scan.
+/// Each file is filed under the first of `folders` that contains it, or else its own
+/// folder, which is what reports, backups and patches are relative to. Rejected paths
+/// are explained in `language`.
+pub fn list_files(
+    paths: impl IntoIterator<Item = PathBuf>,
+    folders: &[PathBuf],
+    rules: &ProjectRules,
+    language: Language,
+) -> Result<FileList, SpindleSpeedUpdaterError> {
+    let folders: Vec<PathBuf> = folders
+        .iter()
+        .map(|folder| std::path::absolute(folder).unwrap_or_else(|_| folder.clone()))
+        .collect();
+    let mut list = FileList {
+        file_cache: HashMap::new(),
+        duplicates: Vec::new(),
+        rejected: Vec::new(),
+    };
+    let mut seen = HashSet::new();
+    for given in paths {
+        let path = std::path::absolute(&given)?;
+        let metadata = match std::fs::metadata(&path) {
+            Ok(metadata) if metadata.is_file() => metadata,
+            Ok(_) => {
+                list.rejected
+                    .push(fill(Text::NotAFile.get(language), &[&given.display()]));
+                continue;
+            }
+            Err(error) => {
+                list.rejected.push(fill(
+                    Text::CantReadPath.get(language),
+                    &[&given.display(), &error],
+                ));
+                continue;
+            }
+        };
+        if !rules.matches_extension(&path) {
+            list.rejected.push(fill(
+                Text::WrongExtension.get(language),
+                &[&rules.extensions.join("/."), &given.display()],
+            ));
+            continue;
+        }
+        // The same file by another name, or the same name twice, is processed once.
+        let canonical = std::fs::canonicalize(&path).unwrap_or_else(|_| path.clone());
+        if !seen.insert(canonical) {
+            list.duplicates.push(path);
+            continue;
+        }
+        let root = folders
+            .iter()
+            .find(|folder| path.starts_with(folder))
+            .cloned()
+            .or_else(|| path.parent().map(Path::to_path_buf))
+            .unwrap_or_default();
+        let file_info = FileInfo {
+            path: path.clone(),
+            root,
+            last_modified: metadata
+                .modified()
+                .map_err(SpindleSpeedUpdaterError::file_io(&path))?,
+            size: metadata.len(),
+        };
+        list.file_cache.insert(path, file_info);
+    }
+    Ok(list)
+}
+
+pub fn scan_folders(roots: &[PathBuf], follow_symlinks: bool, rules: &ProjectRules) -> ScanResult {
+    scan_folders_cancellable(roots, follow_symlinks, rules, &AtomicBool::new(false))
+}
+
+/// [`scan_folders`] that gives up with [`SpindleSpeedUpdaterError::CancelError`] as soon
+/// as `cancel` is set.
+pub fn scan_folders_cancellable(
+    roots: &[PathBuf],
+    follow_symlinks: bool,
+    rules: &ProjectRules,
+    cancel: &AtomicBool,
+) -> ScanResult {
+    let started = Instant::now();
+    let mut file_cache = HashMap::new();
+    let mut seen = HashSet::new();
+    let mut duplicates = Vec::new();
+    // Canonicalizing costs a round trip per file on a network share. Without links and
+    // with a single root every path is already unique, so there is nothing to dedupe.
+    let dedupe = follow_symlinks || roots.len() > 1;
+    for root in roots {
+        let mut files: Vec<(PathBuf, FileInfo)> =
+            scan_folder_cancellable(root, follow_symlinks, rules, cancel)?
+                .into_iter()
+                .collect();
+        files.sort_by(|(a, _), (b, _)| a.cmp(b));
+        for (path, file_info) in files {
+            if !dedupe {
+                file_cache.insert(path, file_info);
+                continue;
+            }
+            let canonical = std::fs::canonicalize(&path).unwrap_or_else(|_| path.clone());
+            if seen.insert(canonical) {
+                file_cache.insert(path, file_info);
+            } else {
+                warn!("Skipping {:?}: same file as one already queued", path);
+                duplicates.push(path);
+            }
+        }
+    }
+    info!(
+        "Scanned {} files in {} ms",
+        file_cache.len(),
+        started.elapsed().as_millis()
+    );
+    Ok((file_cache, duplicates))
+}
+
+/// Collects the files below `folder_path` that have one of the extensions in `rules`.
+///
+/// Symbolic links are skipped unless `follow_symlinks` is set, so a misconfigured share
+/// can't lead the scan into unrelated directories. When they are followed, walkdir
+/// reports links pointing back at an ancestor as errors, which are logged and skipped.
+pub fn scan_folder(
+    folder_path: &Path,
+    follow_symlinks: bool,
+    rules: &ProjectRules,
+) -> Result<HashMap<PathBuf, FileInfo>, SpindleSpeedUpdaterError> {
+    scan_folder_cancellable(folder_path, follow_symlinks, rules, &AtomicBool::new(false))
+}
+
+pub fn scan_folder_cancellable(
+    folder_path: &Path,
+    follow_symlinks: bool,
+    rules: &ProjectRules,
+    cancel: &AtomicBool,
+) -> Result<HashMap<PathBuf, FileInfo>, SpindleSpeedUpdaterError> {
+    let mut file_cache = HashMap::new();
+
+    for entry in WalkDir::new(folder_path).follow_links(follow_symlinks) {
+        if cancel.load(Ordering::Relaxed) {
+            return Err(SpindleSpeedUpdaterError::CancelError(
+                "Folder scan cancelled".to_string(),
+            ));
+        }
+        let entry = match entry {
+            Ok(entry) => entry,
+            Err(error) => {
+                warn!("Skipping entry while scanning {:?}: {}", folder_path, error);
+                continue;
+            }
+        };
+        if entry.path_is_symlink() && !follow_symlinks {
+            info!("Skipping symlink: {:?}", entry.path());
+            continue;
+        }
+        if entry.file_type().is_file() && rules.matches_extension(entry.path()) {
+            // Served from the directory listing where the platform provides it.
+            let metadata = entry
+                .metadata()
+                .map_err(io::Error::from)
+                .map_err(SpindleSpeedUpdaterError::file_io(entry.path()))?;
+            let file_info = FileInfo {
+                path: entry.path().to_path_buf(),
+                root: folder_path.to_path_buf(),
+                last_modified: metadata
+                    .modified()
+                    .map_err(SpindleSpeedUpdaterError::file_io(entry.path()))?,
+                size: metadata.len(),
+            };
+            file_cache.insert(entry.path().to_path_buf(), file_info);
+        }
+    }
+
+    Ok(file_cache)
+}
+
+/// The files of `file_cache` modified less than `days` days before `now`, or all of them
+/// for `None`. A file dated after `now`, by a clock that is ahead, counts as recent.
+pub fn modified_within(
+    file_cache: &HashMap<PathBuf, FileInfo>,
+    days: Option<u32>,
+    now: SystemTime,
+) -> HashMap<PathBuf, FileInfo> {
+    let Some(days) = days else {
+        return file_cache.clone();
+    };
+    let window = Duration::from_secs(u64::from(days) * 24 * 60 * 60);
+    file_cache
+        .iter()
+        .filter(|(_, info)| {
+            now.duration_since(info.last_modified)
+                .map_or(true, |age| age < window)
+        })
+        .map(|(path, info)| (path.clone(), info.clone()))
+        .collect()
+}
+
+/// Makes the run compare each file against the modification time and size the dry pass
+/// saw, so anything touched between the preview and the update is skipped rather than
+/// written.
+pub fn pin_to_preview(file_cache: &mut HashMap<PathBuf, FileInfo>, entries: &[PreviewEntry]) {
+    for entry in entries {
+        if let (Some(modified), Some(info)) = (entry.modified, file_cache.get_mut(&entry.path)) {
+            info.last_modified = modified;
+            info.size = entry.size;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mapping::SpeedPlan;
+    use crate::progress::Progress;
+    use crate::update::{update_files, FileOutcome, RunOptions};
+    use std::sync::Arc;
+
+    #[test]
+    fn nested_and_repeated_roots_are_dropped() {
+        let dir = tempfile::tempdir().unwrap();
+        let jobs = dir.path().join("jobs");
+        let nested = jobs.join("mill");
+        std::fs::create_dir_all(&nested).unwrap();
+
+        let roots = dedupe_roots(&[nested.clone(), jobs.clone(), jobs.clone()]);
+
+        assert_eq!(roots, vec![jobs]);
+    }
+
+    /// Two roots reaching the same folder, one through a symlink: every program is queued
+    /// and written once.
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn file_reached_through_two_roots_is_written_once() {
+        let dir = tempfile::tempdir().unwrap();
+        let jobs = dir.path().join("jobs");
+        std::fs::create_dir(&jobs).unwrap();
+        for name in ["a.tap", "b.tap"] {
+            std::fs::write(jobs.join(name), "S1000 M3\n").unwrap();
+        }
+        let link = dir.path().join("link");
+        std::os::unix::fs::symlink(&jobs, &link).unwrap();
+
+        let roots = [jobs.clone(), link];
+        let (file_cache, duplicates) =
+            scan_folders(&roots, false, &ProjectRules::default()).unwrap();
+        assert_eq!(file_cache.len(), 2);
+        assert_eq!(duplicates.len(), 2);
+
+        let (_cancel, cancel_receiver) = tokio::sync::oneshot::channel();
+        let results = update_files(
+            "run1",
+            &SpeedPlan::Uniform(12000),
+            RunOptions::default(),
+            Arc::new(Progress::default()),
+            &file_cache,
+            cancel_receiver,
+            None,
+        )
+        .await
+        .unwrap();
+
+        let changed = results
+            .files
+            .iter()
+            .filter(|result| matches!(result.outcome, FileOutcome::Changed(_)))
+            .count();
+        assert_eq!(changed, 2);
+        assert_eq!(results.files.len(), 2);
+        for name in ["a.tap", "b.tap"] {
+            let content = std::fs::read_to_string(jobs.join(name)).unwrap();
+            assert_eq!(content, "S12000 M3\n");
+        }
+    }
+
+    #[test]
+    fn window_keeps_only_recently_modified_files() {
+        const DAY: u64 = 24 * 60 * 60;
+        let dir = tempfile::tempdir().unwrap();
+        let now = SystemTime::now();
+        let ages = [
+            ("posted.tap", now - Duration::from_secs(DAY)),
+            ("last_week.tap", now - Duration::from_secs(6 * DAY)),
+            ("last_month.tap", now - Duration::from_secs(20 * DAY)),
+            ("archived.tap", now - Duration::from_secs(400 * DAY)),
+            ("clock_ahead.tap", now + Duration::from_secs(60 * 60)),
+        ];
+        for (name, modified) in ages {
+            let path = dir.path().join(name);
+            std::fs::write(&path, "S1000 M3\n").unwrap();
+            filetime::set_file_mtime(&path, filetime::FileTime::from_system_time(modified))
+                .unwrap();
+        }
+        let roots = [dir.path().to_path_buf()];
+        let (file_cache, _) = scan_folders(&roots, false, &ProjectRules::default()).unwrap();
+
+        let names = |days: Option<u32>| {
+            let mut names: Vec<String> = modified_within(&file_cache, days, now)
+                .keys()
+                .map(|path| path.file_name().unwrap().to_string_lossy().into_owned())
+                .collect();
+            names.sort();
+            names
+        };
+
+        assert_eq!(names(Some(2)), ["clock_ahead.tap", "posted.tap"]);
+        assert_eq!(
+            names(Some(7)),
+            ["clock_ahead.tap", "last_week.tap", "posted.tap"]
+        );
+        assert_eq!(
+            names(Some(30)),
+            [
+                "clock_ahead.tap",
+                "last_month.tap",
+                "last_week.tap",
+                "posted.tap"
+            ]
+        );
+        assert_eq!(names(None).len(), 5);
+    }
+}
@@ -0,0 +1,123 @@
+//! Detection checks built into the binary, so a support engineer can confirm the parser
+//! on a customer machine without touching any of their files. The post samples are the
+//! ones in `fixtures/`, embedded at compile time. Every updated sample is also run a
+//! second time, which must find nothing left to change. The parser's own cases are unit
+//! tests; these only smoke-test the deployed binary.
+
+use crate::gcode::{rewrite_content, SkipReason, UpdateOptions};
+
+/// The speed every expected fixture was produced with.
+const SELF_TEST_SPEED: u32 = 12000;
+
+enum Expected {
+    Content(&'static str),
+    Skipped(fn(&SkipReason) -> bool),
+}
+
+struct Case {
+    name: &'static str,
+    input: &'static str,
+    expected: Expected,
+}
+
+fn cases() -> Vec<Case> {
+    macro_rules! dialect {
+        ($name:literal) => {
+            Case {
+                name: $name,
+                input: include_str!(concat!("../fixtures/", $name, ".tap")),
+                expected: Expected::Content(include_str!(concat!(
+                    "../fixtures/",
+                    $name,
+                    ".expected.tap"
+                ))),
+            }
+        };
+    }
+    vec![
+        dialect!("fanuc"),
+        dialect!("linuxcnc"),
+        dialect!("mach3"),
+        dialect!("haas"),
+        dialect!("spaced"),
+        dialect!("canned"),
+        Case {
+            name: "scientific",
+            input: include_str!("../fixtures/scientific.tap"),
+            expected: Expected::Skipped(|reason| {
+                matches!(reason, SkipReason::UnsupportedNotation { .. })
+            }),
+        },
+        Case {
+            name: "continued",
+            input: include_str!("../fixtures/continued.tap"),
+            expected: Expected::Skipped(|reason| {
+                matches!(reason, SkipReason::ContinuedBlock { .. })
+            }),
+        },
+    ]
+}
+
+/// Runs every case with the default options and prints one `PASS` or `FAIL` line each.
+/// Returns how many failed.
+pub fn run_self_test() -> usize {
+    let options = UpdateOptions::default();
+    let mut failed = 0;
+    for case in cases() {
+        let result = rewrite_content(case.input, SELF_TEST_SPEED, &options);
+        let problem = match (&case.expected, result) {
+            (Expected::Content(expected), Ok(update)) if update.content == *expected => {
+                second_pass_problem(&update.content, &options)
+            }
+            (Expected::Content(_), Ok(_)) => Some("output differs from the expected file".into()),
+            (Expected::Content(_), Err(reason)) => Some(format!("skipped: {}", reason)),
+            (Expected::Skipped(matches), Err(reason)) if matches(&reason) => None,
+            (Expected::Skipped(_), Err(reason)) => Some(format!("wrong reason: {}", reason)),
+            (Expected::Skipped(_), Ok(_)) => Some("updated instead of skipped".to_string()),
+        };
+        failed += usize::from(report(case.name, problem));
+    }
+    failed
+}
+
+/// Prints the case's line; returns whether it failed.
+fn report(name: &str, problem: Option<String>) -> bool {
+    match problem {
+        None => {
+            println!("PASS      {}", name);
+            false
+        }
+        Some(problem) => {
+            println!("FAIL      {}: {}", name, problem);
+            true
+        }
+    }
+}
+
+/// Running again at the same speed must leave the output alone, so a repeated run
+/// reports every file as already correct instead of rewriting it over spacing or line
+/// endings.
+fn second_pass_problem(output: &str, options: &UpdateOptions) -> Option<String> {
+    match rewrite_content(output, SELF_TEST_SPEED, options) {
+        Ok(update) if !update.changed && update.content == output => None,
+        Ok(update) => Some(format!(
+            "second pass changed it again: {:?}",
+            update
+                .line_changes
+                .first()
+                .map(|line| line.new.trim().to_string())
+                .unwrap_or_default()
+        )),
+        Err(reason) => Some(format!("second pass skipped it: {}", reason)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn every_built_in_sample_passes() {
+        assert_eq!(run_self_test(), 0);
+    }
+}
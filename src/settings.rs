@@ -0,0 +1,501 @@
+//! The preferences the window keeps between launches, and the rewrite and run options
+//! they stand for. The command line starts from the defaults.
+
+use crate::gcode::{
+    ChangeStamp, InsertionMarker, InsertionPoint, SpeedOverride, SpindleTarget, UpdateOptions,
+};
+use crate::preview::CleanCriteria;
+use crate::project::ProjectRules;
+use crate::speed::SpeedMode;
+use crate::strings::{Language, Text};
+use crate::timestamp::UtcDateTime;
+use crate::update::{result_chunks, OutputFolder, RunOptions};
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+/// What the success message leads with after a run.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum SuccessCount {
+    /// Only files whose content was rewritten.
+    Changed,
+    /// Every file the run looked at.
+    Processed,
+}
+
+impl SuccessCount {
+    pub const ALL: [SuccessCount; 2] = [SuccessCount::Changed, SuccessCount::Processed];
+
+    pub fn label(self, language: Language) -> &'static str {
+        match self {
+            SuccessCount::Changed => Text::CountChanged,
+            SuccessCount::Processed => Text::CountProcessed,
+        }
+        .get(language)
+    }
+}
+
+/// What Enter in the speed field does once the speed is valid.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum EnterAction {
+    /// Check the files, then ask for confirmation like the button does.
+    Confirm,
+    /// Check the files and start the run without the confirmation dialog.
+    Run,
+}
+
+impl EnterAction {
+    pub const ALL: [EnterAction; 2] = [EnterAction::Confirm, EnterAction::Run];
+
+    pub fn label(self, language: Language) -> &'static str {
+        match self {
+            EnterAction::Confirm => Text::EnterConfirms,
+            EnterAction::Run => Text::EnterRuns,
+        }
+        .get(language)
+    }
+}
+
+/// How file lists, messages and exported results show a file's path.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum PathDisplay {
+    /// From the selected folder the file was found under, which stays readable in deep
+    /// folder structures.
+    #[default]
+    Relative,
+    /// The full path, for pasting into other tools.
+    Absolute,
+}
+
+impl PathDisplay {
+    pub const ALL: [PathDisplay; 2] = [PathDisplay::Relative, PathDisplay::Absolute];
+
+    pub fn label(self, language: Language) -> &'static str {
+        match self {
+            PathDisplay::Relative => Text::PathsRelative,
+            PathDisplay::Absolute => Text::PathsAbsolute,
+        }
+        .get(language)
+    }
+
+    /// `path` as this shows it; relative only when it lies under `root`.
+    pub fn show(self, path: &Path, root: Option<&Path>) -> String {
+        let relative = match self {
+            PathDisplay::Relative => root.and_then(|root| path.strip_prefix(root).ok()),
+            PathDisplay::Absolute => None,
+        };
+        relative.unwrap_or(path).display().to_string()
+    }
+}
+
+/// Where a run writes the programs it updates.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum OutputMode {
+    #[default]
+    InPlace,
+    /// Into [`Settings::output_folder`], mirroring the selected folders, so a reviewed
+    /// batch can be produced without touching the library it came from.
+    OutputFolder,
+}
+
+impl OutputMode {
+    pub const ALL: [OutputMode; 2] = [OutputMode::InPlace, OutputMode::OutputFolder];
+
+    pub fn label(self, language: Language) -> &'static str {
+        match self {
+            OutputMode::InPlace => Text::OverwriteInPlace,
+            OutputMode::OutputFolder => Text::WriteToOutputFolder,
+        }
+        .get(language)
+    }
+}
+
+/// A named copy of the settings, for switching between jobs or machines in one go.
+/// Each carries its own folders and folder fingerprint, so loading a profile and
+/// running it against last week's job is caught before anything is written.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct Profile {
+    pub name: String,
+    pub settings: Settings,
+}
+
+/// User preferences persisted between launches through eframe storage.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Settings {
+    /// Language of the window text.
+    pub language: Language,
+    /// Seconds before the success message clears itself; 0 keeps it until cleared.
+    pub success_message_timeout_secs: u64,
+    pub success_count: SuccessCount,
+    /// The pre-flight checks and their warnings apply either way.
+    pub enter_action: EnterAction,
+    /// Enter confirms the confirmation dialog wherever the focus is, as it did before the
+    /// dialog could be used from the keyboard; otherwise it presses the focused button.
+    pub enter_confirms_dialog: bool,
+    /// The action button runs an audit of the current speeds instead of an update.
+    pub audit_only: bool,
+    pub path_display: PathDisplay,
+    /// Insert an `S{speed} M3` line into files that have no spindle command at all.
+    pub insert_missing_s: bool,
+    pub insertion_point: InsertionPoint,
+    /// Insert after the first line containing `insertion_marker` when the program has one.
+    pub use_insertion_marker: bool,
+    pub insertion_marker: String,
+    /// Skip programs without the marker instead of falling back to `insertion_point`.
+    pub require_insertion_marker: bool,
+    /// Scale the entered speed by the percentage in a `speed_override_marker` comment,
+    /// e.g. `(SPEED OVERRIDE 80%)`, in the programs that have one.
+    pub use_speed_override: bool,
+    pub speed_override_marker: String,
+    /// Put a comment from `change_stamp_template` at the top of each changed file, with
+    /// `{speed}` and `{date}` filled in.
+    pub add_change_stamp: bool,
+    pub change_stamp_template: String,
+    /// Strip trailing whitespace from the lines the tool rewrites.
+    pub trim_rewritten_lines: bool,
+    /// Write `m3`/`m4` on rewritten lines as `M3`/`M4`.
+    pub uppercase_m_codes: bool,
+    /// Zero-pad the new speed to the width of the number it replaces.
+    pub pad_speed_width: bool,
+    /// Write `M3 S1000` as `S12000 M3`.
+    pub spindle_word_first: bool,
+    /// Fail a file instead of writing it when its update would change more than its
+    /// spindle lines.
+    pub check_structure: bool,
+    /// Which spindle words are rewritten on multi-spindle machines.
+    pub spindle_target: SpindleTarget,
+    /// Ignore spindle words before the first tool change (`T` word or `M6`).
+    pub after_tool_change: bool,
+    /// Update the first spindle word after every `M6`, leaving later overrides alone.
+    pub each_tool_change: bool,
+    /// Only update files whose current spindle speed is `current_speed_filter`.
+    pub match_current_speed: bool,
+    pub current_speed_filter: u32,
+    /// Follow symbolic links while scanning the folder.
+    pub follow_symlinks: bool,
+    /// Pause after each written file, for controllers that choke on bursts of writes.
+    pub write_delay_ms: u64,
+    /// Refuse to run when more than this share of the targeted files would fail the
+    /// dry pass. 100 disables the check.
+    pub max_failure_percent: u8,
+    /// Ask before a run that would take most files below `speed_drop_percent` of their
+    /// current speed, which is more often a missing zero than intended.
+    pub warn_on_speed_drop: bool,
+    pub speed_drop_percent: u8,
+    /// Warn in the confirmation dialog when the chosen folders don't look like the ones
+    /// these settings were made for: `sentinel_file` missing from a folder, or a file count
+    /// other than `expected_file_count`. An empty name or a count of 0 isn't checked.
+    pub check_folder_fingerprint: bool,
+    pub sentinel_file: String,
+    pub expected_file_count: u32,
+    /// Extra attempts at reading or writing a file after a transient IO error.
+    pub io_retries: u32,
+    /// Re-read every written file and report it as failed unless it holds exactly what
+    /// was written.
+    pub verify_writes: bool,
+    /// Keep a `.bak` copy of every file before changing it, listed in
+    /// `backup-manifest.json`.
+    pub create_backups: bool,
+    /// Give rewritten files back their modified time (and permissions on Unix).
+    pub preserve_metadata: bool,
+    pub output_mode: OutputMode,
+    /// Used with [`OutputMode::OutputFolder`]; a run refuses to start until one is chosen.
+    pub output_folder: Option<PathBuf>,
+    /// In an output folder run, copy the files that need no change as they are, so the
+    /// folder holds the whole batch rather than only the updated files.
+    pub copy_unchanged: bool,
+    /// Check every file for a detectable spindle command when the app opens.
+    pub startup_self_check: bool,
+    /// Ask for attention, with a desktop notification and its sound, when a run finishes
+    /// while the window is in the background.
+    pub notify_on_completion: bool,
+    /// Start the run straight after the dry pass when it meets `clean_criteria`, and ask
+    /// for confirmation as usual when it doesn't.
+    pub auto_apply_if_clean: bool,
+    pub clean_criteria: CleanCriteria,
+    /// Refuse any run that would modify more than `max_files_to_modify` files. Unlike
+    /// the failure check this can't be overridden from the run itself.
+    pub limit_files_to_modify: bool,
+    pub max_files_to_modify: usize,
+    /// Leave out files last modified more than `recent_days` days ago, such as archived
+    /// programs, from previews and runs.
+    pub limit_to_recent: bool,
+    pub recent_days: u32,
+    /// Refuse any run in which a targeted file has no S command, which usually means a
+    /// bad post. Like the file cap this can't be overridden from the run itself.
+    pub require_spindle_command: bool,
+    /// How the speed was given in the last session, reselected on launch.
+    pub speed_mode: SpeedMode,
+    /// Speed of the last session, restored into the input on launch.
+    pub last_speed: Option<u32>,
+    /// Inputs of [`SpeedMode::Percent`] and [`SpeedMode::SurfaceSpeed`] from the last
+    /// session, restored like [`Settings::last_speed`].
+    pub last_percent: Option<f64>,
+    pub last_surface_speed: Option<f64>,
+    pub last_diameter: Option<f64>,
+    /// Speed mapping CSV of the last session, reloaded on launch.
+    pub last_mapping: Option<PathBuf>,
+    /// Take each file's speed from the `[[speed_rules]]` of the project config when it
+    /// has any and no mapping is loaded.
+    pub use_speed_rules: bool,
+    /// Folders of the last session, rescanned on launch. Until one has been chosen the
+    /// window asks for a folder instead of assuming the executable's.
+    pub last_folders: Vec<PathBuf>,
+    /// Where every run is appended as a row; `runs.csv` next to the executable when unset.
+    pub run_history_file: Option<PathBuf>,
+    /// Write per-file results to a CSV beside the run history every this many files,
+    /// keeping only changed and failed files in memory. 0 keeps every result.
+    pub result_chunk_size: usize,
+    /// Saved profiles, in the order they were first saved.
+    pub profiles: Vec<Profile>,
+    /// The profile last saved or loaded, if the settings haven't been switched since.
+    pub active_profile: Option<String>,
+}
+
+impl Default for Settings {
+    fn default() -> Self {
+        Self {
+            language: Language::English,
+            success_message_timeout_secs: 10,
+            success_count: SuccessCount::Processed,
+            enter_action: EnterAction::Confirm,
+            enter_confirms_dialog: false,
+            audit_only: false,
+            path_display: PathDisplay::default(),
+            insert_missing_s: false,
+            insertion_point: InsertionPoint::AfterFirstMotion,
+            use_insertion_marker: false,
+            insertion_marker: "(START)".to_string(),
+            require_insertion_marker: false,
+            use_speed_override: false,
+            speed_override_marker: "SPEED OVERRIDE".to_string(),
+            add_change_stamp: false,
+            change_stamp_template: "(SPEED SET TO {speed} ON {date})".to_string(),
+            trim_rewritten_lines: false,
+            uppercase_m_codes: false,
+            pad_speed_width: false,
+            spindle_word_first: false,
+            check_structure: true,
+            spindle_target: SpindleTarget::Main,
+            after_tool_change: false,
+            each_tool_change: false,
+            match_current_speed: false,
+            current_speed_filter: 10000,
+            follow_symlinks: false,
+            write_delay_ms: 0,
+            max_failure_percent: 10,
+            warn_on_speed_drop: true,
+            speed_drop_percent: 50,
+            check_folder_fingerprint: false,
+            sentinel_file: String::new(),
+            expected_file_count: 0,
+            io_retries: 2,
+            verify_writes: false,
+            create_backups: false,
+            preserve_metadata: false,
+            output_mode: OutputMode::InPlace,
+            output_folder: None,
+            copy_unchanged: false,
+            startup_self_check: false,
+            notify_on_completion: false,
+            auto_apply_if_clean: false,
+            clean_criteria: CleanCriteria::default(),
+            limit_files_to_modify: false,
+            limit_to_recent: false,
+            recent_days: 30,
+            require_spindle_command: false,
+            max_files_to_modify: 50,
+            speed_mode: SpeedMode::Absolute,
+            last_speed: None,
+            last_percent: None,
+            last_surface_speed: None,
+            last_diameter: None,
+            last_mapping: None,
+            use_speed_rules: false,
+            last_folders: Vec::new(),
+            run_history_file: None,
+            result_chunk_size: 0,
+            profiles: Vec::new(),
+            active_profile: None,
+        }
+    }
+}
+
+impl Settings {
+    /// Saves the current settings as profile `name`, replacing one of the same name, and
+    /// makes it the active profile. A blank name is ignored.
+    pub fn save_profile(&mut self, name: &str) {
+        let name = name.trim();
+        if name.is_empty() {
+            return;
+        }
+        let mut settings = self.clone();
+        settings.profiles.clear();
+        settings.active_profile = None;
+        let profile = Profile {
+            name: name.to_string(),
+            settings,
+        };
+        match self.profiles.iter_mut().find(|saved| saved.name == name) {
+            Some(saved) => *saved = profile,
+            None => self.profiles.push(profile),
+        }
+        self.active_profile = Some(name.to_string());
+    }
+
+    /// Replaces the settings with those of profile `name`, keeping the saved profiles.
+    /// Returns false, changing nothing, when there is no such profile.
+    pub fn load_profile(&mut self, name: &str) -> bool {
+        let Some(profile) = self.profiles.iter().find(|saved| saved.name == name) else {
+            return false;
+        };
+        let mut settings = profile.settings.clone();
+        settings.profiles = std::mem::take(&mut self.profiles);
+        settings.active_profile = Some(name.to_string());
+        *self = settings;
+        true
+    }
+
+    pub fn delete_profile(&mut self, name: &str) {
+        self.profiles.retain(|saved| saved.name != name);
+        if self.active_profile.as_deref() == Some(name) {
+            self.active_profile = None;
+        }
+    }
+
+    /// The age limit in days for files to process, if there is one.
+    pub fn recent_window(&self) -> Option<u32> {
+        self.limit_to_recent.then_some(self.recent_days)
+    }
+
+    pub fn update_options(&self, rules: &ProjectRules) -> UpdateOptions {
+        UpdateOptions {
+            insert_missing: self.insert_missing_s.then_some(self.insertion_point),
+            insertion_marker: (self.use_insertion_marker
+                && !self.insertion_marker.trim().is_empty())
+            .then(|| InsertionMarker {
+                text: self.insertion_marker.trim().to_string(),
+                required: self.require_insertion_marker,
+            }),
+            match_current_speed: self
+                .match_current_speed
+                .then_some(self.current_speed_filter),
+            spindle_on: rules.spindle_on.clone(),
+            trim_rewritten_lines: self.trim_rewritten_lines,
+            uppercase_m_codes: self.uppercase_m_codes,
+            pad_speed_width: self.pad_speed_width,
+            spindle_word_first: self.spindle_word_first,
+            check_structure: self.check_structure,
+            spindle_target: self.spindle_target,
+            spindle_letter: rules.spindle_letter,
+            after_tool_change: self.after_tool_change,
+            each_tool_change: self.each_tool_change,
+            speed_override: (self.use_speed_override
+                && !self.speed_override_marker.trim().is_empty())
+            .then(|| SpeedOverride {
+                marker: self.speed_override_marker.trim().to_string(),
+                speed_range: rules.speed_range.clone(),
+            }),
+            change_stamp: (self.add_change_stamp
+                && ChangeStamp::is_comment(&self.change_stamp_template))
+            .then(|| ChangeStamp {
+                template: self.change_stamp_template.clone(),
+                date: UtcDateTime::now().date(),
+            }),
+        }
+    }
+
+    /// `folders` are the selected folders the run's files come from.
+    pub fn run_options(&self, rules: &ProjectRules, folders: &[PathBuf]) -> RunOptions {
+        let output_folder = match (self.output_mode, &self.output_folder) {
+            (OutputMode::OutputFolder, Some(folder)) => Some(OutputFolder {
+                folder: folder.clone(),
+                copy_unchanged: self.copy_unchanged,
+                nest_by_root: folders.len() > 1,
+            }),
+            _ => None,
+        };
+        RunOptions {
+            update: self.update_options(rules),
+            write_delay: Duration::from_millis(self.write_delay_ms),
+            io_retries: self.io_retries,
+            verify_writes: self.verify_writes,
+            create_backups: self.create_backups,
+            preserve_metadata: self.preserve_metadata,
+            result_chunks: result_chunks(
+                self.result_chunk_size,
+                self.run_history_file.as_deref(),
+                self.path_display,
+            ),
+            output_folder,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn profiles_are_saved_loaded_and_replaced_by_name() {
+        let mut settings = Settings {
+            sentinel_file: "JOB-41.txt".to_string(),
+            expected_file_count: 12,
+            ..Settings::default()
+        };
+        settings.save_profile(" Lathe ");
+        settings.sentinel_file = "JOB-42.txt".to_string();
+        settings.save_profile("Mill");
+        assert_eq!(settings.active_profile.as_deref(), Some("Mill"));
+
+        assert!(settings.load_profile("Lathe"));
+        assert_eq!(settings.sentinel_file, "JOB-41.txt");
+        assert_eq!(settings.expected_file_count, 12);
+        assert_eq!(settings.active_profile.as_deref(), Some("Lathe"));
+        let names: Vec<&str> = settings.profiles.iter().map(|p| p.name.as_str()).collect();
+        assert_eq!(names, ["Lathe", "Mill"]);
+        // A saved profile never holds the other profiles.
+        assert!(settings
+            .profiles
+            .iter()
+            .all(|p| p.settings.profiles.is_empty()));
+
+        settings.expected_file_count = 14;
+        settings.save_profile("Lathe");
+        assert_eq!(settings.profiles.len(), 2);
+        assert!(settings.load_profile("Mill"));
+        assert!(settings.load_profile("Lathe"));
+        assert_eq!(settings.expected_file_count, 14);
+    }
+
+    #[test]
+    fn unknown_or_deleted_profiles_change_nothing() {
+        let mut settings = Settings::default();
+        settings.save_profile("");
+        assert!(settings.profiles.is_empty());
+        settings.save_profile("Lathe");
+        settings.delete_profile("Lathe");
+        assert!(settings.profiles.is_empty());
+        assert_eq!(settings.active_profile, None);
+
+        settings.recent_days = 7;
+        assert!(!settings.load_profile("Lathe"));
+        assert_eq!(settings.recent_days, 7);
+    }
+
+    #[test]
+    fn profiles_survive_a_round_trip_through_storage() {
+        let mut settings = Settings {
+            check_folder_fingerprint: true,
+            ..Settings::default()
+        };
+        settings.save_profile("Lathe");
+
+        let stored = serde_json::to_string(&settings).unwrap();
+        let restored: Settings = serde_json::from_str(&stored).unwrap();
+
+        assert_eq!(restored.profiles.len(), 1);
+        assert!(restored.profiles[0].settings.check_folder_fingerprint);
+    }
+}
@@ -0,0 +1,462 @@
+//! Spindle speeds as the operator types and reads them.
+
+use crate::strings::{fill, Language, Text};
+use serde::{Deserialize, Serialize};
+use std::ops::RangeInclusive;
+
+pub const MIN_SPINDLE_SPEED: u32 = 1;
+pub const MAX_SPINDLE_SPEED: u32 = 24000;
+
+/// How the operator gives the speed a run writes.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum SpeedMode {
+    /// The RPM itself.
+    #[default]
+    Absolute,
+    /// A percentage of the top of the allowed speed range.
+    Percent,
+    /// Constant surface speed, as lathe operators think of it: a cutting speed in m/min
+    /// at a work diameter in mm, written as the RPM that gives it.
+    SurfaceSpeed,
+}
+
+impl SpeedMode {
+    pub const ALL: [SpeedMode; 3] = [
+        SpeedMode::Absolute,
+        SpeedMode::Percent,
+        SpeedMode::SurfaceSpeed,
+    ];
+
+    pub fn label(self, language: Language) -> &'static str {
+        match self {
+            SpeedMode::Absolute => Text::SpeedModeAbsolute,
+            SpeedMode::Percent => Text::SpeedModePercent,
+            SpeedMode::SurfaceSpeed => Text::SpeedModeSurface,
+        }
+        .get(language)
+    }
+}
+
+/// Why a typed spindle speed was refused.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum SpeedError {
+    Empty,
+    OutOfRange {
+        min: u32,
+        max: u32,
+    },
+    NotANumber,
+    /// A percentage, surface speed or diameter of zero, which would write `S0` or divide
+    /// by zero.
+    PercentNotPositive,
+    SurfaceSpeedNotPositive,
+    DiameterNotPositive,
+    /// A speed computed from a percentage or a surface speed, once rounded to whole RPM,
+    /// outside the allowed range.
+    ComputedBelowMinimum {
+        speed: u32,
+        minimum: u32,
+    },
+    ComputedAboveMaximum {
+        speed: u32,
+        maximum: u32,
+    },
+    /// Grouped with the other language's separator, e.g. `12.000` where `,` groups.
+    WrongSeparator {
+        used: char,
+        speed: u32,
+        expected: char,
+    },
+}
+
+impl SpeedError {
+    /// Worded in `language`, for the window.
+    pub fn message(self, language: Language) -> String {
+        match self {
+            SpeedError::Empty => Text::EnterSpeed.get(language).to_string(),
+            SpeedError::OutOfRange { min, max } => {
+                fill(Text::SpeedOutOfRange.get(language), &[&min, &max])
+            }
+            SpeedError::NotANumber => Text::InvalidSpeed.get(language).to_string(),
+            SpeedError::PercentNotPositive => Text::PercentNotPositive.get(language).to_string(),
+            SpeedError::SurfaceSpeedNotPositive => {
+                Text::SurfaceSpeedNotPositive.get(language).to_string()
+            }
+            SpeedError::DiameterNotPositive => Text::DiameterNotPositive.get(language).to_string(),
+            SpeedError::ComputedBelowMinimum { speed, minimum } => fill(
+                Text::ComputedBelowMinimum.get(language),
+                &[&speed, &minimum],
+            ),
+            SpeedError::ComputedAboveMaximum { speed, maximum } => fill(
+                Text::ComputedAboveMaximum.get(language),
+                &[&speed, &maximum],
+            ),
+            SpeedError::WrongSeparator {
+                used,
+                speed,
+                expected,
+            } => fill(
+                Text::WrongSeparator.get(language),
+                &[&used, &speed, &expected],
+            ),
+        }
+    }
+}
+
+/// In English, for the log and the command line.
+impl std::fmt::Display for SpeedError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(&self.message(Language::English))
+    }
+}
+
+impl std::error::Error for SpeedError {}
+
+/// Checks a typed spindle speed against `min..=max`. Shared by the live check on the
+/// input field, the validation before a run and the command line, so all of them agree.
+///
+/// Thousands may be grouped with `separator` (`12,000` in English, `12.000` in Spanish;
+/// see [`Language::thousands_separator`]). Grouping with the other separator is refused
+/// with a hint rather than guessed, since `12.000` reads as twelve to an English speaker.
+pub fn parse_speed(input: &str, min: u32, max: u32, separator: char) -> Result<u32, SpeedError> {
+    let input = input.trim();
+    if input.is_empty() {
+        return Err(SpeedError::Empty);
+    }
+    match input
+        .parse::<u32>()
+        .ok()
+        .or_else(|| parse_grouped(input, separator))
+    {
+        Some(speed) if (min..=max).contains(&speed) => Ok(speed),
+        Some(_) => Err(SpeedError::OutOfRange { min, max }),
+        None => {
+            let other = if separator == ',' { '.' } else { ',' };
+            match parse_grouped(input, other) {
+                Some(speed) => Err(SpeedError::WrongSeparator {
+                    used: other,
+                    speed,
+                    expected: separator,
+                }),
+                None => Err(SpeedError::NotANumber),
+            }
+        }
+    }
+}
+
+/// [`parse_speed`] within the project's `speed_range`, grouped the way `language` does.
+pub fn parse_spindle_speed(
+    input: &str,
+    speed_range: &RangeInclusive<u32>,
+    language: Language,
+) -> Result<u32, SpeedError> {
+    parse_speed(
+        input,
+        *speed_range.start(),
+        *speed_range.end(),
+        language.thousands_separator(),
+    )
+}
+
+/// A typed decimal such as a percentage or a diameter, with `.` or `,` before the
+/// decimals in either language since neither groups thousands there.
+pub fn parse_decimal(input: &str) -> Result<f64, SpeedError> {
+    let input = input.trim();
+    if input.is_empty() {
+        return Err(SpeedError::Empty);
+    }
+    let well_formed = input
+        .bytes()
+        .all(|byte| byte.is_ascii_digit() || byte == b'.' || byte == b',')
+        && input
+            .bytes()
+            .filter(|&byte| byte == b'.' || byte == b',')
+            .count()
+            <= 1;
+    match input.replace(',', ".").parse::<f64>() {
+        Ok(value) if well_formed => Ok(value),
+        _ => Err(SpeedError::NotANumber),
+    }
+}
+
+/// The speed `percent` of the top of `speed_range`, rounded to whole RPM.
+pub fn percent_speed(percent: &str, speed_range: &RangeInclusive<u32>) -> Result<u32, SpeedError> {
+    let percent = parse_decimal(percent)?;
+    if percent <= 0.0 {
+        return Err(SpeedError::PercentNotPositive);
+    }
+    within(f64::from(*speed_range.end()) * percent / 100.0, speed_range)
+}
+
+/// The RPM that gives `surface_speed` m/min at `diameter` mm, rounded to whole RPM:
+/// 1000 × speed / (π × diameter).
+pub fn surface_speed_rpm(
+    surface_speed: &str,
+    diameter: &str,
+    speed_range: &RangeInclusive<u32>,
+) -> Result<u32, SpeedError> {
+    let surface_speed = parse_decimal(surface_speed)?;
+    let diameter = parse_decimal(diameter)?;
+    if surface_speed <= 0.0 {
+        return Err(SpeedError::SurfaceSpeedNotPositive);
+    }
+    if diameter <= 0.0 {
+        return Err(SpeedError::DiameterNotPositive);
+    }
+    within(
+        1000.0 * surface_speed / (std::f64::consts::PI * diameter),
+        speed_range,
+    )
+}
+
+/// A computed `speed` rounded to whole RPM, if that lies in `speed_range`. A speed that
+/// rounds to 0 is below any minimum, so `S0` is never written.
+fn within(speed: f64, speed_range: &RangeInclusive<u32>) -> Result<u32, SpeedError> {
+    // Saturates, so a tiny diameter reads as a huge speed rather than wrapping.
+    let rounded = speed.round() as u32;
+    if rounded < *speed_range.start() {
+        Err(SpeedError::ComputedBelowMinimum {
+            speed: rounded,
+            minimum: *speed_range.start(),
+        })
+    } else if rounded > *speed_range.end() {
+        Err(SpeedError::ComputedAboveMaximum {
+            speed: rounded,
+            maximum: *speed_range.end(),
+        })
+    } else {
+        Ok(rounded)
+    }
+}
+
+/// `input` as digits in groups of three after the first, e.g. `12,000` or `1.250.000`.
+pub fn parse_grouped(input: &str, separator: char) -> Option<u32> {
+    let groups: Vec<&str> = input.split(separator).collect();
+    let (first, rest) = groups.split_first()?;
+    let well_formed = !rest.is_empty()
+        && (1..=3).contains(&first.len())
+        && rest.iter().all(|group| group.len() == 3)
+        && groups
+            .iter()
+            .all(|group| group.bytes().all(|byte| byte.is_ascii_digit()));
+    if !well_formed {
+        return None;
+    }
+    groups.concat().parse().ok()
+}
+
+/// Formats a speed with thousands separators, e.g. "12,000 RPM", so a dropped or
+/// extra zero stands out when confirming.
+pub fn format_rpm(speed: u32) -> String {
+    let digits = speed.to_string();
+    let mut grouped = String::with_capacity(digits.len() + digits.len() / 3);
+    for (index, digit) in digits.chars().enumerate() {
+        if index > 0 && (digits.len() - index).is_multiple_of(3) {
+            grouped.push(',');
+        }
+        grouped.push(digit);
+    }
+    format!("{} RPM", grouped)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(input: &str, language: Language) -> Result<u32, SpeedError> {
+        parse_spindle_speed(input, &(MIN_SPINDLE_SPEED..=MAX_SPINDLE_SPEED), language)
+    }
+
+    #[test]
+    fn range_ends_are_accepted_and_just_outside_them_refused() {
+        let out_of_range = Err(SpeedError::OutOfRange { min: 100, max: 500 });
+        assert_eq!(parse_speed("100", 100, 500, ','), Ok(100));
+        assert_eq!(parse_speed("500", 100, 500, ','), Ok(500));
+        assert_eq!(parse_speed("99", 100, 500, ','), out_of_range);
+        assert_eq!(parse_speed("501", 100, 500, ','), out_of_range);
+        assert_eq!(
+            parse_speed("0", MIN_SPINDLE_SPEED, MAX_SPINDLE_SPEED, ','),
+            Err(SpeedError::OutOfRange {
+                min: MIN_SPINDLE_SPEED,
+                max: MAX_SPINDLE_SPEED,
+            })
+        );
+        assert_eq!(
+            parse_speed("24,001", MIN_SPINDLE_SPEED, MAX_SPINDLE_SPEED, ','),
+            Err(SpeedError::OutOfRange {
+                min: MIN_SPINDLE_SPEED,
+                max: MAX_SPINDLE_SPEED,
+            })
+        );
+    }
+
+    #[test]
+    fn empty_input_is_asked_for() {
+        assert_eq!(parse_speed("", 1, 24000, ','), Err(SpeedError::Empty));
+        assert_eq!(parse_speed("  \t", 1, 24000, ','), Err(SpeedError::Empty));
+    }
+
+    #[test]
+    fn non_numeric_input_is_not_a_number() {
+        for input in ["abc", "12k", "-5", "1e4", "S12000"] {
+            assert_eq!(
+                parse_speed(input, 1, 24000, ','),
+                Err(SpeedError::NotANumber),
+                "{}",
+                input
+            );
+        }
+    }
+
+    #[test]
+    fn surrounding_whitespace_is_ignored() {
+        assert_eq!(parse_speed(" 12000 ", 1, 24000, ','), Ok(12000));
+    }
+
+    #[test]
+    fn decimals_take_either_separator() {
+        assert_eq!(parse_decimal("82.5"), Ok(82.5));
+        assert_eq!(parse_decimal(" 82,5 "), Ok(82.5));
+        assert_eq!(parse_decimal("80"), Ok(80.0));
+        assert_eq!(parse_decimal(""), Err(SpeedError::Empty));
+        for input in ["1.000,5", "-5", "1e3", "abc", "inf"] {
+            assert_eq!(
+                parse_decimal(input),
+                Err(SpeedError::NotANumber),
+                "{}",
+                input
+            );
+        }
+    }
+
+    #[test]
+    fn percent_is_of_the_top_of_the_range() {
+        let range = 1..=24000;
+        assert_eq!(percent_speed("50", &range), Ok(12000));
+        assert_eq!(percent_speed("33,3", &range), Ok(7992));
+        assert_eq!(
+            percent_speed("101", &range),
+            Err(SpeedError::ComputedAboveMaximum {
+                speed: 24240,
+                maximum: 24000,
+            })
+        );
+    }
+
+    #[test]
+    fn surface_speed_gives_the_rpm_at_the_diameter() {
+        let range = 1..=24000;
+        // 200 m/min on a 50 mm bar: 200000 / (π × 50) = 1273.2.
+        assert_eq!(surface_speed_rpm("200", "50", &range), Ok(1273));
+        assert_eq!(surface_speed_rpm("150", "12.5", &range), Ok(3820));
+        assert_eq!(surface_speed_rpm("", "50", &range), Err(SpeedError::Empty));
+    }
+
+    #[test]
+    fn zero_percent_is_refused_by_name() {
+        assert_eq!(
+            percent_speed("0", &(1..=24000)),
+            Err(SpeedError::PercentNotPositive)
+        );
+        assert_eq!(
+            percent_speed("0,0", &(1..=24000)),
+            Err(SpeedError::PercentNotPositive)
+        );
+    }
+
+    #[test]
+    fn zero_diameter_or_surface_speed_is_refused_by_name() {
+        let range = 1..=24000;
+        assert_eq!(
+            surface_speed_rpm("200", "0", &range),
+            Err(SpeedError::DiameterNotPositive)
+        );
+        assert_eq!(
+            surface_speed_rpm("0", "50", &range),
+            Err(SpeedError::SurfaceSpeedNotPositive)
+        );
+        assert_eq!(
+            SpeedError::DiameterNotPositive.to_string(),
+            "The diameter must be more than 0"
+        );
+        // Not zero, but small enough to saturate instead of dividing to infinity.
+        assert_eq!(
+            surface_speed_rpm("200", "0.0000001", &range),
+            Err(SpeedError::ComputedAboveMaximum {
+                speed: u32::MAX,
+                maximum: 24000,
+            })
+        );
+    }
+
+    #[test]
+    fn percentage_rounding_below_the_minimum_is_refused_with_the_speed() {
+        assert_eq!(
+            percent_speed("2", &(1000..=24000)),
+            Err(SpeedError::ComputedBelowMinimum {
+                speed: 480,
+                minimum: 1000,
+            })
+        );
+        // 0.24 RPM rounds to 0, which is never written as S0.
+        assert_eq!(
+            percent_speed("0.001", &(1..=24000)),
+            Err(SpeedError::ComputedBelowMinimum {
+                speed: 0,
+                minimum: 1,
+            })
+        );
+        assert_eq!(
+            SpeedError::ComputedBelowMinimum {
+                speed: 480,
+                minimum: 1000,
+            }
+            .to_string(),
+            "That gives 480 RPM, below the minimum of 1000 RPM"
+        );
+    }
+
+    #[test]
+    fn plain_digits_parse_in_every_language() {
+        assert_eq!(parse("12000", Language::English), Ok(12000));
+        assert_eq!(parse("12000", Language::Spanish), Ok(12000));
+    }
+
+    #[test]
+    fn thousands_grouped_the_languages_way_parse() {
+        assert_eq!(parse("12,000", Language::English), Ok(12000));
+        assert_eq!(parse("12.000", Language::Spanish), Ok(12000));
+    }
+
+    #[test]
+    fn thousands_grouped_the_other_way_are_refused_with_a_hint() {
+        assert_eq!(
+            parse("12.000", Language::English),
+            Err(SpeedError::WrongSeparator {
+                used: '.',
+                speed: 12000,
+                expected: ',',
+            })
+        );
+        assert_eq!(
+            parse("12,000", Language::Spanish),
+            Err(SpeedError::WrongSeparator {
+                used: ',',
+                speed: 12000,
+                expected: '.',
+            })
+        );
+    }
+
+    #[test]
+    fn badly_grouped_thousands_are_not_a_number() {
+        for input in ["12,00", "1,2000", ",000", "12,000,", "12.5"] {
+            assert_eq!(
+                parse(input, Language::English),
+                Err(SpeedError::NotANumber),
+                "{}",
+                input
+            );
+        }
+    }
+}
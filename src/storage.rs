@@ -0,0 +1,192 @@
+//! The check before a run writes anything: every folder it writes to has to take a new
+//! file and have room for what the run could put there. A read-only share or a full disk
+//! then refuses the run up front instead of failing it halfway, with some files changed
+//! and others not.
+
+use crate::mapping::SpeedPlan;
+use crate::scan::FileInfo;
+use crate::update::RunOptions;
+use crate::SpindleSpeedUpdaterError;
+use std::collections::{BTreeSet, HashMap};
+use std::io;
+use std::path::{Path, PathBuf};
+use tracing::warn;
+
+/// Room left over on top of the estimate, for the filesystem's own bookkeeping.
+const SPARE_BYTES: u64 = 1 << 20;
+
+/// Checks every folder the run would write to. Which files change isn't known until they
+/// are read, so the estimate assumes every targeted file does: a temporary copy of the
+/// largest file for an in-place write, plus a backup of each file when backups are kept,
+/// or all of them in an output folder. Folders on the same disk are each held to the
+/// whole estimate rather than sharing it.
+pub async fn check_storage(
+    plan: &SpeedPlan,
+    options: &RunOptions,
+    file_cache: &HashMap<PathBuf, FileInfo>,
+) -> Result<(), SpindleSpeedUpdaterError> {
+    let mut folders = BTreeSet::new();
+    let mut total: u64 = 0;
+    let mut largest: u64 = 0;
+    for (path, info) in file_cache {
+        if plan.speed_for(path).is_none() {
+            continue;
+        }
+        // The size from the scan or preview; a file that has changed since is skipped by
+        // the run anyway.
+        total += info.size;
+        largest = largest.max(info.size);
+        let written = match &options.output_folder {
+            Some(output) => output.destination(path, &info.root),
+            None => path.clone(),
+        };
+        if let Some(folder) = written.parent() {
+            folders.insert(nearest_existing(folder).to_path_buf());
+        }
+    }
+
+    let needed = SPARE_BYTES
+        + match &options.output_folder {
+            Some(_) => total,
+            None if options.backs_up() => total + largest,
+            None => largest,
+        };
+    for folder in &folders {
+        if let Err(error) = write_canary(folder).await {
+            return Err(SpindleSpeedUpdaterError::StorageCheck(format!(
+                "{} can't be written to ({}); no file was changed",
+                folder.display(),
+                error
+            )));
+        }
+        match available_space(folder) {
+            Ok(free) if free < needed => {
+                return Err(SpindleSpeedUpdaterError::StorageCheck(format!(
+                    "{} has {} free, but the run may need {}; no file was changed",
+                    folder.display(),
+                    megabytes(free),
+                    megabytes(needed)
+                )))
+            }
+            Ok(_) => {}
+            Err(error) => warn!("Couldn't tell the free space at {:?}: {}", folder, error),
+        }
+    }
+    Ok(())
+}
+
+/// `folder`, or the closest folder above it that exists, for an output folder that the
+/// run will create.
+fn nearest_existing(folder: &Path) -> &Path {
+    folder
+        .ancestors()
+        .find(|ancestor| ancestor.is_dir())
+        .unwrap_or(folder)
+}
+
+/// Writes a small hidden file to `folder` and removes it again.
+async fn write_canary(folder: &Path) -> io::Result<()> {
+    let canary = folder.join(format!(".spindle-canary-{}", std::process::id()));
+    tokio::fs::write(&canary, b"canary").await?;
+    tokio::fs::remove_file(&canary).await
+}
+
+fn megabytes(bytes: u64) -> String {
+    format!("{:.1} MB", bytes as f64 / 1_000_000.0)
+}
+
+/// Bytes this user may still write on the disk holding `path`.
+#[cfg(unix)]
+fn available_space(path: &Path) -> io::Result<u64> {
+    use std::os::unix::ffi::OsStrExt;
+
+    let path = std::ffi::CString::new(path.as_os_str().as_bytes())
+        .map_err(|error| io::Error::new(io::ErrorKind::InvalidInput, error))?;
+    // SAFETY: `path` is NUL-terminated and `stats` is a plain struct statvfs fills in.
+    let mut stats: libc::statvfs = unsafe { std::mem::zeroed() };
+    if unsafe { libc::statvfs(path.as_ptr(), &mut stats) } != 0 {
+        return Err(io::Error::last_os_error());
+    }
+    #[allow(clippy::useless_conversion)]
+    Ok(u64::from(stats.f_bavail) * u64::from(stats.f_frsize))
+}
+
+/// Bytes this user may still write on the disk holding `path`.
+#[cfg(windows)]
+fn available_space(path: &Path) -> io::Result<u64> {
+    use std::os::windows::ffi::OsStrExt;
+
+    let wide: Vec<u16> = path.as_os_str().encode_wide().chain(Some(0)).collect();
+    // SAFETY: `wide` is NUL-terminated and `available` is a plain integer it fills in.
+    let mut available: winapi::um::winnt::ULARGE_INTEGER = unsafe { std::mem::zeroed() };
+    let succeeded = unsafe {
+        winapi::um::fileapi::GetDiskFreeSpaceExW(
+            wide.as_ptr(),
+            &mut available,
+            std::ptr::null_mut(),
+            std::ptr::null_mut(),
+        )
+    };
+    if succeeded == 0 {
+        return Err(io::Error::last_os_error());
+    }
+    Ok(unsafe { *available.QuadPart() })
+}
+
+#[cfg(not(any(unix, windows)))]
+fn available_space(_path: &Path) -> io::Result<u64> {
+    Err(io::Error::new(
+        io::ErrorKind::Unsupported,
+        "no free-space query on this platform",
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::progress::Progress;
+    use crate::project::ProjectRules;
+    use crate::scan::scan_folders;
+    use crate::update::update_files;
+    use std::sync::Arc;
+
+    /// Two folders of programs to change; the second can't take new files because a
+    /// folder sits where the canary would be written.
+    #[tokio::test]
+    async fn unwritable_folder_refuses_the_run_before_any_file_changes() {
+        let dir = tempfile::tempdir().unwrap();
+        let writable = dir.path().join("writable");
+        let blocked = dir.path().join("blocked");
+        for folder in [&writable, &blocked] {
+            std::fs::create_dir(folder).unwrap();
+            std::fs::write(folder.join("part.tap"), "S1000 M3\n").unwrap();
+        }
+        std::fs::create_dir(blocked.join(format!(".spindle-canary-{}", std::process::id())))
+            .unwrap();
+        let roots = [writable.clone(), blocked.clone()];
+        let (file_cache, _) = scan_folders(&roots, false, &ProjectRules::default()).unwrap();
+        let (_cancel, cancel_receiver) = tokio::sync::oneshot::channel();
+
+        let result = update_files(
+            "run1",
+            &SpeedPlan::Uniform(12000),
+            RunOptions::default(),
+            Arc::new(Progress::default()),
+            &file_cache,
+            cancel_receiver,
+            None,
+        )
+        .await;
+
+        match result {
+            Err(SpindleSpeedUpdaterError::StorageCheck(message)) => {
+                assert!(message.contains("no file was changed"), "{}", message);
+            }
+            other => panic!("expected a storage check failure, got {:?}", other.err()),
+        }
+        for folder in [&writable, &blocked] {
+            let content = std::fs::read_to_string(folder.join("part.tap")).unwrap();
+            assert_eq!(content, "S1000 M3\n");
+        }
+    }
+}
@@ -0,0 +1,1227 @@
+//! Text shown in the window, looked up by [`Language`] so a translation is one more table
+//! here rather than a change to the UI code. Log messages stay in English for support.
+
+use serde::{Deserialize, Serialize};
+use std::fmt::Display;
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Language {
+    #[default]
+    English,
+    Spanish,
+}
+
+impl Language {
+    pub const ALL: [Language; 2] = [Language::English, Language::Spanish];
+
+    /// Each language's own name, so it can be found without reading the current one.
+    pub fn label(self) -> &'static str {
+        match self {
+            Language::English => "English",
+            Language::Spanish => "Español",
+        }
+    }
+
+    /// Groups the thousands of a typed speed, as in `12,000` or `12.000`.
+    pub fn thousands_separator(self) -> char {
+        match self {
+            Language::English => ',',
+            Language::Spanish => '.',
+        }
+    }
+}
+
+/// One piece of window text. `{}` in a text is a value filled in with [`fill`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Text {
+    AppTitle,
+    FirstFolderPrompt,
+    UseProgramFolder,
+    ProjectConfig,
+    IgnoringProjectConfig,
+    NoFolderSelected,
+    Folder,
+    Browse,
+    AddFolder,
+    PickFiles,
+    BackToFolders,
+    PickedFiles,
+    PickedFile,
+    Scanning,
+    Cancel,
+    ScanCancelled,
+    FilesFound,
+    RecentFiles,
+    DuplicatePaths,
+    SpeedMode,
+    SpeedModeAbsolute,
+    SpeedModePercent,
+    SpeedModeSurface,
+    SpeedPrompt,
+    PercentPrompt,
+    SurfaceSpeedPrompt,
+    DiameterPrompt,
+    ComputedSpeed,
+    EnterSpeed,
+    SpeedOutOfRange,
+    PercentNotPositive,
+    SurfaceSpeedNotPositive,
+    DiameterNotPositive,
+    ComputedBelowMinimum,
+    ComputedAboveMaximum,
+    InvalidSpeed,
+    WrongSeparator,
+    Preview,
+    UpdateSpeeds,
+    ProcessedOf,
+    CurrentFile,
+    Stopping,
+    SelfChecking,
+    ProceedAnyway,
+    RunId,
+    RetryFailed,
+    AuditOnly,
+    RunAudit,
+    ChangeHash,
+    Copy,
+    ClearMessages,
+    WhichLines,
+    Settings,
+    Language,
+    HideSuccessAfter,
+    SuccessCounts,
+    EnterInSpeedField,
+    PathsInLists,
+    SpindleToUpdate,
+    InsertMissing,
+    Insert,
+    InsertAfterMarker,
+    SkipWithoutMarker,
+    TrimRewritten,
+    UppercaseMCodes,
+    PadSpeedWidth,
+    SpindleWordFirst,
+    CheckStructure,
+    ApplySpeedOverride,
+    AddChangeStamp,
+    ChangeStampPlaceholders,
+    ChangeStampNotComment,
+    AfterToolChange,
+    EachToolChange,
+    OnlyCurrentlyAt,
+    FollowSymlinks,
+    StartupSelfCheck,
+    AutoApplyIfClean,
+    CleanSingleChange,
+    CleanAllowUnchanged,
+    CleanAllowNoSpindle,
+    CleanAllowInserted,
+    NotAppliedAutomatically,
+    NotifyOnCompletion,
+    MaxFailurePercent,
+    LimitFiles,
+    Files,
+    OnlyModifiedWithin,
+    Days,
+    RequireSpindleCommand,
+    WarnOnSpeedDrop,
+    OfCurrentSpeed,
+    CheckFolderFingerprint,
+    SentinelFile,
+    ExpectedFileCount,
+    UseCurrentCount,
+    WrongFolder,
+    WrongFolderForProfile,
+    Profile,
+    NoProfile,
+    ProfileName,
+    SaveProfile,
+    DeleteProfile,
+    Advanced,
+    WriteDelay,
+    IoRetries,
+    VerifyWrites,
+    CreateBackups,
+    PreserveMetadata,
+    RunHistory,
+    Change,
+    UseDefault,
+    ResultChunkSize,
+    UpdatedFiles,
+    OutputFolder,
+    NoneChosen,
+    CopyUnchanged,
+    ChooseOutputFolder,
+    OutputOverlapsFolder,
+    WritingToOutput,
+    ConfirmTitle,
+    ConfirmQuestion,
+    ExampleChange,
+    Inserted,
+    OutsideFolders,
+    Yes,
+    No,
+    PressEnter,
+    DialogKeys,
+    YesConsequence,
+    NoConsequence,
+    EnterConfirmsDialog,
+    CloseTitle,
+    CloseQuestion,
+    CancelAndClose,
+    KeepRunning,
+    NoProgramFolder,
+    ScanFailed,
+    PickedFilesCheckFailed,
+    AlreadyPicked,
+    CancelFailed,
+    ClearError,
+    SentinelMissing,
+    FileCountMismatch,
+    TargetSpeedRules,
+    TargetMapping,
+    TargetUnknown,
+    SpeedMapping,
+    NoSpeedMapping,
+    LoadCsv,
+    Clear,
+    UseSpeedRules,
+    UseSpeedRulesHint,
+    WhichRule,
+    RuleApplied,
+    NoRuleApplied,
+    MappedFilesMissing,
+    FilesNotMapped,
+    Auditing,
+    AuditTitle,
+    SaveCsv,
+    SaveAuditHint,
+    NoSpeedWord,
+    AuditWritten,
+    AuditSaveFailed,
+    BuildingPreview,
+    PreviewTitle,
+    PreviewMatched,
+    ExportPatch,
+    ExportPatchHint,
+    LineCountChanges,
+    OutcomeInserted,
+    OutcomeChange,
+    OutcomeUnchanged,
+    OutcomeEmpty,
+    OutcomeNoSpindle,
+    OutcomeNotInMapping,
+    OutcomeNoMatchingRule,
+    OutcomeNotMatched,
+    OutcomeError,
+    PreviewLine,
+    Patch,
+    ViewTitle,
+    CantReadFile,
+    NoSpindleDetected,
+    SpindleLinesDetected,
+    CompareFolders,
+    OldFolder,
+    NewFolder,
+    Compare,
+    ComparisonSummary,
+    OnlyInOld,
+    OnlyInNew,
+    RestoreFromBackups,
+    FindBackedUpRuns,
+    NoBackups,
+    BackedUpRun,
+    RestoringTo,
+    RestoreCopy,
+    RestoreRecreate,
+    RestoreMissing,
+    RestoreQuestion,
+    YesRestore,
+    RestoreFiles,
+    RunLog,
+    LastRunStatus,
+    ClickForDetails,
+    TryIt,
+    TryItHint,
+    TryItSpeed,
+    LinesChanged,
+    LeftUnchanged,
+    ChangesSinceRun,
+    TargetChanged,
+    SameAsPreviousRun,
+    NotInRun,
+    FilesFailed,
+    PatchWritten,
+    PatchExportFailed,
+    ManifestsUnreadable,
+    Restored,
+    RestoreNoCopyLeft,
+    RestoreFailed,
+    MatchedCurrentSpeed,
+    PerFileResults,
+    CompletionNotice,
+    FilesFailedExample,
+    CountChanged,
+    CountProcessed,
+    EnterConfirms,
+    EnterRuns,
+    PathsRelative,
+    PathsAbsolute,
+    OverwriteInPlace,
+    WriteToOutputFolder,
+    AfterFirstMotion,
+    AfterHeader,
+    MainSpindle,
+    IndexedSpindle,
+    AllIndexedSpindles,
+    RpmSuffix,
+    FileRowHint,
+    OpenFile,
+    OpenContainingFolder,
+    CopyFullPath,
+    SummaryUpdated,
+    SummaryUpdatedWith,
+    SummaryProcessed,
+    SummaryChanged,
+    SummaryAlreadyCorrect,
+    SummarySkipped,
+    SummarySkippedBecause,
+    SummaryFailed,
+    PreflightWarning,
+    SpeedDropWarning,
+    FileCapRefusal,
+    MissingSpindleRefusal,
+    AndMore,
+    SelfCheckWarning,
+    NotAFile,
+    CantReadPath,
+    WrongExtension,
+    RuleSpindleLetter,
+    RuleEachToolChange,
+    RuleAfterToolChange,
+    RuleSpeedOverride,
+    RuleIndexedLeftAlone,
+    RuleFirstOnly,
+    RuleLineStartsWithS,
+    RuleFirstSWord,
+    RuleScientific,
+    RuleGluedWords,
+    RuleComments,
+    RuleContinuedBlock,
+    RuleMatchCurrent,
+    RuleTrim,
+    RuleUppercase,
+    RulePadWidth,
+    RuleWordFirst,
+    RuleInsertAfterMarkerOnly,
+    RuleInsertAfterMarker,
+    RuleInsert,
+    RuleMissingSkipped,
+    RuleIndexedTarget,
+    RuleEachIndexed,
+    RuleIndexedFirst,
+    RuleOtherSpindles,
+    RuleIndexedScientific,
+    RuleIndexedNoInsert,
+    RuleIndexedMatchCurrent,
+}
+
+impl Text {
+    pub fn get(self, language: Language) -> &'static str {
+        match language {
+            Language::English => self.english(),
+            Language::Spanish => self.spanish(),
+        }
+    }
+
+    fn english(self) -> &'static str {
+        match self {
+            Text::AppTitle => "Spindle Speed Updater",
+            Text::FirstFolderPrompt => {
+                "Choose the folder with the programs to update. It is remembered for the next \
+                 launch and can be changed at any time with Browse…"
+            }
+            Text::UseProgramFolder => "Use this program's folder",
+            Text::ProjectConfig => "Project config: {}",
+            Text::IgnoringProjectConfig => "Ignoring {} ({}); using the defaults",
+            Text::NoFolderSelected => "No folder selected",
+            Text::Folder => "Folder: {}",
+            Text::Browse => "Browse…",
+            Text::AddFolder => "Add folder…",
+            Text::PickFiles => "Pick files…",
+            Text::BackToFolders => "Back to folders",
+            Text::PickedFiles => "Picked files: {}",
+            Text::PickedFile => "{} in {}",
+            Text::Scanning => "Scanning…",
+            Text::Cancel => "Cancel",
+            Text::ScanCancelled => "Scan cancelled; pick a different folder with Browse…",
+            Text::FilesFound => "{} .{} files found",
+            Text::RecentFiles => "{} of them modified in the last {} days will be processed",
+            Text::DuplicatePaths => {
+                "{} paths lead to files already found and will be processed once"
+            }
+            Text::SpeedMode => "Give the speed as:",
+            Text::SpeedModeAbsolute => "RPM",
+            Text::SpeedModePercent => "percent of the maximum",
+            Text::SpeedModeSurface => "constant surface speed",
+            Text::SpeedPrompt => "Enter the desired spindle speed (RPM):",
+            Text::PercentPrompt => "Percentage of {}:",
+            Text::SurfaceSpeedPrompt => "Surface speed (m/min):",
+            Text::DiameterPrompt => "Diameter (mm):",
+            Text::ComputedSpeed => "= {}",
+            Text::EnterSpeed => "Enter a spindle speed",
+            Text::SpeedOutOfRange => "Spindle speed must be between {} and {} RPM",
+            Text::PercentNotPositive => "The percentage must be more than 0",
+            Text::SurfaceSpeedNotPositive => "The surface speed must be more than 0",
+            Text::DiameterNotPositive => "The diameter must be more than 0",
+            Text::ComputedBelowMinimum => "That gives {} RPM, below the minimum of {} RPM",
+            Text::ComputedAboveMaximum => "That gives {} RPM, above the maximum of {} RPM",
+            Text::InvalidSpeed => "Invalid input. Please enter a valid number",
+            Text::WrongSeparator => {
+                "\"{}\" doesn't group thousands here; enter {} without separators or \
+                 grouped with \"{}\""
+            }
+            Text::Preview => "Preview",
+            Text::UpdateSpeeds => "Update Spindle Speeds",
+            Text::ProcessedOf => "Processed {} of {} files",
+            Text::CurrentFile => "Current file: {}",
+            Text::Stopping => "Stopping after the current file…",
+            Text::SelfChecking => "Checking files for spindle commands…",
+            Text::ProceedAnyway => "Proceed anyway",
+            Text::RunId => "Run ID: {}",
+            Text::RetryFailed => "Retry {} failed or in-use files",
+            Text::AuditOnly => "Audit only: report the current speeds, change nothing",
+            Text::RunAudit => "Run Audit",
+            Text::ChangeHash => "Change hash: {}",
+            Text::Copy => "Copy",
+            Text::ClearMessages => "Clear Messages",
+            Text::WhichLines => "Which lines are updated?",
+            Text::Settings => "Settings",
+            Text::Language => "Language",
+            Text::HideSuccessAfter => "Hide success message after (s, 0 = never):",
+            Text::SuccessCounts => "Success message counts",
+            Text::EnterInSpeedField => "Enter in the speed field",
+            Text::PathsInLists => "Paths in lists",
+            Text::SpindleToUpdate => "Spindle to update",
+            Text::InsertMissing => "Insert a spindle command into files without one",
+            Text::Insert => "Insert",
+            Text::InsertAfterMarker => "Insert after the line containing",
+            Text::SkipWithoutMarker => "Skip files without the marker",
+            Text::TrimRewritten => "Remove trailing spaces from rewritten lines",
+            Text::UppercaseMCodes => "Write m3/m4 on rewritten lines as M3/M4",
+            Text::PadSpeedWidth => "Pad the new speed with zeros to the old width (S01000)",
+            Text::SpindleWordFirst => "Write the speed before M3/M4 (M3 S1000 becomes S1000 M3)",
+            Text::CheckStructure => "Fail any file whose update would change more than S lines",
+            Text::ApplySpeedOverride => "Scale the speed by the percentage in comments with",
+            Text::AddChangeStamp => "Stamp changed files with",
+            Text::ChangeStampPlaceholders => {
+                "{speed} and {date} are filled in; an earlier stamp is replaced, not repeated."
+            }
+            Text::ChangeStampNotComment => {
+                "The stamp has to be a comment, (...) or ; ..., so files won't be stamped."
+            }
+            Text::AfterToolChange => "Only update spindle commands after the first tool change",
+            Text::EachToolChange => "Update the first spindle command after every M6",
+            Text::OnlyCurrentlyAt => "Only update files currently at",
+            Text::FollowSymlinks => "Follow symbolic links",
+            Text::StartupSelfCheck => "Check files for a spindle command on startup",
+            Text::AutoApplyIfClean => "Update without confirming when the dry pass is clean:",
+            Text::CleanSingleChange => "exactly one spindle line changes in each file",
+            Text::CleanAllowUnchanged => "files already at the speed are fine",
+            Text::CleanAllowNoSpindle => "files without an S command are fine",
+            Text::CleanAllowInserted => "inserting an S command is fine",
+            Text::NotAppliedAutomatically => "Not applied automatically: {}",
+            Text::NotifyOnCompletion => {
+                "Show a desktop notification with a sound, flash the taskbar and show the \
+                 outcome in the title when a run finishes in the background"
+            }
+            Text::MaxFailurePercent => "Refuse to run when more than this % of files would fail:",
+            Text::LimitFiles => "Refuse runs that would modify more than",
+            Text::Files => "files",
+            Text::OnlyModifiedWithin => "Only process files modified in the last",
+            Text::Days => "days",
+            Text::RequireSpindleCommand => "Refuse runs when any file has no S command",
+            Text::WarnOnSpeedDrop => "Ask before most files drop below",
+            Text::OfCurrentSpeed => "of their current speed",
+            Text::CheckFolderFingerprint => "Check that this is the right folder",
+            Text::SentinelFile => "File that must be there:",
+            Text::ExpectedFileCount => "Expected files (0 = any):",
+            Text::UseCurrentCount => "Use current",
+            Text::WrongFolder => "THIS MAY BE THE WRONG FOLDER:\n{}",
+            Text::WrongFolderForProfile => "The folder doesn't match profile \"{}\"",
+            Text::Profile => "Profile:",
+            Text::NoProfile => "(none)",
+            Text::ProfileName => "profile name",
+            Text::SaveProfile => "Save settings as profile",
+            Text::DeleteProfile => "Delete profile",
+            Text::Advanced => "Advanced",
+            Text::WriteDelay => "Delay between file writes (ms):",
+            Text::IoRetries => "Retries after a transient IO error:",
+            Text::VerifyWrites => "Read back every written file to verify it (doubles IO)",
+            Text::CreateBackups => "Back up each file before changing it",
+            Text::PreserveMetadata => "Keep each file's modified time and permissions",
+            Text::RunHistory => "Run history: {}",
+            Text::Change => "Change…",
+            Text::UseDefault => "Default",
+            Text::ResultChunkSize => "Write per-file results every N files (0 = off):",
+            Text::UpdatedFiles => "Updated files",
+            Text::OutputFolder => "Output folder: {}",
+            Text::NoneChosen => "none chosen",
+            Text::CopyUnchanged => "Copy files that need no change there too",
+            Text::ChooseOutputFolder => "Choose an output folder in Settings first",
+            Text::OutputOverlapsFolder => {
+                "The output folder {} overlaps the selected folder {}; choose one outside it"
+            }
+            Text::WritingToOutput => "Updated files go to {}; the originals stay as they are",
+            Text::ConfirmTitle => "Confirm Update",
+            Text::ConfirmQuestion => {
+                "Are you sure you want to update the spindle speed to {} in all .tap files?"
+            }
+            Text::ExampleChange => "Example change in {}: {} \u{2192} {}",
+            Text::Inserted => "(inserted)",
+            Text::OutsideFolders => "This modifies files outside the program's folder:\n{}",
+            Text::Yes => "Yes",
+            Text::No => "No",
+            Text::PressEnter => "Press Enter to confirm",
+            Text::DialogKeys => "Tab or the arrow keys to choose, Enter to select, Esc to cancel",
+            Text::YesConsequence => "Yes, update the files to {}",
+            Text::NoConsequence => "No, cancel without changing any file",
+            Text::EnterConfirmsDialog => "Enter in the confirmation dialog always means Yes",
+            Text::CloseTitle => "Update in progress",
+            Text::CloseQuestion => {
+                "Closing now could interrupt a file being written. Cancel the update and \
+                 close once the current file is finished?"
+            }
+            Text::CancelAndClose => "Cancel update and close",
+            Text::KeepRunning => "Keep running",
+            Text::NoProgramFolder => "Couldn't determine the program folder; pick one with Browse…",
+            Text::ScanFailed => "Failed to scan folder: {}",
+            Text::PickedFilesCheckFailed => "Failed to check the picked files: {}",
+            Text::AlreadyPicked => "Already picked: {}",
+            Text::CancelFailed => "Failed to cancel operation: {}",
+            Text::ClearError => "Clear Error",
+            Text::SentinelMissing => "{} has no {}",
+            Text::FileCountMismatch => "{} files found, {} expected",
+            Text::TargetSpeedRules => "the speed rules in {}",
+            Text::TargetMapping => "the speeds listed in {}",
+            Text::TargetUnknown => "an unknown speed",
+            Text::SpeedMapping => "Speed mapping: {} ({} files)",
+            Text::NoSpeedMapping => "Speed mapping: none",
+            Text::LoadCsv => "Load CSV…",
+            Text::Clear => "Clear",
+            Text::UseSpeedRules => "Use the {} speed rules in {}",
+            Text::UseSpeedRulesHint => {
+                "Each file gets the speed of the first rule matching its name"
+            }
+            Text::WhichRule => "Which rule applies to each file",
+            Text::RuleApplied => "rule {} ({}, {})",
+            Text::NoRuleApplied => "no matching rule (skipped)",
+            Text::MappedFilesMissing => "{} mapped files not found",
+            Text::FilesNotMapped => "{} files not in the mapping (skipped)",
+            Text::Auditing => "Auditing…",
+            Text::AuditTitle => "Audit: {} of {} files flagged",
+            Text::SaveCsv => "Save CSV…",
+            Text::SaveAuditHint => "Save every file's current speeds and anomalies",
+            Text::NoSpeedWord => "no S",
+            Text::AuditWritten => "Audit written to {}",
+            Text::AuditSaveFailed => "Failed to save the audit: {}",
+            Text::BuildingPreview => "Building preview…",
+            Text::PreviewTitle => "Preview: {} of {} files would change",
+            Text::PreviewMatched => ", {} currently at {}",
+            Text::ExportPatch => "Export patch…",
+            Text::ExportPatchHint => "Save the changes as a unified diff for review",
+            Text::LineCountChanges => "{} files would change line count",
+            Text::OutcomeInserted => "S command inserted",
+            Text::OutcomeChange => "would change",
+            Text::OutcomeUnchanged => "already correct",
+            Text::OutcomeEmpty => "empty",
+            Text::OutcomeNoSpindle => "no S command",
+            Text::OutcomeNotInMapping => "not in mapping",
+            Text::OutcomeNoMatchingRule => "no matching rule",
+            Text::OutcomeNotMatched => "current speed doesn't match",
+            Text::OutcomeError => "error: {}",
+            Text::PreviewLine => "{}: {} \u{2192} {} lines, {} \u{2192} {} bytes ({})",
+            Text::Patch => "Patch",
+            Text::ViewTitle => "View: {}",
+            Text::CantReadFile => "Can't read the file: {}",
+            Text::NoSpindleDetected => "No spindle command detected",
+            Text::SpindleLinesDetected => "{} spindle lines detected, highlighted below",
+            Text::CompareFolders => "Compare folders",
+            Text::OldFolder => "Old: {}",
+            Text::NewFolder => "New: {}",
+            Text::Compare => "Compare",
+            Text::ComparisonSummary => "{} files differ, {} match, {} only in old, {} only in new",
+            Text::OnlyInOld => "{}: only in old",
+            Text::OnlyInNew => "{}: only in new",
+            Text::RestoreFromBackups => "Restore from backups",
+            Text::FindBackedUpRuns => "Find backed-up runs",
+            Text::NoBackups => "No backups in the selected folders",
+            Text::BackedUpRun => "{} ({} files)",
+            Text::RestoringTo => "Restoring to before run {}:",
+            Text::RestoreCopy => "{}: copy from run {}",
+            Text::RestoreRecreate => "{}: deleted since, recreated from run {}",
+            Text::RestoreMissing => "{}: its copy is gone, left as it is",
+            Text::RestoreQuestion => {
+                "Put {} files back the way they were before run {}? Their current contents are \
+                 backed up first."
+            }
+            Text::YesRestore => "Yes, restore",
+            Text::RestoreFiles => "Restore {} files…",
+            Text::RunLog => "Run log ({} lines)",
+            Text::LastRunStatus => {
+                "Last run: {} files at the target speed ({} changed, {} already correct), {} \
+                 skipped, {} failed."
+            }
+            Text::ClickForDetails => "{} Click for the details.",
+            Text::TryIt => "Try it on a snippet",
+            Text::TryItHint => {
+                "Paste a few lines of G-code. Nothing is read from or written to disk."
+            }
+            Text::TryItSpeed => "Speed:",
+            Text::LinesChanged => "{} lines changed:",
+            Text::LeftUnchanged => "Left unchanged: {}",
+            Text::ChangesSinceRun => "Changes since run {} ({} files)",
+            Text::TargetChanged => "Target: {} \u{2192} {}",
+            Text::SameAsPreviousRun => "Every file came out the same as in the previous run.",
+            Text::NotInRun => "not in run",
+            Text::FilesFailed => "{} files could not be updated",
+            Text::PatchWritten => "Patch written to {}",
+            Text::PatchExportFailed => "Failed to export patch: {}",
+            Text::ManifestsUnreadable => "Failed to read the backup manifests: {}",
+            Text::Restored => {
+                "Restored {} files and recreated {}; the replaced versions are backed up as run {}"
+            }
+            Text::RestoreNoCopyLeft => ". {} files had no copy left",
+            Text::RestoreFailed => "{} files couldn't be restored:\n{}",
+            Text::MatchedCurrentSpeed => " {} files matched the current speed {}.",
+            Text::PerFileResults => "Per-file results: {}",
+            Text::CompletionNotice => "Done: {} changed, {} failed",
+            Text::FilesFailedExample => "{} files could not be updated, e.g. {}: {}",
+            Text::CountChanged => "files changed",
+            Text::CountProcessed => "files processed",
+            Text::EnterConfirms => "asks for confirmation",
+            Text::EnterRuns => "starts the run",
+            Text::PathsRelative => "relative to the folder",
+            Text::PathsAbsolute => "absolute",
+            Text::OverwriteInPlace => "overwrite in place",
+            Text::WriteToOutputFolder => "write to an output folder",
+            Text::AfterFirstMotion => "after the first G0-G3 block",
+            Text::AfterHeader => "after the program header",
+            Text::MainSpindle => "S (single spindle)",
+            Text::IndexedSpindle => "S{}= only",
+            Text::AllIndexedSpindles => "every S<n>= spindle",
+            Text::RpmSuffix => " RPM",
+            Text::FileRowHint => "Click to view, right-click for more",
+            Text::OpenFile => "Open file",
+            Text::OpenContainingFolder => "Open containing folder",
+            Text::CopyFullPath => "Copy full path",
+            Text::SummaryUpdated => "Updated {} files to {}.",
+            Text::SummaryUpdatedWith => "Updated {} files to {}; {}.",
+            Text::SummaryProcessed => "Processed {} files for {}: {}.",
+            Text::SummaryChanged => "{} changed",
+            Text::SummaryAlreadyCorrect => "{} already correct",
+            Text::SummarySkipped => "{} skipped",
+            Text::SummarySkippedBecause => "{} skipped ({})",
+            Text::SummaryFailed => "{} failed",
+            Text::PreflightWarning => {
+                "{} of {} files ({}%) have no detectable S command, can't be read or would \
+                 change line count, above the {}% limit. Check the folder and the preview \
+                 before proceeding."
+            }
+            Text::SpeedDropWarning => {
+                "{} of {} files would drop below {}% of their current speed, e.g. {}: {} → {}. \
+                 Check the speed for a missing zero before proceeding."
+            }
+            Text::FileCapRefusal => {
+                "{} files would be modified, more than the limit of {} per run. Nothing was \
+                 written; narrow the folder or the filter, or raise the limit."
+            }
+            Text::MissingSpindleRefusal => {
+                "{} files have no S command, which usually means a bad post. Nothing was \
+                 written:"
+            }
+            Text::AndMore => "…and {} more",
+            Text::SelfCheckWarning => {
+                "Self-check: {} of {} files have no detectable S command or can't be read, \
+                 e.g. {}"
+            }
+            Text::NotAFile => "Not a file: {}",
+            Text::CantReadPath => "Can't read {}: {}",
+            Text::WrongExtension => "Not a .{} file: {}",
+            Text::RuleSpindleLetter => {
+                "The spindle word is addressed by {} on this controller; read S below as {}."
+            }
+            Text::RuleEachToolChange => {
+                "The first spindle word after each M6 tool change is updated, in place of the \
+                 first in the file; later ones up to the next M6 are left alone. Words before \
+                 the first M6 are left alone and files without an M6 are skipped."
+            }
+            Text::RuleAfterToolChange => {
+                "Spindle words before the first tool change (a T word or M6) are left alone; \
+                 files without a tool change are skipped."
+            }
+            Text::RuleSpeedOverride => {
+                "A comment containing \"{}\" followed by a percentage (e.g. \"({} 80%)\") \
+                 scales the speed for that file, rounded to whole RPM; files without one get \
+                 the speed as entered. A percentage that can't be read, that rounds the speed \
+                 to S0, or that scales it outside {} to {} RPM, skips the file."
+            }
+            Text::RuleIndexedLeftAlone => {
+                "Indexed spindle words such as \"S1=1000\" belong to a multi-spindle machine \
+                 and are left alone."
+            }
+            Text::RuleFirstOnly => {
+                "Only the first spindle command in a file is updated; later ones are left \
+                 alone."
+            }
+            Text::RuleLineStartsWithS => {
+                "A line that starts with S is rewritten as \"S<speed>\" followed by its other \
+                 words, such as M42 or its M3 or M4 (in either case), and its comment; {} is \
+                 added when it has no M3 or M4."
+            }
+            Text::RuleFirstSWord => {
+                "Elsewhere, the first word made of S and a number (e.g. \"N10 M3 S8000\") has \
+                 only its number replaced; the rest of the line is kept. A space between S and \
+                 the number (\"S 8000\") is accepted."
+            }
+            Text::RuleScientific => {
+                "Numbers in scientific notation (\"S1.0E3\") are not rewritten; the file is \
+                 skipped."
+            }
+            Text::RuleGluedWords => {
+                "Words are separated by spaces or tabs, so an S glued to another word \
+                 (\"M3S8000\") is not recognised."
+            }
+            Text::RuleComments => "Anything after \"(\" or \";\" is a comment and is ignored.",
+            Text::RuleContinuedBlock => {
+                "A file whose spindle line follows a lone block number (\"N30\") or a line \
+                 ending in \"&\" or \"\\\" is skipped, since the S word may belong to the \
+                 block before it."
+            }
+            Text::RuleMatchCurrent => {
+                "Only files whose first spindle command is currently S{} are updated; all \
+                 others, including files without one, are skipped."
+            }
+            Text::RuleTrim => {
+                "Trailing spaces are removed from the rewritten line only; other lines keep \
+                 theirs."
+            }
+            Text::RuleUppercase => {
+                "An \"m3\" or \"m4\" on the rewritten line is written as M3 or M4."
+            }
+            Text::RulePadWidth => {
+                "The new speed is padded with zeros to as many digits as the old one had \
+                 (\"S01000\" becomes \"S01200\"); a longer speed is written in full."
+            }
+            Text::RuleWordFirst => {
+                "A spindle word after the M3 or M4 of its line is moved in front of it \
+                 (\"M3 {}1000\" becomes \"{}<speed> M3\"); the other words stay where they \
+                 are."
+            }
+            Text::RuleInsertAfterMarkerOnly => {
+                "Files without a spindle command get an \"S<speed> {}\" line inserted after \
+                 the first line containing \"{}\"; files without that line are skipped."
+            }
+            Text::RuleInsertAfterMarker => {
+                "Files without a spindle command get an \"S<speed> {}\" line inserted after \
+                 the first line containing \"{}\", or {} when there is none."
+            }
+            Text::RuleInsert => {
+                "Files without a spindle command get an \"S<speed> {}\" line inserted {}."
+            }
+            Text::RuleMissingSkipped => "Files without a spindle command are skipped.",
+            Text::RuleIndexedTarget => "\"S{}=\"",
+            Text::RuleEachIndexed => "each indexed spindle (\"S1=\", \"S2=\", ...)",
+            Text::RuleIndexedFirst => {
+                "Only the first word for {} in a file has its number replaced, e.g. \
+                 \"S1=8000\" becomes \"S1=<speed>\"; the rest of the line is kept."
+            }
+            Text::RuleOtherSpindles => "Other spindles and bare S words are left alone.",
+            Text::RuleIndexedScientific => {
+                "Numbers in scientific notation are not rewritten; the file is skipped."
+            }
+            Text::RuleIndexedNoInsert => {
+                "Files without a matching spindle word are skipped; nothing is inserted."
+            }
+            Text::RuleIndexedMatchCurrent => {
+                "Only files whose first targeted spindle word is currently at {} are updated."
+            }
+        }
+    }
+
+    fn spanish(self) -> &'static str {
+        match self {
+            Text::AppTitle => "Actualizador de velocidad del husillo",
+            Text::FirstFolderPrompt => {
+                "Elija la carpeta con los programas a actualizar. Se recordará para el próximo \
+                 inicio y se puede cambiar en cualquier momento con Examinar…"
+            }
+            Text::UseProgramFolder => "Usar la carpeta de este programa",
+            Text::ProjectConfig => "Configuración del proyecto: {}",
+            Text::IgnoringProjectConfig => "Se ignora {} ({}); se usan los valores por defecto",
+            Text::NoFolderSelected => "No hay ninguna carpeta seleccionada",
+            Text::Folder => "Carpeta: {}",
+            Text::Browse => "Examinar…",
+            Text::AddFolder => "Añadir carpeta…",
+            Text::PickFiles => "Elegir archivos…",
+            Text::BackToFolders => "Volver a las carpetas",
+            Text::PickedFiles => "Archivos elegidos: {}",
+            Text::PickedFile => "{} en {}",
+            Text::Scanning => "Buscando archivos…",
+            Text::Cancel => "Cancelar",
+            Text::ScanCancelled => "Búsqueda cancelada; elija otra carpeta con Examinar…",
+            Text::FilesFound => "{} archivos .{} encontrados",
+            Text::RecentFiles => "Se procesarán {} de ellos, modificados en los últimos {} días",
+            Text::DuplicatePaths => {
+                "{} rutas llevan a archivos ya encontrados y se procesarán una sola vez"
+            }
+            Text::SpeedMode => "Indicar la velocidad como:",
+            Text::SpeedModeAbsolute => "RPM",
+            Text::SpeedModePercent => "porcentaje del máximo",
+            Text::SpeedModeSurface => "velocidad de corte constante",
+            Text::SpeedPrompt => "Velocidad del husillo deseada (RPM):",
+            Text::PercentPrompt => "Porcentaje de {}:",
+            Text::SurfaceSpeedPrompt => "Velocidad de corte (m/min):",
+            Text::DiameterPrompt => "Diámetro (mm):",
+            Text::ComputedSpeed => "= {}",
+            Text::EnterSpeed => "Introduzca una velocidad del husillo",
+            Text::SpeedOutOfRange => "La velocidad debe estar entre {} y {} RPM",
+            Text::PercentNotPositive => "El porcentaje debe ser mayor que 0",
+            Text::SurfaceSpeedNotPositive => "La velocidad de corte debe ser mayor que 0",
+            Text::DiameterNotPositive => "El diámetro debe ser mayor que 0",
+            Text::ComputedBelowMinimum => "Eso da {} RPM, por debajo del mínimo de {} RPM",
+            Text::ComputedAboveMaximum => "Eso da {} RPM, por encima del máximo de {} RPM",
+            Text::InvalidSpeed => "Entrada no válida. Introduzca un número",
+            Text::WrongSeparator => {
+                "\"{}\" no separa los miles aquí; escriba {} sin separadores o agrupado \
+                 con \"{}\""
+            }
+            Text::Preview => "Vista previa",
+            Text::UpdateSpeeds => "Actualizar velocidades",
+            Text::ProcessedOf => "Procesados {} de {} archivos",
+            Text::CurrentFile => "Archivo actual: {}",
+            Text::Stopping => "Deteniendo después del archivo actual…",
+            Text::SelfChecking => "Buscando comandos de husillo en los archivos…",
+            Text::ProceedAnyway => "Continuar de todos modos",
+            Text::RunId => "ID de ejecución: {}",
+            Text::RetryFailed => "Reintentar {} archivos fallidos o en uso",
+            Text::AuditOnly => {
+                "Solo auditar: informar de las velocidades actuales sin cambiar nada"
+            }
+            Text::RunAudit => "Ejecutar auditoría",
+            Text::ChangeHash => "Hash de cambios: {}",
+            Text::Copy => "Copiar",
+            Text::ClearMessages => "Borrar mensajes",
+            Text::WhichLines => "¿Qué líneas se actualizan?",
+            Text::Settings => "Ajustes",
+            Text::Language => "Idioma",
+            Text::HideSuccessAfter => "Ocultar el mensaje de éxito tras (s, 0 = nunca):",
+            Text::SuccessCounts => "El mensaje de éxito cuenta",
+            Text::EnterInSpeedField => "Intro en el campo de velocidad",
+            Text::PathsInLists => "Rutas en las listas",
+            Text::SpindleToUpdate => "Husillo a actualizar",
+            Text::InsertMissing => "Insertar un comando de husillo en archivos que no lo tienen",
+            Text::Insert => "Insertar",
+            Text::InsertAfterMarker => "Insertar después de la línea que contiene",
+            Text::SkipWithoutMarker => "Omitir archivos sin la marca",
+            Text::TrimRewritten => "Quitar espacios finales de las líneas reescritas",
+            Text::UppercaseMCodes => "Escribir m3/m4 como M3/M4 en las líneas reescritas",
+            Text::SpindleWordFirst => {
+                "Escribir la velocidad antes de M3/M4 (M3 S1000 pasa a S1000 M3)"
+            }
+            Text::CheckStructure => {
+                "Marcar como fallido un archivo cuyo cambio afecte a más que las líneas S"
+            }
+            Text::PadSpeedWidth => {
+                "Rellenar la nueva velocidad con ceros hasta el ancho anterior (S01000)"
+            }
+            Text::ApplySpeedOverride => {
+                "Escalar la velocidad por el porcentaje de los comentarios con"
+            }
+            Text::AddChangeStamp => "Marcar los archivos cambiados con",
+            Text::ChangeStampPlaceholders => {
+                "Se rellenan {speed} y {date}; una marca anterior se reemplaza, no se repite."
+            }
+            Text::ChangeStampNotComment => {
+                "La marca tiene que ser un comentario, (...) o ; ..., así que no se marcarán \
+                 los archivos."
+            }
+            Text::AfterToolChange => {
+                "Actualizar solo comandos de husillo después del primer cambio de herramienta"
+            }
+            Text::EachToolChange => "Actualizar el primer comando de husillo después de cada M6",
+            Text::OnlyCurrentlyAt => "Actualizar solo archivos que ahora están a",
+            Text::FollowSymlinks => "Seguir enlaces simbólicos",
+            Text::StartupSelfCheck => "Buscar un comando de husillo en los archivos al iniciar",
+            Text::AutoApplyIfClean => {
+                "Actualizar sin confirmar cuando la pasada de prueba está limpia:"
+            }
+            Text::CleanSingleChange => "cambia exactamente una línea de husillo por archivo",
+            Text::CleanAllowUnchanged => "se admiten archivos que ya tienen la velocidad",
+            Text::CleanAllowNoSpindle => "se admiten archivos sin comando S",
+            Text::CleanAllowInserted => "se admite insertar un comando S",
+            Text::NotAppliedAutomatically => "No se aplicó automáticamente: {}",
+            Text::NotifyOnCompletion => {
+                "Mostrar una notificación con sonido, hacer parpadear la barra de tareas y \
+                 mostrar el resultado en el título al terminar una ejecución en segundo plano"
+            }
+            Text::MaxFailurePercent => "No ejecutar si fallaría más de este % de archivos:",
+            Text::LimitFiles => "No ejecutar si se modificarían más de",
+            Text::Files => "archivos",
+            Text::OnlyModifiedWithin => "Procesar solo archivos modificados en los últimos",
+            Text::Days => "días",
+            Text::RequireSpindleCommand => "No ejecutar si algún archivo no tiene comando S",
+            Text::WarnOnSpeedDrop => "Preguntar si la mayoría de archivos baja de",
+            Text::OfCurrentSpeed => "de su velocidad actual",
+            Text::CheckFolderFingerprint => "Comprobar que es la carpeta correcta",
+            Text::SentinelFile => "Archivo que debe existir:",
+            Text::ExpectedFileCount => "Archivos esperados (0 = cualquiera):",
+            Text::UseCurrentCount => "Usar el actual",
+            Text::WrongFolder => "PUEDE QUE NO SEA LA CARPETA CORRECTA:\n{}",
+            Text::WrongFolderForProfile => "La carpeta no corresponde al perfil \"{}\"",
+            Text::Profile => "Perfil:",
+            Text::NoProfile => "(ninguno)",
+            Text::ProfileName => "nombre del perfil",
+            Text::SaveProfile => "Guardar la configuración como perfil",
+            Text::DeleteProfile => "Eliminar perfil",
+            Text::Advanced => "Avanzado",
+            Text::WriteDelay => "Pausa entre escrituras de archivos (ms):",
+            Text::IoRetries => "Reintentos tras un error de E/S transitorio:",
+            Text::VerifyWrites => "Releer cada archivo escrito para verificarlo (duplica la E/S)",
+            Text::CreateBackups => "Hacer copia de seguridad de cada archivo antes de cambiarlo",
+            Text::PreserveMetadata => "Conservar la fecha de modificación y los permisos",
+            Text::RunHistory => "Historial de ejecuciones: {}",
+            Text::Change => "Cambiar…",
+            Text::UseDefault => "Por defecto",
+            Text::ResultChunkSize => {
+                "Escribir los resultados por archivo cada N archivos (0 = no):"
+            }
+            Text::UpdatedFiles => "Archivos actualizados",
+            Text::OutputFolder => "Carpeta de salida: {}",
+            Text::NoneChosen => "ninguna elegida",
+            Text::CopyUnchanged => "Copiar allí también los archivos que no necesitan cambios",
+            Text::ChooseOutputFolder => "Elija primero una carpeta de salida en Ajustes",
+            Text::OutputOverlapsFolder => {
+                "La carpeta de salida {} se solapa con la carpeta seleccionada {}; elija una \
+                 fuera de ella"
+            }
+            Text::WritingToOutput => {
+                "Los archivos actualizados van a {}; los originales quedan como están"
+            }
+            Text::ConfirmTitle => "Confirmar actualización",
+            Text::ConfirmQuestion => {
+                "¿Seguro que desea cambiar la velocidad del husillo a {} en todos los archivos \
+                 .tap?"
+            }
+            Text::ExampleChange => "Ejemplo de cambio en {}: {} \u{2192} {}",
+            Text::Inserted => "(insertada)",
+            Text::OutsideFolders => "Esto modifica archivos fuera de la carpeta del programa:\n{}",
+            Text::Yes => "Sí",
+            Text::No => "No",
+            Text::PressEnter => "Pulse Intro para confirmar",
+            Text::DialogKeys => {
+                "Tab o las flechas para elegir, Intro para seleccionar, Esc para cancelar"
+            }
+            Text::YesConsequence => "Sí, actualizar los archivos a {}",
+            Text::NoConsequence => "No, cancelar sin cambiar ningún archivo",
+            Text::EnterConfirmsDialog => "Intro en el diálogo de confirmación siempre significa Sí",
+            Text::CloseTitle => "Actualización en curso",
+            Text::CloseQuestion => {
+                "Cerrar ahora podría interrumpir la escritura de un archivo. ¿Cancelar la \
+                 actualización y cerrar cuando termine el archivo actual?"
+            }
+            Text::CancelAndClose => "Cancelar y cerrar",
+            Text::KeepRunning => "Seguir ejecutando",
+            Text::NoProgramFolder => {
+                "No se pudo determinar la carpeta del programa; elija una con Examinar…"
+            }
+            Text::ScanFailed => "No se pudo examinar la carpeta: {}",
+            Text::PickedFilesCheckFailed => "No se pudieron comprobar los archivos elegidos: {}",
+            Text::AlreadyPicked => "Ya elegido: {}",
+            Text::CancelFailed => "No se pudo cancelar la operación: {}",
+            Text::ClearError => "Borrar error",
+            Text::SentinelMissing => "{} no tiene {}",
+            Text::FileCountMismatch => "{} archivos encontrados, {} esperados",
+            Text::TargetSpeedRules => "las reglas de velocidad de {}",
+            Text::TargetMapping => "las velocidades listadas en {}",
+            Text::TargetUnknown => "una velocidad desconocida",
+            Text::SpeedMapping => "Tabla de velocidades: {} ({} archivos)",
+            Text::NoSpeedMapping => "Tabla de velocidades: ninguna",
+            Text::LoadCsv => "Cargar CSV…",
+            Text::Clear => "Quitar",
+            Text::UseSpeedRules => "Usar las {} reglas de velocidad de {}",
+            Text::UseSpeedRulesHint => {
+                "Cada archivo recibe la velocidad de la primera regla que coincide con su nombre"
+            }
+            Text::WhichRule => "Qué regla se aplica a cada archivo",
+            Text::RuleApplied => "regla {} ({}, {})",
+            Text::NoRuleApplied => "ninguna regla coincide (se omite)",
+            Text::MappedFilesMissing => "{} archivos de la tabla no encontrados",
+            Text::FilesNotMapped => "{} archivos fuera de la tabla (se omiten)",
+            Text::Auditing => "Auditando…",
+            Text::AuditTitle => "Auditoría: {} de {} archivos señalados",
+            Text::SaveCsv => "Guardar CSV…",
+            Text::SaveAuditHint => {
+                "Guardar las velocidades actuales y las anomalías de cada archivo"
+            }
+            Text::NoSpeedWord => "sin S",
+            Text::AuditWritten => "Auditoría guardada en {}",
+            Text::AuditSaveFailed => "No se pudo guardar la auditoría: {}",
+            Text::BuildingPreview => "Preparando la vista previa…",
+            Text::PreviewTitle => "Vista previa: cambiarían {} de {} archivos",
+            Text::PreviewMatched => ", {} ahora a {}",
+            Text::ExportPatch => "Exportar parche…",
+            Text::ExportPatchHint => "Guardar los cambios como diff unificado para revisarlos",
+            Text::LineCountChanges => "{} archivos cambiarían de número de líneas",
+            Text::OutcomeInserted => "orden S insertada",
+            Text::OutcomeChange => "cambiaría",
+            Text::OutcomeUnchanged => "ya correcto",
+            Text::OutcomeEmpty => "vacío",
+            Text::OutcomeNoSpindle => "sin orden S",
+            Text::OutcomeNotInMapping => "fuera de la tabla",
+            Text::OutcomeNoMatchingRule => "ninguna regla coincide",
+            Text::OutcomeNotMatched => "la velocidad actual no coincide",
+            Text::OutcomeError => "error: {}",
+            Text::PreviewLine => "{}: {} \u{2192} {} líneas, {} \u{2192} {} bytes ({})",
+            Text::Patch => "Parche",
+            Text::ViewTitle => "Ver: {}",
+            Text::CantReadFile => "No se puede leer el archivo: {}",
+            Text::NoSpindleDetected => "No se detectó ninguna orden de husillo",
+            Text::SpindleLinesDetected => "{} líneas de husillo detectadas, resaltadas abajo",
+            Text::CompareFolders => "Comparar carpetas",
+            Text::OldFolder => "Anterior: {}",
+            Text::NewFolder => "Nueva: {}",
+            Text::Compare => "Comparar",
+            Text::ComparisonSummary => {
+                "{} archivos difieren, {} coinciden, {} solo en la anterior, {} solo en la nueva"
+            }
+            Text::OnlyInOld => "{}: solo en la anterior",
+            Text::OnlyInNew => "{}: solo en la nueva",
+            Text::RestoreFromBackups => "Restaurar desde las copias",
+            Text::FindBackedUpRuns => "Buscar ejecuciones con copia",
+            Text::NoBackups => "No hay copias en las carpetas seleccionadas",
+            Text::BackedUpRun => "{} ({} archivos)",
+            Text::RestoringTo => "Restaurar a antes de la ejecución {}:",
+            Text::RestoreCopy => "{}: copia de la ejecución {}",
+            Text::RestoreRecreate => "{}: borrado después, se recrea desde la ejecución {}",
+            Text::RestoreMissing => "{}: su copia ya no existe, se deja como está",
+            Text::RestoreQuestion => {
+                "¿Devolver {} archivos al estado anterior a la ejecución {}? Antes se hace una \
+                 copia de su contenido actual."
+            }
+            Text::YesRestore => "Sí, restaurar",
+            Text::RestoreFiles => "Restaurar {} archivos…",
+            Text::RunLog => "Registro de la ejecución ({} líneas)",
+            Text::LastRunStatus => {
+                "Última ejecución: {} archivos a la velocidad deseada ({} cambiados, {} ya \
+                 correctos), {} omitidos, {} con error."
+            }
+            Text::ClickForDetails => "{} Pulse para ver los detalles.",
+            Text::TryIt => "Probar con un fragmento",
+            Text::TryItHint => {
+                "Pegue unas líneas de código G. No se lee ni se escribe nada en el disco."
+            }
+            Text::TryItSpeed => "Velocidad:",
+            Text::LinesChanged => "{} líneas cambiadas:",
+            Text::LeftUnchanged => "Sin cambios: {}",
+            Text::ChangesSinceRun => "Cambios desde la ejecución {} ({} archivos)",
+            Text::TargetChanged => "Velocidad: {} \u{2192} {}",
+            Text::SameAsPreviousRun => {
+                "Todos los archivos quedaron igual que en la ejecución anterior."
+            }
+            Text::NotInRun => "no incluido",
+            Text::FilesFailed => "{} archivos no se pudieron actualizar",
+            Text::PatchWritten => "Parche guardado en {}",
+            Text::PatchExportFailed => "No se pudo exportar el parche: {}",
+            Text::ManifestsUnreadable => "No se pudieron leer los registros de copias: {}",
+            Text::Restored => {
+                "Restaurados {} archivos y recreados {}; las versiones sustituidas tienen copia \
+                 como ejecución {}"
+            }
+            Text::RestoreNoCopyLeft => ". {} archivos ya no tenían copia",
+            Text::RestoreFailed => "{} archivos no se pudieron restaurar:\n{}",
+            Text::MatchedCurrentSpeed => " {} archivos coincidían con la velocidad actual {}.",
+            Text::PerFileResults => "Resultados por archivo: {}",
+            Text::CompletionNotice => "Terminado: {} cambiados, {} con error",
+            Text::FilesFailedExample => "{} archivos no se pudieron actualizar, p. ej. {}: {}",
+            Text::CountChanged => "archivos cambiados",
+            Text::CountProcessed => "archivos procesados",
+            Text::EnterConfirms => "pide confirmación",
+            Text::EnterRuns => "inicia la ejecución",
+            Text::PathsRelative => "relativas a la carpeta",
+            Text::PathsAbsolute => "absolutas",
+            Text::OverwriteInPlace => "sobrescribir en su sitio",
+            Text::WriteToOutputFolder => "escribir en una carpeta de salida",
+            Text::AfterFirstMotion => "tras el primer bloque G0-G3",
+            Text::AfterHeader => "tras la cabecera del programa",
+            Text::MainSpindle => "S (husillo único)",
+            Text::IndexedSpindle => "solo S{}=",
+            Text::AllIndexedSpindles => "todos los husillos S<n>=",
+            Text::RpmSuffix => " RPM",
+            Text::FileRowHint => "Pulse para ver, clic derecho para más",
+            Text::OpenFile => "Abrir archivo",
+            Text::OpenContainingFolder => "Abrir la carpeta que lo contiene",
+            Text::CopyFullPath => "Copiar la ruta completa",
+            Text::SummaryUpdated => "Se actualizaron {} archivos a {}.",
+            Text::SummaryUpdatedWith => "Se actualizaron {} archivos a {}; {}.",
+            Text::SummaryProcessed => "Se procesaron {} archivos para {}: {}.",
+            Text::SummaryChanged => "{} cambiados",
+            Text::SummaryAlreadyCorrect => "{} ya correctos",
+            Text::SummarySkipped => "{} omitidos",
+            Text::SummarySkippedBecause => "{} omitidos ({})",
+            Text::SummaryFailed => "{} con error",
+            Text::PreflightWarning => {
+                "{} de {} archivos ({}%) no tienen un comando S detectable, no se pueden leer o \
+                 cambiarían el número de líneas, por encima del límite del {}%. Revise la \
+                 carpeta y la vista previa antes de continuar."
+            }
+            Text::SpeedDropWarning => {
+                "{} de {} archivos bajarían por debajo del {}% de su velocidad actual, p. ej. \
+                 {}: {} → {}. Compruebe que a la velocidad no le falte un cero antes de \
+                 continuar."
+            }
+            Text::FileCapRefusal => {
+                "Se modificarían {} archivos, más que el límite de {} por ejecución. No se \
+                 escribió nada; acote la carpeta o el filtro, o suba el límite."
+            }
+            Text::MissingSpindleRefusal => {
+                "{} archivos no tienen comando S, lo que suele indicar un postprocesador \
+                 erróneo. No se escribió nada:"
+            }
+            Text::AndMore => "…y {} más",
+            Text::SelfCheckWarning => {
+                "Autocomprobación: {} de {} archivos no tienen un comando S detectable o no se \
+                 pueden leer, p. ej. {}"
+            }
+            Text::NotAFile => "No es un archivo: {}",
+            Text::CantReadPath => "No se puede leer {}: {}",
+            Text::WrongExtension => "No es un archivo .{}: {}",
+            Text::RuleSpindleLetter => {
+                "En este control la palabra del husillo usa la dirección {}; lea S como {} a \
+                 continuación."
+            }
+            Text::RuleEachToolChange => {
+                "Se actualiza la primera palabra del husillo tras cada cambio de herramienta \
+                 M6, en lugar de la primera del archivo; las siguientes hasta el próximo M6 no \
+                 se tocan. Las palabras anteriores al primer M6 no se tocan y los archivos sin \
+                 M6 se omiten."
+            }
+            Text::RuleAfterToolChange => {
+                "Las palabras del husillo anteriores al primer cambio de herramienta (una \
+                 palabra T o M6) no se tocan; los archivos sin cambio de herramienta se omiten."
+            }
+            Text::RuleSpeedOverride => {
+                "Un comentario que contiene \"{}\" seguido de un porcentaje (p. ej. \
+                 \"({} 80%)\") escala la velocidad de ese archivo, redondeada a RPM enteras; \
+                 los archivos sin él reciben la velocidad introducida. Un porcentaje que no se \
+                 puede leer, que redondea la velocidad a S0 o que la lleva fuera de {} a {} \
+                 RPM hace que se omita el archivo."
+            }
+            Text::RuleIndexedLeftAlone => {
+                "Las palabras de husillo indexadas como \"S1=1000\" pertenecen a una máquina \
+                 de varios husillos y no se tocan."
+            }
+            Text::RuleFirstOnly => {
+                "Solo se actualiza el primer comando del husillo de cada archivo; los \
+                 siguientes no se tocan."
+            }
+            Text::RuleLineStartsWithS => {
+                "Una línea que empieza por S se reescribe como \"S<velocidad>\" seguida de sus \
+                 demás palabras, como M42 o su M3 o M4 (en mayúsculas o minúsculas), y su \
+                 comentario; se añade {} cuando no tiene M3 ni M4."
+            }
+            Text::RuleFirstSWord => {
+                "En otros casos, a la primera palabra formada por S y un número (p. ej. \
+                 \"N10 M3 S8000\") solo se le cambia el número; el resto de la línea se \
+                 conserva. Se acepta un espacio entre la S y el número (\"S 8000\")."
+            }
+            Text::RuleScientific => {
+                "Los números en notación científica (\"S1.0E3\") no se reescriben; el \
+                 archivo se omite."
+            }
+            Text::RuleGluedWords => {
+                "Las palabras se separan con espacios o tabuladores, así que una S pegada a \
+                 otra palabra (\"M3S8000\") no se reconoce."
+            }
+            Text::RuleComments => "Todo lo que sigue a \"(\" o \";\" es un comentario y se ignora.",
+            Text::RuleContinuedBlock => {
+                "Se omite un archivo cuya línea del husillo sigue a un número de bloque suelto \
+                 (\"N30\") o a una línea que termina en \"&\" o \"\\\", ya que la palabra \
+                 S puede pertenecer al bloque anterior."
+            }
+            Text::RuleMatchCurrent => {
+                "Solo se actualizan los archivos cuyo primer comando del husillo es ahora \
+                 S{}; todos los demás, incluidos los que no tienen ninguno, se omiten."
+            }
+            Text::RuleTrim => {
+                "Los espacios finales se quitan solo de la línea reescrita; las demás líneas \
+                 conservan los suyos."
+            }
+            Text::RuleUppercase => {
+                "Un \"m3\" o \"m4\" en la línea reescrita se escribe como M3 o M4."
+            }
+            Text::RulePadWidth => {
+                "La nueva velocidad se rellena con ceros hasta tener tantas cifras como la \
+                 anterior (\"S01000\" pasa a \"S01200\"); una velocidad más larga se \
+                 escribe completa."
+            }
+            Text::RuleWordFirst => {
+                "Una palabra del husillo situada tras el M3 o M4 de su línea se mueve delante \
+                 (\"M3 {}1000\" pasa a \"{}<velocidad> M3\"); las demás palabras se quedan \
+                 donde están."
+            }
+            Text::RuleInsertAfterMarkerOnly => {
+                "A los archivos sin comando del husillo se les inserta una línea \
+                 \"S<velocidad> {}\" tras la primera línea que contiene \"{}\"; los \
+                 archivos sin esa línea se omiten."
+            }
+            Text::RuleInsertAfterMarker => {
+                "A los archivos sin comando del husillo se les inserta una línea \
+                 \"S<velocidad> {}\" tras la primera línea que contiene \"{}\", o {} si no \
+                 hay ninguna."
+            }
+            Text::RuleInsert => {
+                "A los archivos sin comando del husillo se les inserta una línea \
+                 \"S<velocidad> {}\" {}."
+            }
+            Text::RuleMissingSkipped => "Los archivos sin comando del husillo se omiten.",
+            Text::RuleIndexedTarget => "\"S{}=\"",
+            Text::RuleEachIndexed => "cada husillo indexado (\"S1=\", \"S2=\", ...)",
+            Text::RuleIndexedFirst => {
+                "Solo a la primera palabra de {} de cada archivo se le cambia el número, p. ej. \
+                 \"S1=8000\" pasa a \"S1=<velocidad>\"; el resto de la línea se conserva."
+            }
+            Text::RuleOtherSpindles => {
+                "Los demás husillos y las palabras S sin índice no se tocan."
+            }
+            Text::RuleIndexedScientific => {
+                "Los números en notación científica no se reescriben; el archivo se omite."
+            }
+            Text::RuleIndexedNoInsert => {
+                "Los archivos sin una palabra de husillo que coincida se omiten; no se inserta \
+                 nada."
+            }
+            Text::RuleIndexedMatchCurrent => {
+                "Solo se actualizan los archivos cuya primera palabra de husillo elegida está \
+                 ahora en {}."
+            }
+        }
+    }
+}
+
+/// `template` with each `{}` replaced by the next of `values`, in order.
+pub fn fill(template: &str, values: &[&dyn Display]) -> String {
+    let mut filled = String::with_capacity(template.len());
+    let mut values = values.iter();
+    let mut pieces = template.split("{}");
+    if let Some(first) = pieces.next() {
+        filled.push_str(first);
+    }
+    for piece in pieces {
+        if let Some(value) = values.next() {
+            filled.push_str(&value.to_string());
+        }
+        filled.push_str(piece);
+    }
+    filled
+}
@@ -0,0 +1,97 @@
+//! The summary lines of a finished run, for the window and the command line.
+
+use crate::report::RunTotals;
+use crate::settings::SuccessCount;
+use crate::strings::{fill, Language, Text};
+use std::collections::BTreeMap;
+use std::path::Path;
+
+/// E.g. "Updated 12 files to 12,000 RPM; 48 already correct." or "Processed 60 files
+/// for 12,000 RPM: 12 changed, 48 already correct." Categories with no files are left out.
+pub fn success_message(
+    totals: &RunTotals,
+    target: &str,
+    count: SuccessCount,
+    language: Language,
+) -> String {
+    let t = |text: Text| text.get(language);
+    let changed = totals.changed;
+    let skipped_files = totals.skipped_files();
+    let skipped = match skip_breakdown(totals) {
+        Some(reasons) => fill(t(Text::SummarySkippedBecause), &[&skipped_files, &reasons]),
+        None => fill(t(Text::SummarySkipped), &[&skipped_files]),
+    };
+    let breakdown: Vec<String> = [
+        (
+            totals.unchanged,
+            fill(t(Text::SummaryAlreadyCorrect), &[&totals.unchanged]),
+        ),
+        (skipped_files, skipped),
+        (
+            totals.failed,
+            fill(t(Text::SummaryFailed), &[&totals.failed]),
+        ),
+    ]
+    .into_iter()
+    .filter(|(files, _)| *files > 0)
+    .map(|(_, part)| part)
+    .collect();
+
+    match count {
+        SuccessCount::Changed if breakdown.is_empty() => {
+            fill(t(Text::SummaryUpdated), &[&changed, &target])
+        }
+        SuccessCount::Changed => fill(
+            t(Text::SummaryUpdatedWith),
+            &[&changed, &target, &breakdown.join(", ")],
+        ),
+        SuccessCount::Processed => {
+            let mut parts = vec![fill(t(Text::SummaryChanged), &[&changed])];
+            parts.extend(breakdown);
+            fill(
+                t(Text::SummaryProcessed),
+                &[&totals.processed(), &target, &parts.join(", ")],
+            )
+        }
+    }
+}
+
+/// Skipped files per [`SkipReason::label`], most common first and ties in name order.
+pub fn skip_counts(totals: &RunTotals) -> Vec<(&'static str, usize)> {
+    let mut counts: Vec<(&'static str, usize)> = totals
+        .skipped
+        .iter()
+        .map(|(&label, &files)| (label, files))
+        .collect();
+    counts.sort_by_key(|&(_, files)| std::cmp::Reverse(files));
+    counts
+}
+
+/// E.g. "3 no S command, 1 read-only", or `None` when nothing was skipped.
+pub fn skip_breakdown(totals: &RunTotals) -> Option<String> {
+    let counts = skip_counts(totals);
+    (!counts.is_empty()).then(|| {
+        counts
+            .iter()
+            .map(|(label, files)| format!("{} {}", files, label))
+            .collect::<Vec<_>>()
+            .join(", ")
+    })
+}
+
+/// Files that passed the current-speed filter: everything it skips is reported as skipped,
+/// so only changed and already-correct files matched.
+pub fn matched_count(totals: &RunTotals) -> usize {
+    totals.changed + totals.unchanged
+}
+
+/// "3 of 5 changed" per root folder, for runs over several folders.
+pub fn summarize_by_root(totals: &RunTotals) -> BTreeMap<&Path, String> {
+    totals
+        .by_root
+        .iter()
+        .map(|(root, (changed, total))| {
+            (root.as_path(), format!("{} of {} changed", changed, total))
+        })
+        .collect()
+}
@@ -0,0 +1,1113 @@
+//! The update run itself: every file read, rewritten, backed up and written atomically,
+//! with what happened to each collected for the report.
+
+use crate::backup::{backup_path, BackupEntry, RunBackups};
+use crate::encoding::read_program_blocking;
+use crate::gcode::{rewrite_content, ContentUpdate, LineChange, SkipReason, UpdateOptions};
+use crate::history::{append_results, history_path, result_row, results_path};
+use crate::mapping::SpeedPlan;
+use crate::progress::Progress;
+use crate::report::{RunResults, RunTotals};
+use crate::retry::{is_in_use, retry_io_blocking};
+use crate::scan::FileInfo;
+use crate::settings::PathDisplay;
+use crate::storage::check_storage;
+use crate::timestamp::UtcDateTime;
+use crate::SpindleSpeedUpdaterError;
+use serde::Serialize;
+use std::collections::hash_map::RandomState;
+use std::collections::HashMap;
+use std::hash::{BuildHasher, Hasher};
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::mpsc::Sender;
+use std::sync::Arc;
+use std::time::{Duration, SystemTime};
+use tokio::sync::oneshot::{self, error::TryRecvError};
+use tracing::{error, info, info_span, warn, Instrument};
+
+/// Chunking of `size` files, 0 for none, with the results going beside the run history
+/// at `history_file`, or its default location.
+pub fn result_chunks(
+    size: usize,
+    history_file: Option<&Path>,
+    path_display: PathDisplay,
+) -> Option<ResultChunks> {
+    if size == 0 {
+        return None;
+    }
+    match history_path(history_file) {
+        Ok(history_file) => Some(ResultChunks {
+            size,
+            history_file,
+            path_display,
+        }),
+        Err(error) => {
+            warn!(
+                "Keeping every result in memory, no run history folder: {}",
+                error
+            );
+            None
+        }
+    }
+}
+
+/// Per-file results written out every `size` files to [`results_path`] beside
+/// `history_file`, so a run over a huge folder only keeps the changed and failed ones.
+#[derive(Clone, Debug)]
+pub struct ResultChunks {
+    pub size: usize,
+    pub history_file: PathBuf,
+    pub path_display: PathDisplay,
+}
+
+/// Options for a whole update run, on top of how each file's text is rewritten.
+#[derive(Clone, Debug, Default)]
+pub struct RunOptions {
+    pub update: UpdateOptions,
+    /// Wait this long after each file that was written. Zero disables the pause.
+    pub write_delay: Duration,
+    /// Extra attempts at a read or write that failed with a transient error.
+    pub io_retries: u32,
+    /// Read each written file back and compare it with what was written.
+    pub verify_writes: bool,
+    /// Copy each file next to itself before rewriting it and list it in the folder's
+    /// backup manifest.
+    pub create_backups: bool,
+    /// Restore the original modified time, and permissions on Unix, after each write.
+    pub preserve_metadata: bool,
+    pub result_chunks: Option<ResultChunks>,
+    /// Write updated files here instead of over the originals. Nothing is backed up
+    /// then, since the originals are never touched.
+    pub output_folder: Option<OutputFolder>,
+}
+
+impl RunOptions {
+    pub fn backs_up(&self) -> bool {
+        self.create_backups && self.output_folder.is_none()
+    }
+
+    pub fn copies_unchanged(&self) -> bool {
+        self.output_folder
+            .as_ref()
+            .is_some_and(|output| output.copy_unchanged)
+    }
+}
+
+/// A folder a run writes its updated files to, laid out like the selected folders.
+#[derive(Clone, Debug)]
+pub struct OutputFolder {
+    pub folder: PathBuf,
+    pub copy_unchanged: bool,
+    /// Put each selected folder's files under a subfolder named after it, so files from
+    /// several folders can't land on the same path.
+    pub nest_by_root: bool,
+}
+
+impl OutputFolder {
+    /// Where `path`, found under `root`, is written: at the same place relative to the
+    /// output folder as it is relative to `root`.
+    pub fn destination(&self, path: &Path, root: &Path) -> PathBuf {
+        let relative = path
+            .strip_prefix(root)
+            .unwrap_or_else(|_| Path::new(path.file_name().unwrap_or_default()));
+        let mut destination = self.folder.clone();
+        if self.nest_by_root {
+            if let Some(name) = root.file_name() {
+                destination.push(name);
+            }
+        }
+        destination.join(relative)
+    }
+}
+
+/// The selected folder that `output` lies in or contains, if any. Writing there would
+/// overwrite the originals or have the next scan pick up the copies.
+pub fn overlapping_folder<'a>(output: &Path, folders: &'a [PathBuf]) -> Option<&'a PathBuf> {
+    let canonical = |path: &Path| path.canonicalize().unwrap_or_else(|_| path.to_path_buf());
+    let output = canonical(output);
+    folders.iter().find(|folder| {
+        let folder = canonical(folder);
+        output.starts_with(&folder) || folder.starts_with(&output)
+    })
+}
+
+/// Identifies one update run in the log: the UTC start time plus a random suffix so
+/// runs started in the same second by different operators stay distinct.
+pub fn new_run_id() -> String {
+    let mut hasher = RandomState::new().build_hasher();
+    if let Ok(elapsed) = SystemTime::now().duration_since(SystemTime::UNIX_EPOCH) {
+        hasher.write_u128(elapsed.as_nanos());
+    }
+    format!(
+        "{}-{:04x}",
+        UtcDateTime::now().compact(),
+        hasher.finish() as u16
+    )
+}
+
+/// What a run did with one file.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize)]
+#[serde(tag = "outcome", content = "detail", rename_all = "lowercase")]
+pub enum FileOutcome {
+    /// Carries the spindle lines before and after, for the run's change hash.
+    Changed(Vec<LineChange>),
+    Unchanged,
+    Skipped(SkipReason),
+    Error(String),
+}
+
+impl FileOutcome {
+    pub fn label(&self) -> &'static str {
+        match self {
+            FileOutcome::Changed(_) => "CHANGED",
+            FileOutcome::Unchanged => "UNCHANGED",
+            FileOutcome::Skipped(_) => "SKIPPED",
+            FileOutcome::Error(_) => "ERROR",
+        }
+    }
+}
+
+#[derive(Clone, Debug, Serialize)]
+pub struct FileResult {
+    pub path: PathBuf,
+    pub root: PathBuf,
+    #[serde(flatten)]
+    pub outcome: FileOutcome,
+}
+
+impl FileResult {
+    /// "updated job12.tap" or "skipped job13.tap: no S command", for the run log.
+    pub fn event_line(&self) -> String {
+        let name = self.path.file_name().unwrap_or_default().to_string_lossy();
+        match &self.outcome {
+            FileOutcome::Changed(_) => format!("updated {}", name),
+            FileOutcome::Unchanged => format!("already correct {}", name),
+            FileOutcome::Skipped(reason) => format!("skipped {}: {}", name, reason),
+            FileOutcome::Error(error) => format!("failed {}: {}", name, error),
+        }
+    }
+}
+
+/// The error ending a cancelled run. Files already written stay written, so the message
+/// says how far the run got.
+pub fn cancelled(
+    totals: &RunTotals,
+    total_files: usize,
+    progress: &Progress,
+) -> SpindleSpeedUpdaterError {
+    if let Err(error) = progress.set_current_file(None) {
+        warn!("{}", error);
+    }
+    info!(
+        "Cancelled after {} of {} files",
+        totals.processed(),
+        total_files
+    );
+    SpindleSpeedUpdaterError::CancelError(format!(
+        "Operation cancelled by user after {} of {} files ({} changed)",
+        totals.processed(),
+        total_files,
+        totals.changed
+    ))
+}
+
+/// Runs [`update_files`] in a `run` span, so every message it logs names the run.
+pub async fn update_spindle_speed(
+    run_id: &str,
+    plan: &SpeedPlan,
+    options: RunOptions,
+    progress: Arc<Progress>,
+    file_cache: &HashMap<PathBuf, FileInfo>,
+    cancel_receiver: oneshot::Receiver<()>,
+    events: Option<Sender<String>>,
+) -> Result<RunResults, SpindleSpeedUpdaterError> {
+    update_files(
+        run_id,
+        plan,
+        options,
+        progress,
+        file_cache,
+        cancel_receiver,
+        events,
+    )
+    .instrument(info_span!("run", id = %run_id))
+    .await
+}
+
+/// Updates every cached file and returns what happened to each one. A file that fails
+/// is recorded as an error and the run carries on; only cancelling or an internal
+/// failure ends it early. With [`RunOptions::result_chunks`] set, results go to the
+/// run's results file as the run goes and only the changed and failed files are kept.
+/// Nothing is written unless [`check_storage`] passes first.
+///
+/// Every backup the run takes goes into the backup manifests once the run ends, however
+/// it ends, so a file that failed after its copy was taken can still be restored.
+pub async fn update_files(
+    run_id: &str,
+    plan: &SpeedPlan,
+    options: RunOptions,
+    progress: Arc<Progress>,
+    file_cache: &HashMap<PathBuf, FileInfo>,
+    mut cancel_receiver: oneshot::Receiver<()>,
+    events: Option<Sender<String>>,
+) -> Result<RunResults, SpindleSpeedUpdaterError> {
+    let mut backups = RunBackups::default();
+    // An async block, so returning early still reaches the manifest save below.
+    let result: Result<RunResults, SpindleSpeedUpdaterError> = async {
+        match plan {
+            SpeedPlan::Uniform(speed) => {
+                info!("update_spindle_speed started with speed: {}", speed)
+            }
+            SpeedPlan::PerFile(speeds) => info!(
+                "update_spindle_speed started with {} mapped speeds",
+                speeds.len()
+            ),
+            SpeedPlan::Rules(rules) => {
+                info!(
+                    "update_spindle_speed started with {} speed rules",
+                    rules.len()
+                )
+            }
+        }
+        let total_files = file_cache.len();
+        info!("Total files to process: {}", total_files);
+        check_storage(plan, &options, file_cache).await?;
+        let chunks = options.result_chunks.as_ref();
+        let mut results = RunResults {
+            files: Vec::with_capacity(if chunks.is_some() { 0 } else { total_files }),
+            results_file: chunks.map(|chunks| results_path(&chunks.history_file, run_id)),
+            ..RunResults::default()
+        };
+        let mut pending_rows = String::new();
+        let mut pending_files = 0;
+
+        progress.start(total_files);
+
+        for (file_path, file_info) in file_cache {
+            // Checked between files rather than raced against them, so a cancel never
+            // abandons a file halfway through being written.
+            if !matches!(cancel_receiver.try_recv(), Err(TryRecvError::Empty)) {
+                flush_results(&results, &mut pending_rows).await?;
+                return Err(cancelled(&results.totals, total_files, &progress));
+            }
+
+            let file_span = info_span!("file", path = %file_path.display());
+            let outcome = match plan.speed_for(file_path) {
+                Some(spindle_speed) => {
+                    progress.set_current_file(Some(file_path))?;
+                    process_file(run_id, file_path, file_info, spindle_speed, &options)
+                        .instrument(file_span.clone())
+                        .await
+                }
+                None => {
+                    let reason = match plan {
+                        SpeedPlan::Rules(_) => SkipReason::NoMatchingRule,
+                        _ => SkipReason::NotInMapping,
+                    };
+                    file_span.in_scope(|| info!("Skipping: {}", reason));
+                    FileOutcome::Skipped(reason)
+                }
+            };
+
+            let changed = matches!(outcome, FileOutcome::Changed(_));
+            if options.backs_up() {
+                // Whatever the outcome, a copy on disk means the file may have been written.
+                let backup = backup_path(file_path, run_id);
+                if tokio::fs::try_exists(&backup).await.unwrap_or(false) {
+                    let entry = BackupEntry {
+                        original: file_path.clone(),
+                        backup,
+                        backed_up_at: UtcDateTime::now().compact(),
+                    };
+                    backups.add(&file_info.root, entry);
+                }
+            }
+            let result = FileResult {
+                path: file_path.clone(),
+                root: file_info.root.clone(),
+                outcome,
+            };
+            if let Some(events) = &events {
+                // The window may already be gone; the run finishes regardless.
+                let _ = events.send(result.event_line());
+            }
+            results.totals.add(&result);
+            match chunks {
+                Some(chunks) => {
+                    pending_rows.push_str(&result_row(&result, chunks.path_display));
+                    pending_files += 1;
+                    if matches!(
+                        result.outcome,
+                        FileOutcome::Changed(_) | FileOutcome::Error(_)
+                    ) {
+                        results.files.push(result);
+                    }
+                    if pending_files == chunks.size {
+                        flush_results(&results, &mut pending_rows).await?;
+                        pending_files = 0;
+                    }
+                }
+                None => results.files.push(result),
+            }
+            progress.increment();
+
+            if changed && !options.write_delay.is_zero() {
+                tokio::select! {
+                    _ = &mut cancel_receiver => {
+                        flush_results(&results, &mut pending_rows).await?;
+                        return Err(cancelled(&results.totals, total_files, &progress));
+                    }
+                    _ = tokio::time::sleep(options.write_delay) => {}
+                }
+            }
+        }
+        flush_results(&results, &mut pending_rows).await?;
+        progress.set_current_file(None)?;
+
+        Ok(results)
+    }
+    .await;
+    if let Err((manifest, error)) = backups.save(run_id).await {
+        error!("Failed to update {:?}: {}", manifest, error);
+        if result.is_ok() {
+            return Err(SpindleSpeedUpdaterError::BackupFailure(format!(
+                "couldn't record the run's backups in {}: {}",
+                manifest.display(),
+                error
+            )));
+        }
+    }
+    result
+}
+
+/// Appends the rows gathered since the last chunk to the run's results file, if it has one.
+pub async fn flush_results(
+    results: &RunResults,
+    pending_rows: &mut String,
+) -> Result<(), SpindleSpeedUpdaterError> {
+    let Some(path) = &results.results_file else {
+        return Ok(());
+    };
+    if pending_rows.is_empty() {
+        return Ok(());
+    }
+    append_results(path, pending_rows)
+        .await
+        .map_err(SpindleSpeedUpdaterError::file_io(path))?;
+    pending_rows.clear();
+    Ok(())
+}
+
+pub async fn process_file(
+    run_id: &str,
+    file_path: &Path,
+    file_info: &FileInfo,
+    spindle_speed: u32,
+    options: &RunOptions,
+) -> FileOutcome {
+    match tokio::fs::metadata(file_path).await {
+        Ok(metadata) if !file_info.matches(&metadata) => {
+            warn!("Skipping: modified since the preview");
+            return FileOutcome::Skipped(SkipReason::ChangedAfterPreview);
+        }
+        Ok(_) => {}
+        Err(error) if error.kind() == io::ErrorKind::NotFound => {
+            warn!("Skipping: deleted since the preview");
+            return FileOutcome::Skipped(SkipReason::ChangedAfterPreview);
+        }
+        Err(error) => {
+            error!("Failed to read: {}", error);
+            return FileOutcome::Error(
+                SpindleSpeedUpdaterError::file_io(file_path)(error).to_string(),
+            );
+        }
+    }
+    // Still empty, so there is nothing to read; empty files are never written.
+    if file_info.size == 0 {
+        return FileOutcome::Skipped(SkipReason::Empty);
+    }
+
+    let backup = options.backs_up().then(|| backup_path(file_path, run_id));
+    let destination = options
+        .output_folder
+        .as_ref()
+        .map(|output| output.destination(file_path, &file_info.root));
+    let outcome = match update_file_spindle_speed(
+        file_path,
+        spindle_speed,
+        options,
+        backup.as_deref(),
+        destination.as_deref(),
+    )
+    .await
+    {
+        Ok(outcome) => outcome,
+        Err(error) => failure_outcome(file_path, error),
+    };
+
+    match &outcome {
+        FileOutcome::Changed(_) => info!("Updated the spindle speed"),
+        FileOutcome::Unchanged => info!("Spindle speed already correct"),
+        FileOutcome::Skipped(reason) => info!("Skipping: {}", reason),
+        FileOutcome::Error(error) => error!("Failed to update: {}", error),
+    }
+
+    outcome
+}
+
+/// A file that couldn't be updated is skipped when another program holds it open, and
+/// fails with its path otherwise.
+fn failure_outcome(file_path: &Path, error: io::Error) -> FileOutcome {
+    if is_in_use(&error) {
+        warn!("In use by another program: {}", error);
+        return FileOutcome::Skipped(SkipReason::InUse);
+    }
+    FileOutcome::Error(SpindleSpeedUpdaterError::file_io(file_path)(error).to_string())
+}
+
+/// A failed backup copy leaves the file alone. When the file is in use that is passed
+/// up, so it is skipped like any other file in use; otherwise just this file fails.
+fn backup_failure(error: io::Error) -> io::Result<FileOutcome> {
+    if is_in_use(&error) {
+        return Err(error);
+    }
+    Ok(FileOutcome::Error(format!("backup failed: {}", error)))
+}
+
+/// Reported when a file read back after writing doesn't hold what was written, e.g.
+/// because another program rewrote it in between.
+pub const VERIFY_MISMATCH: &str =
+    "verification failed: the file on disk differs from what was written";
+
+/// The file-independent half of an update: the outcome for one file's text, plus the new
+/// text when it has to be written.
+pub fn transform_file_content(
+    content: &str,
+    spindle_speed: u32,
+    options: &UpdateOptions,
+) -> (FileOutcome, Option<String>) {
+    match rewrite_content(content, spindle_speed, options) {
+        Ok(ContentUpdate {
+            content,
+            line_changes,
+            ..
+        }) if !line_changes.is_empty() => (FileOutcome::Changed(line_changes), Some(content)),
+        Ok(_) => (FileOutcome::Unchanged, None),
+        Err(reason @ SkipReason::StructureChanged { .. }) => {
+            (FileOutcome::Error(reason.to_string()), None)
+        }
+        Err(reason) => (FileOutcome::Skipped(reason), None),
+    }
+}
+
+/// Runs [`update_file_spindle_speed_blocking`] on tokio's blocking pool, inside the
+/// caller's span, so a run and a caller without a runtime go through the same steps.
+pub async fn update_file_spindle_speed(
+    file_path: &Path,
+    spindle_speed: u32,
+    options: &RunOptions,
+    backup: Option<&Path>,
+    destination: Option<&Path>,
+) -> io::Result<FileOutcome> {
+    let file_path = file_path.to_path_buf();
+    let options = options.clone();
+    let backup = backup.map(Path::to_path_buf);
+    let destination = destination.map(Path::to_path_buf);
+    let span = tracing::Span::current();
+    tokio::task::spawn_blocking(move || {
+        span.in_scope(|| {
+            update_file_spindle_speed_blocking(
+                &file_path,
+                spindle_speed,
+                &options,
+                backup.as_deref(),
+                destination.as_deref(),
+            )
+        })
+    })
+    .await
+    .map_err(io::Error::other)?
+}
+
+/// Reads, rewrites and writes back one file using `std::fs`, with the same retries and
+/// atomic write as a run. `backup`, when given, is where the original is copied before
+/// it is overwritten; the file is left alone if that copy fails. `destination`, when
+/// given, is written instead of the original, which then only gets read.
+/// `options.write_delay` is left to the caller, as it is between files.
+pub fn update_file_spindle_speed_blocking(
+    file_path: &Path,
+    spindle_speed: u32,
+    options: &RunOptions,
+    backup: Option<&Path>,
+    destination: Option<&Path>,
+) -> io::Result<FileOutcome> {
+    let (content, encoding) =
+        match retry_io_blocking(options.io_retries, || read_program_blocking(file_path)) {
+            Err(error) if error.kind() == io::ErrorKind::InvalidData => {
+                return Ok(FileOutcome::Skipped(SkipReason::Binary))
+            }
+            result => result?,
+        };
+
+    let (outcome, new_content) = transform_file_content(&content, spindle_speed, &options.update);
+    let target = destination.unwrap_or(file_path);
+    if let Some(new_content) = new_content {
+        let metadata = std::fs::metadata(file_path)?;
+        if destination.is_none() && metadata.permissions().readonly() {
+            return Ok(FileOutcome::Skipped(SkipReason::ReadOnly));
+        }
+        if let Some(backup) = backup {
+            if let Err(error) =
+                retry_io_blocking(options.io_retries, || std::fs::copy(file_path, backup))
+            {
+                return backup_failure(error);
+            }
+        }
+        if let Some(parent) = destination.and_then(Path::parent) {
+            retry_io_blocking(options.io_retries, || std::fs::create_dir_all(parent))?;
+        }
+        let bytes = encoding.encode(&new_content);
+        retry_io_blocking(options.io_retries, || {
+            write_atomically_blocking(target, &bytes)
+        })?;
+        if options.preserve_metadata {
+            if let Err(error) = restore_metadata(target, &metadata) {
+                return Ok(FileOutcome::Error(format!(
+                    "written, but its metadata couldn't be restored: {}",
+                    error
+                )));
+            }
+        }
+        if options.verify_writes {
+            let on_disk = retry_io_blocking(options.io_retries, || std::fs::read(target))?;
+            if on_disk != bytes {
+                return Ok(FileOutcome::Error(VERIFY_MISMATCH.to_string()));
+            }
+        }
+    } else if let (FileOutcome::Unchanged, Some(destination)) = (&outcome, destination) {
+        if options.copies_unchanged() {
+            if let Some(parent) = destination.parent() {
+                retry_io_blocking(options.io_retries, || std::fs::create_dir_all(parent))?;
+            }
+            retry_io_blocking(options.io_retries, || std::fs::copy(file_path, destination))?;
+            if options.preserve_metadata {
+                restore_metadata(destination, &std::fs::metadata(file_path)?)?;
+            }
+        }
+    }
+    Ok(outcome)
+}
+
+/// Puts back what the rename of an atomic write replaces: the modified time the file had
+/// before, and on Unix its permission bits.
+pub fn restore_metadata(path: &Path, original: &std::fs::Metadata) -> io::Result<()> {
+    #[cfg(unix)]
+    std::fs::set_permissions(path, original.permissions())?;
+    filetime::set_file_mtime(
+        path,
+        filetime::FileTime::from_last_modification_time(original),
+    )
+}
+
+/// Name of the temporary file an atomic write goes through: hidden, next to `path`.
+pub fn atomic_temp_path(path: &Path) -> PathBuf {
+    let mut temp_name = std::ffi::OsString::from(".");
+    temp_name.push(path.file_name().unwrap_or_default());
+    temp_name.push(".spindle-tmp");
+    path.with_file_name(temp_name)
+}
+
+/// Writes to a temporary file next to `path` and renames it over the original, so an
+/// interrupted write leaves the old program intact rather than a truncated one.
+///
+/// A symlink is followed and its target replaced, so the link stays a link, and the
+/// replacement keeps the original file's permissions.
+pub async fn write_atomically(path: &Path, contents: &[u8]) -> io::Result<()> {
+    let path = match tokio::fs::canonicalize(path).await {
+        Ok(target) => target,
+        Err(error) if error.kind() == io::ErrorKind::NotFound => path.to_path_buf(),
+        Err(error) => return Err(error),
+    };
+    let original_permissions = tokio::fs::metadata(&path)
+        .await
+        .ok()
+        .map(|metadata| metadata.permissions());
+    let temp_path = atomic_temp_path(&path);
+
+    let result = async {
+        tokio::fs::write(&temp_path, contents).await?;
+        if let Some(permissions) = original_permissions {
+            tokio::fs::set_permissions(&temp_path, permissions).await?;
+        }
+        tokio::fs::rename(&temp_path, &path).await
+    }
+    .await;
+    if result.is_err() {
+        let _ = tokio::fs::remove_file(&temp_path).await;
+    }
+    result
+}
+
+/// Blocking counterpart of [`write_atomically`].
+pub fn write_atomically_blocking(path: &Path, contents: &[u8]) -> io::Result<()> {
+    let path = match std::fs::canonicalize(path) {
+        Ok(target) => target,
+        Err(error) if error.kind() == io::ErrorKind::NotFound => path.to_path_buf(),
+        Err(error) => return Err(error),
+    };
+    let original_permissions = std::fs::metadata(&path)
+        .ok()
+        .map(|metadata| metadata.permissions());
+    let temp_path = atomic_temp_path(&path);
+
+    let result = std::fs::write(&temp_path, contents)
+        .and_then(|()| match original_permissions {
+            Some(permissions) => std::fs::set_permissions(&temp_path, permissions),
+            None => Ok(()),
+        })
+        .and_then(|()| std::fs::rename(&temp_path, &path));
+    if result.is_err() {
+        let _ = std::fs::remove_file(&temp_path);
+    }
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::backup::BackupManifest;
+    use crate::project::ProjectRules;
+    use crate::scan::scan_folders;
+
+    /// A folder of `count` programs at S1000, scanned as a run would see it.
+    fn programs(count: usize) -> (tempfile::TempDir, HashMap<PathBuf, FileInfo>) {
+        let names: Vec<String> = (0..count)
+            .map(|index| format!("part{}.tap", index))
+            .collect();
+        let files: Vec<(&str, &str)> = names
+            .iter()
+            .map(|name| (name.as_str(), "G21\nS1000 M3\nM30\n"))
+            .collect();
+        programs_with(&files)
+    }
+
+    /// A folder of the `(name, content)` programs, scanned as a run would see it.
+    fn programs_with(files: &[(&str, &str)]) -> (tempfile::TempDir, HashMap<PathBuf, FileInfo>) {
+        let dir = tempfile::tempdir().unwrap();
+        for (name, content) in files {
+            let path = dir.path().join(name);
+            std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+            std::fs::write(path, content).unwrap();
+        }
+        let roots = [dir.path().to_path_buf()];
+        let (file_cache, _) = scan_folders(&roots, false, &ProjectRules::default()).unwrap();
+        (dir, file_cache)
+    }
+
+    async fn run(
+        plan: &SpeedPlan,
+        options: RunOptions,
+        file_cache: &HashMap<PathBuf, FileInfo>,
+    ) -> RunResults {
+        let (_cancel, cancel_receiver) = oneshot::channel();
+        let progress = Arc::new(Progress::default());
+        update_files(
+            "run1",
+            plan,
+            options,
+            progress,
+            file_cache,
+            cancel_receiver,
+            None,
+        )
+        .await
+        .unwrap()
+    }
+
+    #[tokio::test]
+    async fn only_files_at_the_matched_speed_change() {
+        let (dir, file_cache) = programs_with(&[
+            ("a.tap", "S10000 M3\n"),
+            ("b.tap", "S8000 M3\n"),
+            ("c.tap", "G21\nS10000.0 M3\n"),
+            ("d.tap", "S12000 M3\n"),
+        ]);
+        let options = RunOptions {
+            update: UpdateOptions {
+                match_current_speed: Some(10000),
+                ..UpdateOptions::default()
+            },
+            ..RunOptions::default()
+        };
+
+        let results = run(&SpeedPlan::Uniform(12000), options, &file_cache).await;
+
+        let read = |name: &str| std::fs::read_to_string(dir.path().join(name)).unwrap();
+        assert_eq!(read("a.tap"), "S12000 M3\n");
+        assert_eq!(read("b.tap"), "S8000 M3\n");
+        assert_eq!(read("c.tap"), "G21\nS12000 M3\n");
+        assert_eq!(read("d.tap"), "S12000 M3\n");
+        assert_eq!(results.totals.changed, 2);
+        assert_eq!(crate::summary::matched_count(&results.totals), 2);
+        let mismatched = results
+            .files
+            .iter()
+            .filter(|result| {
+                matches!(
+                    result.outcome,
+                    FileOutcome::Skipped(SkipReason::SpeedMismatch { .. })
+                )
+            })
+            .count();
+        assert_eq!(mismatched, 2);
+    }
+
+    #[tokio::test]
+    async fn empty_and_blank_files_are_skipped_and_never_written() {
+        let (dir, file_cache) = programs_with(&[("empty.tap", ""), ("blank.tap", "\n  \n\t\n")]);
+        let options = RunOptions {
+            update: UpdateOptions {
+                insert_missing: Some(crate::gcode::InsertionPoint::AfterHeader),
+                ..UpdateOptions::default()
+            },
+            ..RunOptions::default()
+        };
+
+        let results = run(&SpeedPlan::Uniform(12000), options, &file_cache).await;
+
+        assert_eq!(results.files.len(), 2);
+        for result in &results.files {
+            assert_eq!(result.outcome, FileOutcome::Skipped(SkipReason::Empty));
+        }
+        assert_eq!(std::fs::read(dir.path().join("empty.tap")).unwrap(), b"");
+        assert_eq!(
+            std::fs::read_to_string(dir.path().join("blank.tap")).unwrap(),
+            "\n  \n\t\n"
+        );
+    }
+
+    #[tokio::test]
+    async fn chunked_run_keeps_only_changed_results_in_memory() {
+        // One program in a hundred needs the new speed; the rest are already at it.
+        let names: Vec<String> = (0..5000)
+            .map(|index| format!("part{}.tap", index))
+            .collect();
+        let files: Vec<(&str, &str)> = names
+            .iter()
+            .enumerate()
+            .map(|(index, name)| {
+                let content = if index % 100 == 0 {
+                    "S1000 M3\n"
+                } else {
+                    "S12000 M3\n"
+                };
+                (name.as_str(), content)
+            })
+            .collect();
+        let (_dir, file_cache) = programs_with(&files);
+        let history = tempfile::tempdir().unwrap();
+        let options = RunOptions {
+            result_chunks: result_chunks(
+                250,
+                Some(&history.path().join("history.csv")),
+                PathDisplay::Absolute,
+            ),
+            ..RunOptions::default()
+        };
+
+        let results = run(&SpeedPlan::Uniform(12000), options, &file_cache).await;
+
+        assert_eq!(results.totals.changed, 50);
+        assert_eq!(results.totals.unchanged, 4950);
+        assert_eq!(results.files.len(), 50);
+        assert!(results.files.capacity() < 500);
+        let results_file = results.results_file.unwrap();
+        let rows = std::fs::read_to_string(results_file).unwrap();
+        // The header, then a row for every file processed.
+        assert_eq!(rows.lines().count(), 5001);
+    }
+
+    /// Runs over a small tree of two programs to change, one of them nested, and one
+    /// already at the speed, writing to a new output folder as configured.
+    async fn run_to_output(
+        copy_unchanged: bool,
+        nest_by_root: bool,
+    ) -> (tempfile::TempDir, tempfile::TempDir) {
+        let (dir, file_cache) = programs_with(&[
+            ("top.tap", "S1000 M3\n"),
+            ("mill/deep/slot.tap", "S1000 M3\n"),
+            ("mill/done.tap", "S12000 M3\n"),
+        ]);
+        let output = tempfile::tempdir().unwrap();
+        let options = RunOptions {
+            output_folder: Some(OutputFolder {
+                folder: output.path().to_path_buf(),
+                copy_unchanged,
+                nest_by_root,
+            }),
+            ..RunOptions::default()
+        };
+
+        run(&SpeedPlan::Uniform(12000), options, &file_cache).await;
+
+        (dir, output)
+    }
+
+    #[tokio::test]
+    async fn output_folder_mirrors_the_input_structure() {
+        let (dir, output) = run_to_output(false, false).await;
+
+        let read = |path: PathBuf| std::fs::read_to_string(path).unwrap();
+        assert_eq!(read(output.path().join("top.tap")), "S12000 M3\n");
+        assert_eq!(
+            read(output.path().join("mill/deep/slot.tap")),
+            "S12000 M3\n"
+        );
+        assert!(!output.path().join("mill/done.tap").exists());
+        // The originals are only read.
+        assert_eq!(read(dir.path().join("top.tap")), "S1000 M3\n");
+        assert_eq!(read(dir.path().join("mill/deep/slot.tap")), "S1000 M3\n");
+    }
+
+    #[tokio::test]
+    async fn output_folder_gets_unchanged_files_when_asked() {
+        let (_dir, output) = run_to_output(true, false).await;
+
+        let done = std::fs::read_to_string(output.path().join("mill/done.tap")).unwrap();
+        assert_eq!(done, "S12000 M3\n");
+    }
+
+    #[tokio::test]
+    async fn output_folder_nests_each_folder_under_its_name() {
+        let (dir, output) = run_to_output(false, true).await;
+
+        let nested = output.path().join(dir.path().file_name().unwrap());
+        assert!(nested.join("top.tap").is_file());
+        assert!(nested.join("mill/deep/slot.tap").is_file());
+        assert!(!output.path().join("top.tap").exists());
+    }
+
+    #[tokio::test]
+    async fn mapping_run_skips_files_it_doesnt_list() {
+        let (dir, file_cache) = programs(2);
+        let speeds = HashMap::from([("part0.tap".to_string(), 9000)]);
+        let plan = SpeedPlan::PerFile(Arc::new(speeds));
+
+        let results = run(&plan, RunOptions::default(), &file_cache).await;
+
+        let outcome = |name: &str| {
+            let path = dir.path().join(name);
+            let result = results.files.iter().find(|result| result.path == path);
+            result.unwrap().outcome.clone()
+        };
+        assert!(matches!(outcome("part0.tap"), FileOutcome::Changed(_)));
+        assert_eq!(
+            outcome("part1.tap"),
+            FileOutcome::Skipped(SkipReason::NotInMapping)
+        );
+        let written = std::fs::read_to_string(dir.path().join("part0.tap")).unwrap();
+        assert_eq!(written, "G21\nS9000 M3\nM30\n");
+    }
+
+    #[tokio::test]
+    async fn backup_of_a_file_that_failed_to_write_is_in_the_manifest() {
+        let (dir, file_cache) = programs(3);
+        let failing = dir.path().join("part1.tap");
+        // A directory in the temporary file's place makes the write fail after the copy.
+        std::fs::create_dir(atomic_temp_path(&failing)).unwrap();
+        let options = RunOptions {
+            create_backups: true,
+            ..RunOptions::default()
+        };
+
+        let results = run(&SpeedPlan::Uniform(12000), options, &file_cache).await;
+
+        let failed = results.files.iter().find(|result| result.path == failing);
+        assert!(matches!(failed.unwrap().outcome, FileOutcome::Error(_)));
+        let manifest = BackupManifest::load(dir.path()).await.unwrap();
+        assert_eq!(manifest.runs.len(), 1);
+        let mut listed: Vec<&PathBuf> = manifest.runs[0]
+            .files
+            .iter()
+            .map(|entry| &entry.original)
+            .collect();
+        listed.sort();
+        let mut expected: Vec<&PathBuf> = file_cache.keys().collect();
+        expected.sort();
+        assert_eq!(listed, expected);
+    }
+
+    #[tokio::test]
+    async fn failed_write_names_the_file() {
+        let (dir, file_cache) = programs(1);
+        let failing = dir.path().join("part0.tap");
+        std::fs::create_dir(atomic_temp_path(&failing)).unwrap();
+
+        let results = run(
+            &SpeedPlan::Uniform(12000),
+            RunOptions::default(),
+            &file_cache,
+        )
+        .await;
+
+        let FileOutcome::Error(message) = &results.files[0].outcome else {
+            panic!("expected an error, got {:?}", results.files[0].outcome);
+        };
+        let prefix = format!("{}: IO error: ", failing.display());
+        assert!(message.starts_with(&prefix), "{}", message);
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn write_atomically_updates_a_symlinks_target() {
+        let dir = tempfile::tempdir().unwrap();
+        let target = dir.path().join("part.nc");
+        let link = dir.path().join("link.nc");
+        std::fs::write(&target, "S8000 M3\n").unwrap();
+        std::os::unix::fs::symlink(&target, &link).unwrap();
+
+        write_atomically(&link, b"S12000 M3\n").await.unwrap();
+
+        assert!(std::fs::symlink_metadata(&link)
+            .unwrap()
+            .file_type()
+            .is_symlink());
+        assert_eq!(std::fs::read_to_string(&target).unwrap(), "S12000 M3\n");
+        assert!(!atomic_temp_path(&target).exists());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn write_atomically_keeps_permissions() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("part.nc");
+        std::fs::write(&path, "S8000 M3\n").unwrap();
+        std::fs::set_permissions(&path, std::fs::Permissions::from_mode(0o755)).unwrap();
+
+        write_atomically_blocking(&path, b"S12000 M3\n").unwrap();
+
+        let mode = std::fs::metadata(&path).unwrap().permissions().mode();
+        assert_eq!(mode & 0o777, 0o755);
+        assert_eq!(std::fs::read_to_string(&path).unwrap(), "S12000 M3\n");
+    }
+
+    #[test]
+    fn changed_file_keeps_its_modified_time_when_asked() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("part.tap");
+        std::fs::write(&path, "S1000 M3\n").unwrap();
+        let archived = filetime::FileTime::from_unix_time(1_600_000_000, 0);
+        filetime::set_file_mtime(&path, archived).unwrap();
+        let options = RunOptions {
+            preserve_metadata: true,
+            ..RunOptions::default()
+        };
+
+        let outcome = update_file_spindle_speed_blocking(&path, 12000, &options, None, None);
+
+        assert!(matches!(outcome.unwrap(), FileOutcome::Changed(_)));
+        assert_eq!(std::fs::read_to_string(&path).unwrap(), "S12000 M3\n");
+        let metadata = std::fs::metadata(&path).unwrap();
+        assert_eq!(
+            filetime::FileTime::from_last_modification_time(&metadata),
+            archived
+        );
+    }
+
+    #[tokio::test]
+    async fn changed_file_gets_a_new_modified_time_by_default() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("part.tap");
+        std::fs::write(&path, "S1000 M3\n").unwrap();
+        let archived = filetime::FileTime::from_unix_time(1_600_000_000, 0);
+        filetime::set_file_mtime(&path, archived).unwrap();
+
+        let options = RunOptions::default();
+        let outcome = update_file_spindle_speed(&path, 12000, &options, None, None).await;
+
+        assert!(matches!(outcome.unwrap(), FileOutcome::Changed(_)));
+        let metadata = std::fs::metadata(&path).unwrap();
+        assert_ne!(
+            filetime::FileTime::from_last_modification_time(&metadata),
+            archived
+        );
+    }
+
+    #[test]
+    fn original_is_copied_to_the_backup_before_it_is_rewritten() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("part.tap");
+        std::fs::write(&path, "S1000 M3\n").unwrap();
+        let backup = backup_path(&path, "run1");
+
+        let outcome = update_file_spindle_speed_blocking(
+            &path,
+            12000,
+            &RunOptions::default(),
+            Some(&backup),
+            None,
+        );
+
+        assert!(matches!(outcome.unwrap(), FileOutcome::Changed(_)));
+        assert_eq!(std::fs::read_to_string(&backup).unwrap(), "S1000 M3\n");
+        assert_eq!(std::fs::read_to_string(&path).unwrap(), "S12000 M3\n");
+    }
+
+    #[tokio::test]
+    async fn failed_backup_leaves_the_file_alone() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("part.tap");
+        std::fs::write(&path, "S1000 M3\n").unwrap();
+        let backup = dir.path().join("missing").join("part.tap.bak");
+
+        let outcome =
+            update_file_spindle_speed(&path, 12000, &RunOptions::default(), Some(&backup), None)
+                .await;
+
+        let FileOutcome::Error(message) = outcome.unwrap() else {
+            panic!("expected the backup to fail");
+        };
+        assert!(message.starts_with("backup failed: "), "{}", message);
+        assert_eq!(std::fs::read_to_string(&path).unwrap(), "S1000 M3\n");
+    }
+
+    #[test]
+    fn file_in_use_is_skipped_and_anything_else_fails() {
+        let path = Path::new("part.tap");
+        let busy = || io::Error::from(io::ErrorKind::ResourceBusy);
+
+        assert_eq!(
+            failure_outcome(path, busy()),
+            FileOutcome::Skipped(SkipReason::InUse)
+        );
+        let denied = io::Error::from(io::ErrorKind::PermissionDenied);
+        assert!(matches!(
+            failure_outcome(path, denied),
+            FileOutcome::Error(_)
+        ));
+        // A backup copy refused because the file is in use skips the file too.
+        let error = backup_failure(busy()).unwrap_err();
+        assert_eq!(
+            failure_outcome(path, error),
+            FileOutcome::Skipped(SkipReason::InUse)
+        );
+        let denied = io::Error::from(io::ErrorKind::PermissionDenied);
+        assert!(matches!(
+            backup_failure(denied),
+            Ok(FileOutcome::Error(message)) if message.starts_with("backup failed: ")
+        ));
+    }
+
+    #[test]
+    fn write_atomically_creates_a_missing_file() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("new.nc");
+
+        write_atomically_blocking(&path, b"S12000 M3\n").unwrap();
+
+        assert_eq!(std::fs::read_to_string(&path).unwrap(), "S12000 M3\n");
+    }
+}
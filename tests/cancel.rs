@@ -0,0 +1,128 @@
+//! Cancelling a scan or a run part way: it stops early, reports the cancel, and leaves
+//! every file either untouched or completely written.
+
+use spindle_speed_manager::mapping::SpeedPlan;
+use spindle_speed_manager::progress::Progress;
+use spindle_speed_manager::project::ProjectRules;
+use spindle_speed_manager::scan::{scan_folders, scan_folders_cancellable};
+use spindle_speed_manager::update::{update_files, RunOptions};
+use spindle_speed_manager::SpindleSpeedUpdaterError;
+use std::fs;
+use std::path::PathBuf;
+use std::sync::atomic::AtomicBool;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::oneshot;
+
+const FILES: usize = 50;
+const ORIGINAL: &str = "G21\nS1000 M3\nM30\n";
+const UPDATED: &str = "G21\nS12000 M3\nM30\n";
+
+fn job_folder() -> tempfile::TempDir {
+    let dir = tempfile::tempdir().unwrap();
+    for index in 0..FILES {
+        fs::write(dir.path().join(format!("part{:02}.tap", index)), ORIGINAL).unwrap();
+    }
+    dir
+}
+
+#[tokio::test]
+async fn cancelled_run_stops_between_files() {
+    let dir = job_folder();
+    let roots = [dir.path().to_path_buf()];
+    let (file_cache, _) = scan_folders(&roots, false, &ProjectRules::default()).unwrap();
+    let options = RunOptions {
+        // Long enough for the cancel to land while the run is still going.
+        write_delay: Duration::from_millis(20),
+        ..RunOptions::default()
+    };
+    let progress = Arc::new(Progress::default());
+    let (cancel_sender, cancel_receiver) = oneshot::channel();
+
+    let watcher = {
+        let progress = Arc::clone(&progress);
+        tokio::spawn(async move {
+            while progress.counts().0 < 3 {
+                tokio::time::sleep(Duration::from_millis(1)).await;
+            }
+            cancel_sender.send(()).unwrap();
+        })
+    };
+    let result = update_files(
+        "run1",
+        &SpeedPlan::Uniform(12000),
+        options,
+        Arc::clone(&progress),
+        &file_cache,
+        cancel_receiver,
+        None,
+    )
+    .await;
+    watcher.await.unwrap();
+
+    assert!(
+        matches!(result, Err(SpindleSpeedUpdaterError::CancelError(_))),
+        "{:?}",
+        result.map(|results| results.totals.processed())
+    );
+    let (processed, total) = progress.counts();
+    assert_eq!(total, FILES);
+    assert!((3..FILES).contains(&processed), "{} processed", processed);
+
+    let mut updated = 0;
+    for entry in fs::read_dir(dir.path()).unwrap() {
+        let path: PathBuf = entry.unwrap().path();
+        let name = path.file_name().unwrap().to_string_lossy().into_owned();
+        assert!(!name.ends_with(".spindle-tmp"), "{} left behind", name);
+        match fs::read_to_string(&path).unwrap().as_str() {
+            UPDATED => updated += 1,
+            ORIGINAL => {}
+            other => panic!("{} partly written: {:?}", name, other),
+        }
+    }
+    assert_eq!(updated, processed);
+}
+
+#[tokio::test]
+async fn run_cancelled_before_the_first_file_writes_nothing() {
+    let dir = job_folder();
+    let roots = [dir.path().to_path_buf()];
+    let (file_cache, _) = scan_folders(&roots, false, &ProjectRules::default()).unwrap();
+    let (cancel_sender, cancel_receiver) = oneshot::channel();
+    cancel_sender.send(()).unwrap();
+
+    let progress = Arc::new(Progress::default());
+    let result = update_files(
+        "run1",
+        &SpeedPlan::Uniform(12000),
+        RunOptions::default(),
+        Arc::clone(&progress),
+        &file_cache,
+        cancel_receiver,
+        None,
+    )
+    .await;
+
+    assert!(matches!(
+        result,
+        Err(SpindleSpeedUpdaterError::CancelError(_))
+    ));
+    assert_eq!(progress.counts(), (0, FILES));
+    for entry in fs::read_dir(dir.path()).unwrap() {
+        assert_eq!(fs::read_to_string(entry.unwrap().path()).unwrap(), ORIGINAL);
+    }
+}
+
+#[test]
+fn cancelled_scan_reports_the_cancel() {
+    let dir = job_folder();
+    let roots = [dir.path().to_path_buf()];
+    let cancel = AtomicBool::new(true);
+
+    let result = scan_folders_cancellable(&roots, false, &ProjectRules::default(), &cancel);
+
+    assert!(matches!(
+        result,
+        Err(SpindleSpeedUpdaterError::CancelError(_))
+    ));
+}
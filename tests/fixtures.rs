@@ -0,0 +1,160 @@
+//! Every post-processor sample in `fixtures/` updated to 12000 RPM, against the result
+//! expected for its dialect, and a folder of them updated twice.
+
+use spindle_speed_manager::gcode::{
+    rewrite_content, update_spindle_speed_in_content, SkipReason, UpdateOptions,
+};
+use spindle_speed_manager::mapping::SpeedPlan;
+use spindle_speed_manager::progress::Progress;
+use spindle_speed_manager::project::ProjectRules;
+use spindle_speed_manager::report::RunResults;
+use spindle_speed_manager::scan::scan_folders;
+use spindle_speed_manager::update::{update_files, FileOutcome, RunOptions};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+/// The speed every expected fixture was produced with.
+const FIXTURE_SPEED: u32 = 12000;
+
+fn fixture(name: &str) -> PathBuf {
+    Path::new(env!("CARGO_MANIFEST_DIR"))
+        .join("fixtures")
+        .join(name)
+}
+
+fn update(name: &str) -> String {
+    let input = fs::read_to_string(fixture(name)).unwrap();
+    update_spindle_speed_in_content(&input, FIXTURE_SPEED, &UpdateOptions::default()).unwrap()
+}
+
+fn assert_dialect(dialect: &str) {
+    let expected = fs::read_to_string(fixture(&format!("{}.expected.tap", dialect))).unwrap();
+    assert_eq!(update(&format!("{}.tap", dialect)), expected);
+}
+
+#[test]
+fn fanuc() {
+    assert_dialect("fanuc");
+}
+
+#[test]
+fn linuxcnc() {
+    assert_dialect("linuxcnc");
+}
+
+#[test]
+fn mach3() {
+    assert_dialect("mach3");
+}
+
+#[test]
+fn haas() {
+    assert_dialect("haas");
+}
+
+#[test]
+fn spaced() {
+    assert_dialect("spaced");
+}
+
+#[test]
+fn canned() {
+    assert_dialect("canned");
+}
+
+/// A sample added to `fixtures/` without a test above still gets checked.
+#[test]
+fn every_expected_file_has_its_sample_updated_to_it() {
+    for entry in fs::read_dir(fixture("")).unwrap() {
+        let name = entry.unwrap().file_name().to_string_lossy().into_owned();
+        if let Some(dialect) = name.strip_suffix(".expected.tap") {
+            assert_dialect(dialect);
+        }
+    }
+}
+
+fn skip_reason(name: &str) -> SkipReason {
+    let input = fs::read_to_string(fixture(name)).unwrap();
+    rewrite_content(&input, FIXTURE_SPEED, &UpdateOptions::default()).unwrap_err()
+}
+
+#[test]
+fn continued_block_is_skipped() {
+    let reason = skip_reason("continued.tap");
+    assert!(
+        matches!(reason, SkipReason::ContinuedBlock { line: 6 }),
+        "{}",
+        reason
+    );
+}
+
+#[test]
+fn scientific_notation_is_skipped() {
+    let reason = skip_reason("scientific.tap");
+    assert!(
+        matches!(reason, SkipReason::UnsupportedNotation { .. }),
+        "{}",
+        reason
+    );
+}
+
+async fn update_folder(folder: &Path) -> RunResults {
+    let roots = [folder.to_path_buf()];
+    let (file_cache, _) = scan_folders(&roots, false, &ProjectRules::default()).unwrap();
+    let (_cancel, cancel_receiver) = tokio::sync::oneshot::channel();
+    update_files(
+        "run1",
+        &SpeedPlan::Uniform(FIXTURE_SPEED),
+        RunOptions::default(),
+        Arc::new(Progress::default()),
+        &file_cache,
+        cancel_receiver,
+        None,
+    )
+    .await
+    .unwrap()
+}
+
+/// A second run at the same speed over the updated samples finds every file already
+/// correct and leaves its bytes alone.
+#[tokio::test]
+async fn second_run_over_the_samples_changes_nothing() {
+    let dir = tempfile::tempdir().unwrap();
+    for entry in fs::read_dir(fixture("")).unwrap() {
+        let path = entry.unwrap().path();
+        if path.extension().is_some_and(|extension| extension == "tap") {
+            fs::copy(&path, dir.path().join(path.file_name().unwrap())).unwrap();
+        }
+    }
+
+    let first = update_folder(dir.path()).await;
+    assert!(first.totals.changed > 0);
+    let written: Vec<(PathBuf, Vec<u8>)> = first
+        .files
+        .iter()
+        .map(|result| (result.path.clone(), fs::read(&result.path).unwrap()))
+        .collect();
+
+    let second = update_folder(dir.path()).await;
+    assert_eq!(second.totals.changed, 0);
+    for result in &second.files {
+        assert!(
+            !matches!(result.outcome, FileOutcome::Changed(_)),
+            "{:?} changed again",
+            result.path
+        );
+    }
+    for (path, bytes) in written {
+        assert_eq!(fs::read(&path).unwrap(), bytes, "{:?}", path);
+    }
+}
+
+/// What `scientific.tap` should become once `S1.0E3` can be rewritten safely.
+#[test]
+#[ignore = "S1.0E3 is skipped as an unsupported notation for now"]
+fn scientific_notation_is_rewritten() {
+    let input = fs::read_to_string(fixture("scientific.tap")).unwrap();
+    let expected = input.replacen("S1.0E3", "S12000", 1);
+    assert_eq!(update("scientific.tap"), expected);
+}
@@ -0,0 +1,81 @@
+//! Building the file cache over a generated tree of several thousand programs stays
+//! within a time budget, as a guard against a quadratic pass creeping into the scan.
+
+use spindle_speed_manager::project::ProjectRules;
+use spindle_speed_manager::scan::{scan_folders, scan_folders_cancellable};
+use spindle_speed_manager::SpindleSpeedUpdaterError;
+use std::fs;
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::{Duration, Instant};
+
+const FOLDERS: usize = 50;
+const FILES_PER_FOLDER: usize = 100;
+/// Far above what a linear scan of the tree takes, even on a slow CI disk.
+const BUDGET: Duration = Duration::from_secs(10);
+
+fn generated_tree() -> tempfile::TempDir {
+    let dir = tempfile::tempdir().unwrap();
+    for folder in 0..FOLDERS {
+        let folder = dir.path().join(format!("job{:02}", folder));
+        fs::create_dir(&folder).unwrap();
+        for file in 0..FILES_PER_FOLDER {
+            fs::write(folder.join(format!("part{:03}.tap", file)), "S1000 M3\n").unwrap();
+            fs::write(folder.join(format!("notes{:03}.txt", file)), "").unwrap();
+        }
+    }
+    dir
+}
+
+#[test]
+fn cache_of_thousands_of_files_builds_within_budget() {
+    let dir = generated_tree();
+    let root = dir.path().to_path_buf();
+
+    let started = Instant::now();
+    let (file_cache, _) = scan_folders(&[root], false, &ProjectRules::default()).unwrap();
+    let elapsed = started.elapsed();
+
+    assert_eq!(file_cache.len(), FOLDERS * FILES_PER_FOLDER);
+    assert!(elapsed < BUDGET, "scan took {:?}", elapsed);
+}
+
+/// Several roots turn on deduplication, which canonicalizes every path.
+#[test]
+fn deduplicated_cache_builds_within_budget() {
+    let dir = generated_tree();
+    let roots: Vec<PathBuf> = (0..FOLDERS)
+        .map(|folder| dir.path().join(format!("job{:02}", folder)))
+        .collect();
+
+    let started = Instant::now();
+    let (file_cache, duplicates) = scan_folders(&roots, false, &ProjectRules::default()).unwrap();
+    let elapsed = started.elapsed();
+
+    assert_eq!(file_cache.len(), FOLDERS * FILES_PER_FOLDER);
+    assert!(duplicates.is_empty());
+    assert!(elapsed < BUDGET, "scan took {:?}", elapsed);
+}
+
+#[test]
+fn scan_of_a_large_tree_stops_soon_after_a_cancel() {
+    let dir = generated_tree();
+    let root = dir.path().to_path_buf();
+    let cancel = AtomicBool::new(false);
+
+    let started = Instant::now();
+    let result = std::thread::scope(|scope| {
+        scope.spawn(|| {
+            std::thread::sleep(Duration::from_millis(5));
+            cancel.store(true, Ordering::Relaxed);
+        });
+        scan_folders_cancellable(&[root], false, &ProjectRules::default(), &cancel)
+    });
+
+    // A scan quick enough to finish before the cancel is fine too.
+    match result {
+        Ok((file_cache, _)) => assert_eq!(file_cache.len(), FOLDERS * FILES_PER_FOLDER),
+        Err(error) => assert!(matches!(error, SpindleSpeedUpdaterError::CancelError(_))),
+    }
+    assert!(started.elapsed() < BUDGET);
+}